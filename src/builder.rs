@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+
+use crate::dom::{validate_elements, Element, Root, TemplateError, Value};
+
+/// Builds template elements from code, as an alternative to writing DSL text.
+///
+/// ```
+/// use delver::builder::{ElementBuilder, TemplateBuilder};
+///
+/// let root = TemplateBuilder::new()
+///     .element(
+///         ElementBuilder::section("risks")
+///             .match_pattern("Risk Factors")
+///             .end_match("Unresolved Staff Comments")
+///             .child(ElementBuilder::text_chunk().chunk_size(500).overlap(150)),
+///     )
+///     .build()
+///     .unwrap();
+/// assert_eq!(root.elements[0].children.len(), 1);
+/// ```
+#[derive(Debug, Default)]
+pub struct TemplateBuilder {
+    elements: Vec<Element>,
+}
+
+impl TemplateBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn element(mut self, element: ElementBuilder) -> Self {
+        self.elements.push(element.build());
+        self
+    }
+
+    /// Finish the template, applying the same checks as `parse_template`.
+    pub fn build(self) -> Result<Root, TemplateError> {
+        validate_elements(&self.elements)?;
+        Ok(Root {
+            elements: self.elements,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ElementBuilder {
+    element: Element,
+}
+
+impl ElementBuilder {
+    pub fn new(name: &str) -> Self {
+        ElementBuilder {
+            element: Element {
+                name: name.to_string(),
+                attributes: HashMap::new(),
+                children: Vec::new(),
+            },
+        }
+    }
+
+    /// A `Section` whose matches are referred to as `alias`.
+    pub fn section(alias: &str) -> Self {
+        Self::new("Section").attribute("as", Value::String(alias.to_string()))
+    }
+
+    pub fn text_chunk() -> Self {
+        Self::new("TextChunk")
+    }
+
+    pub fn attribute(mut self, key: &str, value: Value) -> Self {
+        self.element.attributes.insert(key.to_string(), value);
+        self
+    }
+
+    pub fn match_pattern(self, pattern: &str) -> Self {
+        self.attribute("match", Value::String(pattern.to_string()))
+    }
+
+    pub fn end_match(self, pattern: &str) -> Self {
+        self.attribute("end_match", Value::String(pattern.to_string()))
+    }
+
+    pub fn threshold(self, threshold: f64) -> Self {
+        self.attribute("threshold", Value::Float(threshold))
+    }
+
+    pub fn chunk_size(self, size: i64) -> Self {
+        self.attribute("chunkSize", Value::Number(size))
+    }
+
+    pub fn overlap(self, overlap: i64) -> Self {
+        self.attribute("chunkOverlap", Value::Number(overlap))
+    }
+
+    /// Attach the metadata of the sections with these aliases.
+    pub fn add_meta(self, aliases: &[&str]) -> Self {
+        let aliases = aliases
+            .iter()
+            .map(|alias| Value::Identifier(alias.to_string()))
+            .collect();
+        self.attribute("addMeta", Value::Array(aliases))
+    }
+
+    /// Keep only lines matching this regex.
+    pub fn include_pattern(self, pattern: &str) -> Self {
+        self.attribute("includePattern", Value::String(pattern.to_string()))
+    }
+
+    /// Only look for this element from `page` (1-based) on.
+    pub fn page_start(self, page: u32) -> Self {
+        self.attribute("pageStart", Value::Number(page.into()))
+    }
+
+    /// Only look for this element up to and including `page`.
+    pub fn page_end(self, page: u32) -> Self {
+        self.attribute("pageEnd", Value::Number(page.into()))
+    }
+
+    /// Drop lines matching this regex.
+    pub fn exclude_pattern(self, pattern: &str) -> Self {
+        self.attribute("excludePattern", Value::String(pattern.to_string()))
+    }
+
+    /// Only match text at least `size` points tall.
+    pub fn min_font_size(self, size: f64) -> Self {
+        self.attribute("minFontSize", Value::Float(size))
+    }
+
+    /// Only match text at most `size` points tall.
+    pub fn max_font_size(self, size: f64) -> Self {
+        self.attribute("maxFontSize", Value::Float(size))
+    }
+
+    /// Only match text in a font whose name contains `name`.
+    pub fn font_name(self, name: &str) -> Self {
+        self.attribute("fontName", Value::String(name.to_string()))
+    }
+
+    /// Only look inside this part of the page, given as fractions from the top-left
+    /// corner.
+    pub fn region(self, x0: f64, y0: f64, x1: f64, y1: f64) -> Self {
+        let corners = [x0, y0, x1, y1].into_iter().map(Value::Float).collect();
+        self.attribute("region", Value::Array(corners))
+    }
+
+    /// Only match text filled in this `#rrggbb` colour.
+    pub fn color(self, color: &str) -> Self {
+        self.attribute("color", Value::String(color.to_string()))
+    }
+
+    /// Drop text filled in this `#rrggbb` colour.
+    pub fn exclude_color(self, color: &str) -> Self {
+        self.attribute("excludeColor", Value::String(color.to_string()))
+    }
+
+    /// Only match text whose font is (or, with false, isn't) bold.
+    pub fn bold(self, bold: bool) -> Self {
+        self.attribute("bold", Value::Boolean(bold))
+    }
+
+    pub fn child(mut self, child: ElementBuilder) -> Self {
+        self.element.children.push(child.build());
+        self
+    }
+
+    pub fn build(self) -> Element {
+        self.element
+    }
+}
@@ -1,37 +1,54 @@
+use log::{debug, warn};
 use lopdf::{Dictionary, Document, Encoding, Error as LopdfError, Object, Result as LopdfResult};
+use pest::error::{Error as PestError, LineColLocation};
 use pest::iterators::Pair;
 use pest::Parser as PestParser;
 use pest_derive::Parser as PestParserDerive;
-use std::{
-    collections::{BTreeMap, HashMap},
-    io::Error,
-};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use thiserror::Error;
+
+use crate::error::DelverError;
 
 #[derive(PestParserDerive)]
 #[grammar = "template.pest"]
 pub struct TemplateParser;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Root {
     pub elements: Vec<Element>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Element {
     pub name: String,
     pub attributes: HashMap<String, Value>,
     pub children: Vec<Element>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
 pub enum Value {
     String(String),
     Number(i64),
+    Float(f64),
     Boolean(bool),
     Array(Vec<Value>),
     Identifier(String),
 }
 
+impl Value {
+    /// Numeric value of either `Number` or `Float`.
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n as f64),
+            Value::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct DocumentElement {
     pub element_type: ElementType,
@@ -56,26 +73,410 @@ pub struct MatchedElement {
     pub metadata: HashMap<String, Value>,
 }
 
-pub fn parse_template(template_str: &str) -> Result<Root, Error> {
-    let pairs = TemplateParser::parse(Rule::template, template_str)
-        .expect("Failed to parse template")
+/// A syntax or validation error in a template, located by 1-based line and column.
+/// JSON templates only report a position for syntax errors; validation errors there
+/// have line and column 0.
+#[derive(Debug, Error)]
+#[error("line {line}, column {column}: {message}")]
+pub struct TemplateError {
+    pub line: usize,
+    pub column: usize,
+    pub element: Option<String>,
+    pub message: String,
+}
+
+impl From<PestError<Rule>> for TemplateError {
+    fn from(error: PestError<Rule>) -> Self {
+        let (line, column) = match error.line_col {
+            LineColLocation::Pos(pos) => pos,
+            LineColLocation::Span(start, _) => start,
+        };
+        TemplateError {
+            line,
+            column,
+            element: None,
+            message: error.variant.message().into_owned(),
+        }
+    }
+}
+
+/// Attributes each element type understands, or None for unknown elements.
+fn allowed_attributes(element: &str) -> Option<&'static [&'static str]> {
+    match element {
+        "Section" => Some(&[
+            "match",
+            "end_match",
+            "as",
+            "threshold",
+            "pageStart",
+            "pageEnd",
+            "minFontSize",
+            "maxFontSize",
+            "fontName",
+            "bold",
+            "color",
+            "region",
+        ]),
+        "Paragraph" => Some(&["match", "as"]),
+        "TextChunk" => Some(&[
+            "chunkSize",
+            "chunkOverlap",
+            "addMeta",
+            "includePattern",
+            "excludePattern",
+            "pageStart",
+            "pageEnd",
+            "region",
+            "excludeColor",
+        ]),
+        _ => None,
+    }
+}
+
+pub fn parse_template(template_str: &str) -> Result<Root, TemplateError> {
+    let pairs = TemplateParser::parse(Rule::template, template_str)?
         .next()
         .unwrap();
-    Ok(_parse_template(pairs))
+    validate_template(pairs.clone())?;
+    let root = _parse_template(pairs)?;
+    // Values are checked once fragments are expanded, so parameters are checked too
+    validate_elements(&root.elements)?;
+    Ok(root)
+}
+
+/// Reject unknown element names and attributes before the template is built.
+fn validate_template(pair: Pair<Rule>) -> Result<(), TemplateError> {
+    for inner_pair in pair.into_inner() {
+        if inner_pair.as_rule() != Rule::element {
+            validate_template(inner_pair)?;
+            continue;
+        }
+
+        let mut element_rules = inner_pair.into_inner();
+        let identifier = element_rules.next().unwrap();
+        let name = identifier.as_str();
+        let error = |pair: &Pair<Rule>, message: String| error_at(pair, Some(name), message);
+        let allowed =
+            allowed_attributes(name).ok_or_else(|| error(&identifier, unknown_element(name)))?;
+
+        for rule in element_rules {
+            if rule.as_rule() == Rule::attributes {
+                for attribute in rule.into_inner().flat_map(|list| list.into_inner()) {
+                    let key = attribute.into_inner().next().unwrap();
+                    if !allowed.contains(&key.as_str()) {
+                        return Err(error(&key, unknown_attribute(name, key.as_str(), allowed)));
+                    }
+                }
+            } else {
+                validate_template(rule)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Attributes whose values are checked when the template loads, not just their names.
+fn has_checked_value(key: &str) -> bool {
+    matches!(
+        key,
+        "match"
+            | "end_match"
+            | "includePattern"
+            | "excludePattern"
+            | "pageStart"
+            | "pageEnd"
+            | "minFontSize"
+            | "maxFontSize"
+            | "fontName"
+            | "bold"
+            | "region"
+            | "color"
+            | "excludeColor"
+    )
+}
+
+fn check_attribute_value(key: &str, value: &Value) -> Result<(), String> {
+    match (key, value) {
+        ("match" | "end_match", Value::String(_)) => Ok(()),
+        ("match" | "end_match", Value::Array(values)) => check_match(key, values),
+        ("match" | "end_match", _) => Err(format!(
+            "`{key}` must be a string or [pattern, threshold, mode]"
+        )),
+        ("includePattern" | "excludePattern", Value::String(pattern)) => regex::Regex::new(pattern)
+            .map(|_| ())
+            .map_err(|e| format!("invalid regex in `{key}`: {e}")),
+        ("includePattern" | "excludePattern", _) => Err(format!("`{key}` must be a string")),
+        ("pageStart" | "pageEnd", Value::Number(page)) if *page >= 1 => Ok(()),
+        ("pageStart" | "pageEnd", _) => Err(format!("`{key}` must be a page number from 1")),
+        ("minFontSize" | "maxFontSize", _) if value.as_number().is_some_and(|size| size > 0.0) => {
+            Ok(())
+        }
+        ("minFontSize" | "maxFontSize", _) => Err(format!("`{key}` must be a positive size")),
+        ("fontName", Value::String(_)) | ("bold", Value::Boolean(_)) => Ok(()),
+        ("fontName", _) => Err(format!("`{key}` must be a string")),
+        ("bold", _) => Err(format!("`{key}` must be true or false")),
+        ("region", _) => check_region(value),
+        ("color" | "excludeColor", Value::String(color)) if parse_hex_color(color).is_some() => {
+            Ok(())
+        }
+        ("color" | "excludeColor", _) => Err(format!("`{key}` must be a colour like \"#ff0000\"")),
+        _ => Ok(()),
+    }
+}
+
+/// `[pattern]`, `[pattern, threshold]` or `[pattern, threshold, mode]`, where the
+/// mode is "text" or "regex". A regex must compile.
+fn check_match(key: &str, values: &[Value]) -> Result<(), String> {
+    let threshold = |value: &Value| value.as_number().is_some();
+    let (pattern, mode) = match values {
+        [Value::String(pattern)] => (pattern, "text"),
+        [Value::String(pattern), t] if threshold(t) => (pattern, "text"),
+        [Value::String(pattern), t, Value::String(mode)] if threshold(t) => {
+            (pattern, mode.as_str())
+        }
+        _ => {
+            return Err(format!(
+                "`{key}` must be a string or [pattern, threshold, mode]"
+            ))
+        }
+    };
+    match mode {
+        "text" => Ok(()),
+        "regex" => regex::Regex::new(pattern)
+            .map(|_| ())
+            .map_err(|e| format!("invalid regex in `{key}`: {e}")),
+        mode => Err(format!(
+            "unknown match mode \"{mode}\" in `{key}`, expected \"text\" or \"regex\""
+        )),
+    }
+}
+
+/// An RGB colour written `#rrggbb`.
+pub fn parse_hex_color(color: &str) -> Option<(u8, u8, u8)> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// `[x0, y0, x1, y1]`, fractions of the page with the first corner above and to
+/// the left of the second.
+fn check_region(value: &Value) -> Result<(), String> {
+    let fractions = match value {
+        Value::Array(values) => values
+            .iter()
+            .map(Value::as_number)
+            .collect::<Option<Vec<_>>>(),
+        _ => None,
+    };
+    match fractions.as_deref() {
+        Some(&[x0, y0, x1, y1])
+            if [x0, y0, x1, y1].iter().all(|f| (0.0..=1.0).contains(f)) && x0 < x1 && y0 < y1 =>
+        {
+            Ok(())
+        }
+        _ => Err(
+            "`region` must be [x0, y0, x1, y1] with fractions from 0 to 1, x0 < x1 and y0 < y1"
+                .to_string(),
+        ),
+    }
+}
+
+fn unknown_element(name: &str) -> String {
+    format!("unknown element `{name}`")
+}
+
+fn unknown_attribute(name: &str, key: &str, allowed: &[&str]) -> String {
+    format!(
+        "unknown attribute `{key}` on {name}, expected one of: {}",
+        allowed.join(", ")
+    )
+}
+
+/// Read and parse a template file, in either the DSL or JSON.
+pub fn load_template<P: AsRef<Path>>(path: P) -> Result<Root, DelverError> {
+    let template_str = std::fs::read_to_string(path)?;
+    Ok(parse_template_auto(&template_str)?)
+}
+
+/// Parse a template given either in the DSL or as JSON, picked by the first
+/// non-whitespace character (`{` or `[` means JSON).
+pub fn parse_template_auto(template_str: &str) -> Result<Root, TemplateError> {
+    match template_str.trim_start().chars().next() {
+        Some('{') | Some('[') => parse_template_json(template_str),
+        _ => parse_template(template_str),
+    }
+}
+
+/// Parse a template written as JSON, either `{"elements": [...]}` or a bare array of
+/// elements. Each element is an object with a `type` (the DSL element name) and
+/// optional `attributes` and `children`. Attribute values are JSON strings, numbers,
+/// booleans and arrays; `{"identifier": "name"}` stands for a bare DSL identifier.
+///
+/// ```json
+/// [{"type": "Section", "attributes": {"match": "Risk Factors", "as": "risks"},
+///   "children": [{"type": "TextChunk", "attributes": {"chunkSize": 500}}]}]
+/// ```
+pub fn parse_template_json(template_str: &str) -> Result<Root, TemplateError> {
+    let elements: Vec<JsonElement> = if template_str.trim_start().starts_with('[') {
+        serde_json::from_str(template_str)
+    } else {
+        serde_json::from_str::<JsonRoot>(template_str).map(|root| root.elements)
+    }
+    .map_err(|e| TemplateError {
+        line: e.line(),
+        column: e.column(),
+        element: None,
+        message: e.to_string(),
+    })?;
+
+    let elements: Vec<Element> = elements.into_iter().map(Element::from).collect();
+    validate_elements(&elements)?;
+    Ok(Root { elements })
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonRoot {
+    elements: Vec<JsonElement>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonElement {
+    #[serde(rename = "type")]
+    element_type: String,
+    #[serde(default)]
+    attributes: HashMap<String, JsonValue>,
+    #[serde(default)]
+    children: Vec<JsonElement>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonValue {
+    Identifier { identifier: String },
+    Boolean(bool),
+    Number(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+}
+
+impl From<JsonElement> for Element {
+    fn from(element: JsonElement) -> Self {
+        Element {
+            name: element.element_type,
+            attributes: element
+                .attributes
+                .into_iter()
+                .map(|(key, value)| (key, value.into()))
+                .collect(),
+            children: element.children.into_iter().map(Element::from).collect(),
+        }
+    }
+}
+
+impl From<JsonValue> for Value {
+    fn from(value: JsonValue) -> Self {
+        match value {
+            JsonValue::Identifier { identifier } => Value::Identifier(identifier),
+            JsonValue::Boolean(b) => Value::Boolean(b),
+            JsonValue::Number(n) => Value::Number(n),
+            JsonValue::Float(f) => Value::Float(f),
+            JsonValue::String(s) => Value::String(s),
+            JsonValue::Array(values) => Value::Array(values.into_iter().map(Value::from).collect()),
+        }
+    }
 }
 
-fn _parse_template(pair: Pair<Rule>) -> Root {
+/// Check element names, attribute names and attribute values of a built tree, either
+/// from JSON or from the DSL once its fragments are expanded.
+pub(crate) fn validate_elements(elements: &[Element]) -> Result<(), TemplateError> {
+    for element in elements {
+        let error = |message: String| TemplateError {
+            line: 0,
+            column: 0,
+            element: Some(element.name.clone()),
+            message,
+        };
+        let allowed = allowed_attributes(&element.name)
+            .ok_or_else(|| error(unknown_element(&element.name)))?;
+        // Sorted so the reported key doesn't depend on hash order
+        let mut keys: Vec<&String> = element.attributes.keys().collect();
+        keys.sort();
+        if let Some(key) = keys.iter().find(|key| !allowed.contains(&key.as_str())) {
+            return Err(error(unknown_attribute(&element.name, key, allowed)));
+        }
+        for key in keys.into_iter().filter(|key| has_checked_value(key)) {
+            check_attribute_value(key, &element.attributes[key]).map_err(error)?;
+        }
+        validate_elements(&element.children)?;
+    }
+    Ok(())
+}
+
+fn error_at(pair: &Pair<Rule>, element: Option<&str>, message: String) -> TemplateError {
+    let (line, column) = pair.line_col();
+    TemplateError {
+        line,
+        column,
+        element: element.map(str::to_string),
+        message,
+    }
+}
+
+/// A `define` block: its parameter names and the body expanded at each `use`.
+struct Fragment<'a> {
+    parameters: Vec<String>,
+    body: Pair<'a, Rule>,
+}
+
+/// Fragment definitions plus the names currently being expanded, to catch recursion.
+struct Fragments<'a> {
+    definitions: HashMap<String, Fragment<'a>>,
+    expanding: Vec<String>,
+}
+
+fn _parse_template(pair: Pair<Rule>) -> Result<Root, TemplateError> {
     let mut elements = Vec::new();
 
     match pair.as_rule() {
         Rule::template => {
-            for inner_pair in pair.into_inner() {
-                match inner_pair.as_rule() {
-                    Rule::expression => {
-                        let element = process_element(inner_pair);
-                        elements.push(element);
+            let mut fragments = Fragments {
+                definitions: HashMap::new(),
+                expanding: Vec::new(),
+            };
+            // Collect every definition first so fragments can be used before they're defined
+            for inner_pair in pair.clone().into_inner() {
+                if inner_pair.as_rule() == Rule::definition {
+                    let mut definition = inner_pair.into_inner();
+                    let _keyword = definition.next();
+                    let name = definition.next().unwrap();
+                    let mut parameters = Vec::new();
+                    let mut body = definition.next().unwrap();
+                    if body.as_rule() == Rule::parameter_list {
+                        parameters = body.into_inner().map(|p| p.as_str().to_string()).collect();
+                        body = definition.next().unwrap();
+                    }
+                    if fragments.definitions.contains_key(name.as_str()) {
+                        return Err(error_at(
+                            &name,
+                            None,
+                            format!("fragment `{}` is defined more than once", name.as_str()),
+                        ));
                     }
-                    _ => {}
+                    fragments
+                        .definitions
+                        .insert(name.as_str().to_string(), Fragment { parameters, body });
+                }
+            }
+
+            for inner_pair in pair.into_inner() {
+                if inner_pair.as_rule() == Rule::expression {
+                    elements.extend(process_expression(inner_pair, &mut fragments)?);
                 }
             }
         }
@@ -84,18 +485,107 @@ fn _parse_template(pair: Pair<Rule>) -> Root {
         }
     }
 
-    Root { elements }
+    Ok(Root { elements })
 }
 
-fn process_element(pair: Pair<Rule>) -> Element {
-    // If we receive an expression, get the element inside it
-    let element_pair = if pair.as_rule() == Rule::expression {
-        pair.into_inner().next().unwrap()
-    } else {
-        pair
-    };
+fn process_expression(
+    pair: Pair<Rule>,
+    fragments: &mut Fragments,
+) -> Result<Vec<Element>, TemplateError> {
+    let inner_pair = pair.into_inner().next().unwrap();
+    match inner_pair.as_rule() {
+        Rule::fragment_use => expand_fragment(inner_pair, fragments),
+        _ => Ok(vec![process_element(inner_pair, fragments)?]),
+    }
+}
+
+/// Expand `use Name(param=value, ...)` into copies of the fragment's elements with
+/// each identifier naming a parameter replaced by its argument.
+fn expand_fragment(
+    pair: Pair<Rule>,
+    fragments: &mut Fragments,
+) -> Result<Vec<Element>, TemplateError> {
+    let mut inner_rules = pair.clone().into_inner();
+    let _keyword = inner_rules.next();
+    let name = inner_rules.next().unwrap().as_str().to_string();
+    let arguments = inner_rules
+        .next()
+        .map(|arguments| process_attributes(arguments, None))
+        .transpose()?
+        .unwrap_or_default();
+
+    let fragment = fragments
+        .definitions
+        .get(&name)
+        .ok_or_else(|| error_at(&pair, None, format!("unknown fragment `{name}`")))?;
+    if fragments.expanding.contains(&name) {
+        return Err(error_at(
+            &pair,
+            None,
+            format!(
+                "fragment `{name}` uses itself: {} -> {name}",
+                fragments.expanding.join(" -> ")
+            ),
+        ));
+    }
+    if let Some(missing) = fragment
+        .parameters
+        .iter()
+        .find(|p| !arguments.contains_key(*p))
+    {
+        return Err(error_at(
+            &pair,
+            None,
+            format!("missing argument `{missing}` for fragment `{name}`"),
+        ));
+    }
+    if let Some(extra) = arguments.keys().find(|a| !fragment.parameters.contains(a)) {
+        return Err(error_at(
+            &pair,
+            None,
+            format!("fragment `{name}` has no parameter `{extra}`"),
+        ));
+    }
+
+    let body = fragment.body.clone();
+    fragments.expanding.push(name);
+    let mut elements = Vec::new();
+    for expr in body.into_inner() {
+        if expr.as_rule() == Rule::expression {
+            elements.extend(process_expression(expr, fragments)?);
+        }
+    }
+    fragments.expanding.pop();
 
-    let mut inner_rules = element_pair.into_inner();
+    for element in &mut elements {
+        substitute_arguments(element, &arguments);
+    }
+    Ok(elements)
+}
+
+fn substitute_arguments(element: &mut Element, arguments: &HashMap<String, Value>) {
+    fn substitute(value: &mut Value, arguments: &HashMap<String, Value>) {
+        match value {
+            Value::Identifier(name) => {
+                if let Some(argument) = arguments.get(name) {
+                    *value = argument.clone();
+                }
+            }
+            Value::Array(values) => values.iter_mut().for_each(|v| substitute(v, arguments)),
+            _ => {}
+        }
+    }
+
+    for value in element.attributes.values_mut() {
+        substitute(value, arguments);
+    }
+    for child in &mut element.children {
+        substitute_arguments(child, arguments);
+    }
+}
+
+fn process_element(pair: Pair<Rule>, fragments: &mut Fragments) -> Result<Element, TemplateError> {
+    let mut inner_rules = pair.into_inner();
     let identifier = inner_rules.next().unwrap().as_str().to_string();
 
     let mut attributes = HashMap::new();
@@ -105,12 +595,12 @@ fn process_element(pair: Pair<Rule>) -> Element {
     for inner_pair in inner_rules {
         match inner_pair.as_rule() {
             Rule::attributes => {
-                attributes = process_attributes(inner_pair);
+                attributes = process_attributes(inner_pair, Some(&identifier))?;
             }
             Rule::element_body => {
                 for expr in inner_pair.into_inner() {
                     if expr.as_rule() == Rule::expression {
-                        children.push(process_element(expr));
+                        children.extend(process_expression(expr, fragments)?);
                     }
                 }
             }
@@ -118,14 +608,17 @@ fn process_element(pair: Pair<Rule>) -> Element {
         }
     }
 
-    Element {
+    Ok(Element {
         name: identifier,
         attributes,
         children,
-    }
+    })
 }
 
-fn process_attributes(pair: Pair<Rule>) -> HashMap<String, Value> {
+fn process_attributes(
+    pair: Pair<Rule>,
+    element: Option<&str>,
+) -> Result<HashMap<String, Value>, TemplateError> {
     let mut attributes = HashMap::new();
 
     for inner_pair in pair.into_inner() {
@@ -134,17 +627,22 @@ fn process_attributes(pair: Pair<Rule>) -> HashMap<String, Value> {
                 if attr_pair.as_rule() == Rule::attribute {
                     let mut attr_inner = attr_pair.into_inner();
                     let key = attr_inner.next().unwrap().as_str().to_string();
-                    let value = process_value(attr_inner.next().unwrap());
+                    let value = process_value(attr_inner.next().unwrap(), &key, element)?;
                     attributes.insert(key, value);
                 }
             }
         }
     }
-    attributes
+    Ok(attributes)
 }
 
-fn process_value(pair: Pair<Rule>) -> Value {
-    println!(
+/// Convert a value of the attribute `key`. Fails for numbers that don't fit.
+fn process_value(
+    pair: Pair<Rule>,
+    key: &str,
+    element: Option<&str>,
+) -> Result<Value, TemplateError> {
+    debug!(
         "Processing value rule: {:?}, text: {}",
         pair.as_rule(),
         pair.as_str()
@@ -153,24 +651,47 @@ fn process_value(pair: Pair<Rule>) -> Value {
         Rule::string => {
             let s = pair.as_str();
             // Remove the surrounding quotes
-            Value::String(s[1..s.len() - 1].to_string())
+            let quote = if s.len() >= 6 && s.starts_with("\"\"\"") {
+                3
+            } else {
+                1
+            };
+            Ok(Value::String(s[quote..s.len() - quote].to_string()))
         }
         Rule::number => {
-            let n = pair.as_str().parse::<i64>().unwrap();
-            Value::Number(n)
+            let s = pair.as_str();
+            let invalid = |e: String| {
+                error_at(
+                    &pair,
+                    element,
+                    format!("invalid number `{s}` in `{key}`: {e}"),
+                )
+            };
+            if s.contains('.') {
+                s.parse::<f64>()
+                    .map(Value::Float)
+                    .map_err(|e| invalid(e.to_string()))
+            } else {
+                s.parse::<i64>()
+                    .map(Value::Number)
+                    .map_err(|e| invalid(e.to_string()))
+            }
         }
         Rule::boolean => {
             let b = pair.as_str().parse::<bool>().unwrap();
-            Value::Boolean(b)
+            Ok(Value::Boolean(b))
         }
-        Rule::identifier => Value::Identifier(pair.as_str().to_string()),
+        Rule::identifier => Ok(Value::Identifier(pair.as_str().to_string())),
         Rule::array => {
-            let values: Vec<Value> = pair.into_inner().map(process_value).collect();
-            Value::Array(values)
+            let values = pair
+                .into_inner()
+                .map(|value| process_value(value, key, element))
+                .collect::<Result<Vec<Value>, TemplateError>>()?;
+            Ok(Value::Array(values))
         }
         rule => {
-            println!("Unexpected value rule: {:?}", rule);
-            Value::String(pair.as_str().to_string())
+            warn!("Unexpected value rule: {:?}", rule);
+            Ok(Value::String(pair.as_str().to_string()))
         }
     }
 }
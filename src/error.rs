@@ -0,0 +1,26 @@
+use thiserror::Error;
+
+use crate::dom::TemplateError;
+
+/// Errors returned by the public loading, parsing and extraction functions.
+#[derive(Debug, Error)]
+pub enum DelverError {
+    #[error("invalid template: {0}")]
+    Template(#[from] TemplateError),
+    #[error("failed to load PDF: {0}")]
+    PdfLoad(#[from] lopdf::Error),
+    #[error("incorrect password for encrypted PDF")]
+    IncorrectPassword,
+    #[error("failed to extract text from page {page}: {source}")]
+    PageExtraction {
+        page: u32,
+        #[source]
+        source: lopdf::Error,
+    },
+    #[error(transparent)]
+    Serialization(#[from] serde_json::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, DelverError>;
@@ -0,0 +1,427 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Error, ErrorKind, Read};
+use std::path::Path;
+use std::sync::{Arc, LazyLock, RwLock};
+
+use crate::error::Result;
+
+/// A font name split into family and style, so "ABCDEF+Arial-BoldMT",
+/// "Arial,Bold" and "ArialBold" all read as bold Arial.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct CanonicalFont {
+    pub family: String,
+    /// CSS-style weight: 400 for regular, 700 for bold.
+    pub weight: u16,
+    pub italic: bool,
+    pub condensed: bool,
+}
+
+pub const REGULAR_WEIGHT: u16 = 400;
+
+/// Lowest weight that reads as bold; semibold and demibold count.
+pub const BOLD_WEIGHT: u16 = 600;
+
+/// What a style token sets.
+#[derive(Clone, Copy)]
+enum Style {
+    Weight(u16),
+    Italic,
+    Condensed,
+    /// Vendor suffixes such as the "MT" of "Arial-BoldMT", which say nothing
+    /// about the style.
+    Ignored,
+}
+
+/// Style tokens as they appear after the family, in lowercase; longer tokens
+/// come before the tokens they end with.
+const STYLE_TOKENS: &[(&str, Style)] = &[
+    ("extrabold", Style::Weight(800)),
+    ("ultrabold", Style::Weight(800)),
+    ("extralight", Style::Weight(200)),
+    ("ultralight", Style::Weight(200)),
+    ("semibold", Style::Weight(600)),
+    ("demibold", Style::Weight(600)),
+    ("bold", Style::Weight(700)),
+    ("demi", Style::Weight(600)),
+    ("black", Style::Weight(900)),
+    ("heavy", Style::Weight(800)),
+    ("medium", Style::Weight(500)),
+    ("light", Style::Weight(300)),
+    ("thin", Style::Weight(100)),
+    ("regular", Style::Weight(REGULAR_WEIGHT)),
+    ("roman", Style::Weight(REGULAR_WEIGHT)),
+    ("normal", Style::Weight(REGULAR_WEIGHT)),
+    ("book", Style::Weight(REGULAR_WEIGHT)),
+    ("italic", Style::Italic),
+    ("oblique", Style::Italic),
+    ("it", Style::Italic),
+    ("condensed", Style::Condensed),
+    ("narrow", Style::Condensed),
+    ("cond", Style::Condensed),
+    ("psmt", Style::Ignored),
+    ("mt", Style::Ignored),
+    ("ps", Style::Ignored),
+];
+
+/// Style tokens recognised when run straight onto the family, as in
+/// "ArialNarrowBold". Only capitalised, unambiguous words, so "TimesNewRoman"
+/// keeps its "Roman" and "Second" its "cond".
+const FAMILY_SUFFIXES: &[(&str, Style)] = &[
+    ("ExtraBold", Style::Weight(800)),
+    ("SemiBold", Style::Weight(600)),
+    ("Semibold", Style::Weight(600)),
+    ("DemiBold", Style::Weight(600)),
+    ("Bold", Style::Weight(700)),
+    ("Black", Style::Weight(900)),
+    ("Heavy", Style::Weight(800)),
+    ("Medium", Style::Weight(500)),
+    ("Light", Style::Weight(300)),
+    ("Regular", Style::Weight(REGULAR_WEIGHT)),
+    ("Italic", Style::Italic),
+    ("Oblique", Style::Italic),
+    ("Condensed", Style::Condensed),
+    ("Narrow", Style::Condensed),
+    ("PSMT", Style::Ignored),
+    ("MT", Style::Ignored),
+    ("PS", Style::Ignored),
+];
+
+/// Display names for common families, keyed by the family in lowercase with
+/// spaces removed. Other families keep the spelling of the font name.
+const FAMILIES: &[(&str, &str)] = &[
+    ("arial", "Arial"),
+    ("arialunicode", "Arial Unicode"),
+    ("helvetica", "Helvetica"),
+    ("helveticaneue", "Helvetica Neue"),
+    ("times", "Times"),
+    ("timesnewroman", "Times New Roman"),
+    ("courier", "Courier"),
+    ("couriernew", "Courier New"),
+    ("calibri", "Calibri"),
+    ("cambria", "Cambria"),
+    ("candara", "Candara"),
+    ("consolas", "Consolas"),
+    ("constantia", "Constantia"),
+    ("corbel", "Corbel"),
+    ("garamond", "Garamond"),
+    ("ebgaramond", "EB Garamond"),
+    ("adobegaramondpro", "Adobe Garamond Pro"),
+    ("georgia", "Georgia"),
+    ("verdana", "Verdana"),
+    ("tahoma", "Tahoma"),
+    ("trebuchetms", "Trebuchet MS"),
+    ("segoeui", "Segoe UI"),
+    ("centurygothic", "Century Gothic"),
+    ("bookantiqua", "Book Antiqua"),
+    ("palatino", "Palatino"),
+    ("palatinolinotype", "Palatino Linotype"),
+    ("myriadpro", "Myriad Pro"),
+    ("minionpro", "Minion Pro"),
+    ("frutiger", "Frutiger"),
+    ("futura", "Futura"),
+    ("gillsans", "Gill Sans"),
+    ("franklingothic", "Franklin Gothic"),
+    ("univers", "Univers"),
+    ("symbol", "Symbol"),
+    ("zapfdingbats", "ZapfDingbats"),
+    ("wingdings", "Wingdings"),
+];
+
+impl CanonicalFont {
+    /// Parse a BaseFont such as "ABCDEF+TimesNewRomanPS-BoldItalicMT". The
+    /// subset prefix is dropped, the style is read from the part after the last
+    /// '-' or first ',' and from words run onto the family.
+    pub fn parse(base_font: &str) -> Self {
+        let mut font = CanonicalFont {
+            family: String::new(),
+            weight: REGULAR_WEIGHT,
+            italic: false,
+            condensed: false,
+        };
+
+        let name = strip_subset_prefix(base_font);
+        let (family, style) = match name.split_once(',') {
+            Some((family, style)) => (family, style),
+            None => name.rsplit_once('-').unwrap_or((name, "")),
+        };
+
+        let style = style.replace([',', '-', ' ', '_'], "");
+        let mut unread = style.as_str();
+        // Tokens are read from the end, so "BoldItalic" reads "Italic" first
+        while let Some((token, rest)) = strip_token(unread, STYLE_TOKENS, true) {
+            font.apply(token);
+            unread = rest;
+        }
+
+        let mut family = family.trim();
+        while let Some((token, rest)) = strip_token(family, FAMILY_SUFFIXES, false) {
+            font.apply(token);
+            family = rest.trim_end_matches([' ', '-']);
+        }
+        font.family = display_family(family);
+        // Whatever of the style wasn't understood belongs to the family, as in
+        // "Heiti-SC"
+        if !unread.is_empty() {
+            font.family = format!("{}-{}", font.family, unread);
+        }
+        font
+    }
+
+    pub fn is_bold(&self) -> bool {
+        self.weight >= BOLD_WEIGHT
+    }
+
+    fn apply(&mut self, style: Style) {
+        match style {
+            Style::Weight(weight) => self.weight = weight,
+            Style::Italic => self.italic = true,
+            Style::Condensed => self.condensed = true,
+            Style::Ignored => {}
+        }
+    }
+}
+
+/// The family followed by the style, e.g. "Times New Roman-BoldItalic" or just
+/// "Arial" for the regular face.
+impl fmt::Display for CanonicalFont {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let weight = match self.weight {
+            REGULAR_WEIGHT => "",
+            100 => "Thin",
+            200 => "ExtraLight",
+            300 => "Light",
+            500 => "Medium",
+            600 => "Semibold",
+            700 => "Bold",
+            800 => "ExtraBold",
+            _ => "Black",
+        };
+        let condensed = if self.condensed { "Condensed" } else { "" };
+        let italic = if self.italic { "Italic" } else { "" };
+        let style = format!("{weight}{condensed}{italic}");
+        if style.is_empty() {
+            write!(f, "{}", self.family)
+        } else {
+            write!(f, "{}-{}", self.family, style)
+        }
+    }
+}
+
+/// Drop the six capital letters and '+' that mark a font subset.
+fn strip_subset_prefix(name: &str) -> &str {
+    match name.split_once('+') {
+        Some((prefix, rest))
+            if prefix.len() == 6 && prefix.bytes().all(|b| b.is_ascii_uppercase()) =>
+        {
+            rest
+        }
+        _ => name,
+    }
+}
+
+/// The first of `tokens` that `text` ends with, and the text before it.
+fn strip_token<'a>(
+    text: &'a str,
+    tokens: &[(&str, Style)],
+    ignore_case: bool,
+) -> Option<(Style, &'a str)> {
+    tokens.iter().find_map(|(token, style)| {
+        let split = text.len().checked_sub(token.len())?;
+        let (rest, end) = (text.get(..split)?, text.get(split..)?);
+        let matches = if ignore_case {
+            end.eq_ignore_ascii_case(token)
+        } else {
+            end == *token
+        };
+        // A suffix needs some family left in front of it
+        (matches && (ignore_case || !rest.is_empty())).then_some((*style, rest))
+    })
+}
+
+fn display_family(family: &str) -> String {
+    let key: String = family
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_lowercase();
+    FAMILIES
+        .iter()
+        .find(|(name, _)| *name == key)
+        .map_or_else(|| family.to_string(), |(_, display)| display.to_string())
+}
+
+/// Glyph widths of a font, in thousandths of an em, used to estimate how far
+/// shown text reaches. As JSON:
+///
+/// ```json
+/// { "ascent": 718, "descent": -207, "missing_width": 500, "widths": { "A": 667, " ": 278 } }
+/// ```
+///
+/// Every field is optional.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FontMetrics {
+    pub ascent: f32,
+    pub descent: f32,
+    /// Width of characters missing from `widths`.
+    pub missing_width: f32,
+    pub widths: HashMap<char, f32>,
+}
+
+impl Default for FontMetrics {
+    fn default() -> Self {
+        FontMetrics {
+            ascent: 718.0,
+            descent: -207.0,
+            missing_width: 500.0,
+            widths: HashMap::new(),
+        }
+    }
+}
+
+impl FontMetrics {
+    pub fn width(&self, c: char) -> f32 {
+        self.widths.get(&c).copied().unwrap_or(self.missing_width)
+    }
+}
+
+/// How the character codes of a PDF font pick glyphs in its embedded program.
+#[cfg(feature = "font-parsing")]
+#[derive(Debug, Clone, Copy)]
+pub enum GlyphMapping<'a> {
+    /// One-byte codes of a simple font, looked up in the program's own cmap.
+    Cmap,
+    /// Codes that are CIDs, used as glyph ids as they are (`None`) or through the
+    /// big-endian pairs of a `/CIDToGIDMap` stream.
+    CidToGid(Option<&'a [u8]>),
+}
+
+/// Metrics from the advances and vertical extent of an embedded TrueType or
+/// OpenType font program, keyed by the character `decode` gives each code.
+/// None when the program can't be parsed, e.g. a bare CFF font.
+#[cfg(feature = "font-parsing")]
+pub fn program_metrics(
+    program: &[u8],
+    mapping: GlyphMapping,
+    decode: impl Fn(u16) -> Option<char>,
+) -> Option<FontMetrics> {
+    use ttf_parser::{Face, GlyphId, PlatformId};
+
+    let face = Face::parse(program, 0).ok()?;
+    let scale = 1000.0 / f32::from(face.units_per_em());
+    let codes = match mapping {
+        GlyphMapping::Cmap => 0..=255,
+        GlyphMapping::CidToGid(None) => 0..=face.number_of_glyphs().saturating_sub(1),
+        GlyphMapping::CidToGid(Some(map)) => 0..=((map.len() / 2).saturating_sub(1) as u16),
+    };
+    let glyph = |code: u16, c: char| match mapping {
+        // Symbolic fonts map codes as they are in (1, 0), and from 0xF000 in (3, 0)
+        GlyphMapping::Cmap => face.glyph_index(c).or_else(|| {
+            face.tables().cmap?.subtables.into_iter().find_map(|table| {
+                match (table.platform_id, table.encoding_id) {
+                    (PlatformId::Macintosh, 0) => table.glyph_index(u32::from(code)),
+                    (PlatformId::Windows, 0) => table
+                        .glyph_index(0xF000 + u32::from(code))
+                        .or_else(|| table.glyph_index(u32::from(code))),
+                    _ => None,
+                }
+            })
+        }),
+        GlyphMapping::CidToGid(None) => Some(GlyphId(code)),
+        GlyphMapping::CidToGid(Some(map)) => {
+            let at = usize::from(code) * 2;
+            let gid = map.get(at..at + 2)?;
+            Some(GlyphId(u16::from_be_bytes([gid[0], gid[1]])))
+        }
+    };
+
+    let mut metrics = FontMetrics {
+        ascent: f32::from(face.ascender()) * scale,
+        descent: f32::from(face.descender()) * scale,
+        ..Default::default()
+    };
+    for code in codes {
+        let Some(c) = decode(code) else {
+            continue;
+        };
+        if let Some(advance) = glyph(code, c).and_then(|gid| face.glyph_hor_advance(gid)) {
+            metrics.widths.insert(c, f32::from(advance) * scale);
+        }
+    }
+    Some(metrics)
+}
+
+/// Metrics registered at runtime, keyed by the canonical font name.
+static REGISTERED_METRICS: LazyLock<RwLock<HashMap<String, Arc<FontMetrics>>>> =
+    LazyLock::new(Default::default);
+
+/// Use `metrics` for text in the font `name` from now on, wherever the PDF
+/// doesn't give the font's widths itself. Subset prefixes and
+/// spellings of the style don't matter: registering "Acme-Bold" covers
+/// "ABCDEF+Acme,Bold".
+pub fn register_metrics(name: &str, metrics: FontMetrics) {
+    let key = CanonicalFont::parse(name).to_string();
+    REGISTERED_METRICS
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(key, Arc::new(metrics));
+}
+
+/// The metrics registered for the font `base_font`, if any.
+pub fn registered_metrics(base_font: &str) -> Option<Arc<FontMetrics>> {
+    let key = CanonicalFont::parse(base_font).to_string();
+    REGISTERED_METRICS
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(&key)
+        .cloned()
+}
+
+/// Read an Adobe Font Metrics file. Character codes are read as Latin-1, and
+/// characters without a code are skipped.
+pub fn load_afm(path: impl AsRef<Path>) -> Result<FontMetrics> {
+    let afm = std::fs::read_to_string(path)?;
+    let mut metrics = FontMetrics::default();
+    let mut in_char_metrics = false;
+    for line in afm.lines() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("Ascender") => metrics.ascent = afm_number(words.next()),
+            Some("Descender") => metrics.descent = afm_number(words.next()),
+            Some("StartCharMetrics") => in_char_metrics = true,
+            Some("EndCharMetrics") => in_char_metrics = false,
+            Some("C") if in_char_metrics => {
+                // C 65 ; WX 667 ; N A ; B 14 0 654 718 ;
+                let mut code = None;
+                let mut width = None;
+                for field in line.split(';') {
+                    let mut parts = field.split_whitespace();
+                    match (parts.next(), parts.next()) {
+                        (Some("C"), Some(value)) => code = value.parse::<i32>().ok(),
+                        (Some("WX"), Some(value)) => width = value.parse::<f32>().ok(),
+                        _ => {}
+                    }
+                }
+                if let (Some(code @ 0..=255), Some(width)) = (code, width) {
+                    metrics.widths.insert(char::from(code as u8), width);
+                }
+            }
+            _ => {}
+        }
+    }
+    if metrics.widths.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidData, "no character metrics in AFM file").into());
+    }
+    Ok(metrics)
+}
+
+fn afm_number(word: Option<&str>) -> f32 {
+    word.and_then(|word| word.parse().ok()).unwrap_or(0.0)
+}
+
+/// Read metrics in the JSON form shown on `FontMetrics`.
+pub fn load_metrics_json(reader: impl Read) -> Result<FontMetrics> {
+    Ok(serde_json::from_reader(reader)?)
+}
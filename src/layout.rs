@@ -1,9 +1,20 @@
+use log::warn;
 use lopdf::Document;
 use rayon::prelude::*;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::io::{Error, ErrorKind};
+use std::ops::Range;
+use std::str::FromStr;
 
-use crate::parse::TextElement;
+use crate::dom::{parse_hex_color, Element, Root, TemplateError, Value};
+use crate::error::DelverError;
+use crate::fonts::CanonicalFont;
+use crate::parse::{
+    collect_link_targets, get_outline_entries, get_page_geometry, LinkTarget, OutlineEntry,
+    PageGeometry, PathElement, TextElement, DEFAULT_PAGE_GEOMETRY, MAX_RULE_THICKNESS,
+};
 
 // #[derive(Debug, Clone)]
 // pub struct TextElement {
@@ -17,10 +28,709 @@ use crate::parse::TextElement;
 //     // Add other metadata as needed
 // }
 
+/// Maximum baseline difference, in points, for two elements to share a line.
+pub const DEFAULT_LINE_JOIN_THRESHOLD: f32 = 5.0;
+
+/// A distance in points, or relative to the font size when written as e.g. "1.2em".
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JoinThreshold {
+    Points(f32),
+    Em(f32),
+}
+
+impl JoinThreshold {
+    pub fn resolve(&self, font_size: f32) -> f32 {
+        match self {
+            JoinThreshold::Points(points) => *points,
+            JoinThreshold::Em(em) => em * font_size,
+        }
+    }
+}
+
+impl Default for JoinThreshold {
+    fn default() -> Self {
+        JoinThreshold::Points(DEFAULT_LINE_JOIN_THRESHOLD)
+    }
+}
+
+impl FromStr for JoinThreshold {
+    type Err = String;
+
+    /// Accepts "5", "5pt" or "1.2em".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (number, relative) = match s.strip_suffix("em") {
+            Some(number) => (number, true),
+            None => (s.strip_suffix("pt").unwrap_or(s), false),
+        };
+        let value: f32 = number
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid join threshold `{s}`, expected e.g. 5, 5pt or 1.2em"))?;
+        Ok(if relative {
+            JoinThreshold::Em(value)
+        } else {
+            JoinThreshold::Points(value)
+        })
+    }
+}
+
+/// Consecutive text elements on the same page and baseline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextLine {
+    pub text: String,
+    pub page_number: u32,
+    pub position: (f32, f32), // Position of the first element
+    pub elements: Vec<usize>, // Indices into the elements the line was built from
+}
+
+pub fn group_text_into_lines(
+    text_elements: &[TextElement],
+    line_join_threshold: f32,
+) -> Vec<TextLine> {
+    group_text_into_lines_with_threshold(text_elements, JoinThreshold::Points(line_join_threshold))
+}
+
+/// Like `group_text_into_lines`, with an `Em` threshold measured against the font
+/// size of the element being joined.
+pub fn group_text_into_lines_with_threshold(
+    text_elements: &[TextElement],
+    line_join_threshold: JoinThreshold,
+) -> Vec<TextLine> {
+    let mut lines: Vec<TextLine> = Vec::new();
+
+    for (index, element) in text_elements.iter().enumerate() {
+        match lines.last_mut() {
+            Some(line)
+                if line.page_number == element.page_number
+                    && (line.position.1 - element.position.1).abs()
+                        <= line_join_threshold.resolve(element.font_size) =>
+            {
+                // Runs split by font changes often lack the space between them
+                if !line.text.ends_with(char::is_whitespace)
+                    && !element.text.starts_with(char::is_whitespace)
+                {
+                    line.text.push(' ');
+                }
+                line.text.push_str(&element.text);
+                line.elements.push(index);
+            }
+            _ => lines.push(TextLine {
+                text: element.text.clone(),
+                page_number: element.page_number,
+                position: element.position,
+                elements: vec![index],
+            }),
+        }
+    }
+
+    lines
+}
+
+/// What a line of text is, judged from its font and its first characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LineKind {
+    Heading,
+    Body,
+    ListItem,
+    Caption,
+}
+
+/// Font size z-score at or above which a line is taken to be a heading.
+const HEADING_Z_SCORE: f32 = 1.0;
+
+/// Longest line, in words, that a bold font alone can make a heading.
+const MAX_BOLD_HEADING_WORDS: usize = 12;
+
+/// Label each line as a heading, body text, list item or caption. Headings are set
+/// noticeably larger than the document's text or are short lines in a bold face.
+pub fn classify_lines(text_elements: &[TextElement], lines: &[TextLine]) -> Vec<LineKind> {
+    let sizes: Vec<f32> = text_elements.iter().map(|mi| mi.font_size).collect();
+    let mean = sizes.iter().sum::<f32>() / sizes.len().max(1) as f32;
+    let std_dev = (sizes.iter().map(|size| (size - mean).powi(2)).sum::<f32>()
+        / sizes.len().max(1) as f32)
+        .sqrt();
+    let body_size = font_size_percentile(text_elements, 0.5);
+
+    lines
+        .iter()
+        .map(|line| {
+            let first = &text_elements[line.elements[0]];
+            let size = line
+                .elements
+                .iter()
+                .map(|&index| text_elements[index].font_size)
+                .fold(0.0, f32::max);
+            let text = line.text.trim();
+            let larger =
+                std_dev > 0.0 && size > body_size && (size - mean) / std_dev >= HEADING_Z_SCORE;
+            let bold = first.font_name.as_deref().is_some_and(is_bold_font)
+                && text.split_whitespace().count() <= MAX_BOLD_HEADING_WORDS;
+
+            if larger || bold {
+                LineKind::Heading
+            } else if size <= body_size && is_caption(text) {
+                LineKind::Caption
+            } else if is_list_item(text) {
+                LineKind::ListItem
+            } else {
+                LineKind::Body
+            }
+        })
+        .collect()
+}
+
+fn is_bold_font(font_name: &str) -> bool {
+    CanonicalFont::parse(font_name).is_bold()
+}
+
+/// "Table 3: ...", "Figure 1 - ...", "Source: ..."
+fn is_caption(text: &str) -> bool {
+    let mut words = text.split_whitespace();
+    match (words.next(), words.next()) {
+        (Some("Source:"), _) | (Some("Note:"), _) => true,
+        (Some("Table" | "Figure" | "Exhibit" | "Chart"), Some(number)) => {
+            number.starts_with(|c: char| c.is_ascii_digit())
+        }
+        _ => false,
+    }
+}
+
+/// Lines starting with a bullet, or a marker like "1." "a)" "(iv)" before more text.
+fn is_list_item(text: &str) -> bool {
+    let Some((marker, rest)) = text.split_once(char::is_whitespace) else {
+        return false;
+    };
+    if rest.trim().is_empty() {
+        return false;
+    }
+    if matches!(marker, "•" | "◦" | "▪" | "‣" | "-" | "–" | "*") {
+        return true;
+    }
+    let label = marker
+        .strip_prefix('(')
+        .and_then(|m| m.strip_suffix(')'))
+        .or_else(|| marker.strip_suffix(')'))
+        .or_else(|| marker.strip_suffix('.'));
+    label.is_some_and(|label| {
+        !label.is_empty()
+            && (label.chars().all(|c| c.is_ascii_digit())
+                || (label.len() == 1 && label.chars().all(|c| c.is_ascii_lowercase()))
+                || label.chars().all(|c| matches!(c, 'i' | 'v' | 'x')))
+    })
+}
+
+/// Weights of the signals `detect_headings` adds up, and the score a line needs to
+/// be reported.
+#[derive(Debug, Clone)]
+pub struct HeadingConfig {
+    /// Score for a line set at twice the body font size, scaled with the excess.
+    pub size_weight: f32,
+    pub bold_weight: f32,
+    pub italic_weight: f32,
+    /// Score for a line with at least `gap_ratio` body font sizes of space above it.
+    pub gap_weight: f32,
+    pub gap_ratio: f32,
+    /// Score for a numbered line such as "1.2 Scope", "Item 7." or "Part II".
+    pub numbering_weight: f32,
+    /// Score for a line an outline entry points at.
+    pub outline_weight: f32,
+    /// Removed from lines longer than `max_words` words.
+    pub length_penalty: f32,
+    pub max_words: usize,
+    pub min_score: f32,
+}
+
+impl Default for HeadingConfig {
+    fn default() -> Self {
+        HeadingConfig {
+            size_weight: 2.5,
+            bold_weight: 0.8,
+            italic_weight: 0.3,
+            gap_weight: 0.4,
+            gap_ratio: 2.0,
+            numbering_weight: 0.5,
+            outline_weight: 1.0,
+            length_penalty: 1.0,
+            max_words: MAX_BOLD_HEADING_WORDS,
+            min_score: 1.0,
+        }
+    }
+}
+
+/// A line `detect_headings` took to be a heading. `element` is the line's first
+/// element; `level` is 1 for the largest heading size, 2 for the next and so on.
+#[derive(Debug, Clone)]
+pub struct HeadingCandidate {
+    pub text: String,
+    pub element: TextElement,
+    pub score: f32,
+    pub level: usize,
+}
+
+/// Score every line with `config` and return those reaching `min_score`, in
+/// reading order.
+///
+/// Unlike `classify_lines`, size is measured against the body size (the size most
+/// of the text is set in) rather than the mean, so documents with few size steps
+/// or with bold headings at body size still get their headings found.
+pub fn detect_headings(
+    text_elements: &[TextElement],
+    outlines: &[OutlineEntry],
+    config: &HeadingConfig,
+) -> Vec<HeadingCandidate> {
+    let mut sorted_elements = text_elements.to_vec();
+    sort_reading_order(&mut sorted_elements);
+    let body_size = body_font_size(&sorted_elements);
+    let bookmarked: BTreeSet<usize> = outlines
+        .iter()
+        .filter_map(|entry| landing_index(&sorted_elements, entry.page, entry.y))
+        .collect();
+
+    let lines = group_text_into_lines(&sorted_elements, DEFAULT_LINE_JOIN_THRESHOLD);
+    let mut headings: Vec<HeadingCandidate> = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let first = &sorted_elements[line.elements[0]];
+        let size = line
+            .elements
+            .iter()
+            .map(|&index| sorted_elements[index].font_size)
+            .fold(0.0, f32::max);
+        let font_name = first.font_name.as_deref().unwrap_or("");
+        let text = line.text.trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        let mut score = 0.0;
+        if body_size > 0.0 {
+            score += config.size_weight * (size / body_size - 1.0).max(0.0);
+        }
+        if is_bold_font(font_name) {
+            score += config.bold_weight;
+        }
+        if is_italic_font(font_name) {
+            score += config.italic_weight;
+        }
+        let gap_above = index
+            .checked_sub(1)
+            .map(|previous| &lines[previous])
+            .filter(|previous| previous.page_number == line.page_number)
+            .map(|previous| previous.position.1 - line.position.1);
+        if gap_above.is_some_and(|gap| gap >= config.gap_ratio * body_size) {
+            score += config.gap_weight;
+        }
+        if is_numbered_heading(text) {
+            score += config.numbering_weight;
+        }
+        if bookmarked.contains(&line.elements[0]) {
+            score += config.outline_weight;
+        }
+        if text.split_whitespace().count() > config.max_words {
+            score -= config.length_penalty;
+        }
+
+        if score >= config.min_score {
+            headings.push(HeadingCandidate {
+                text: text.to_string(),
+                element: first.clone(),
+                score,
+                level: 0,
+            });
+        }
+    }
+
+    let mut sizes: Vec<f32> = headings.iter().map(|h| h.element.font_size).collect();
+    sizes.sort_by(|a, b| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+    sizes.dedup();
+    for heading in &mut headings {
+        heading.level = sizes
+            .iter()
+            .position(|size| *size == heading.element.font_size)
+            .unwrap_or(0)
+            + 1;
+    }
+    headings
+}
+
+/// The font size covering the most characters.
+fn body_font_size(text_elements: &[TextElement]) -> f32 {
+    let mut characters: Vec<(f32, usize)> = Vec::new();
+    for mi in text_elements {
+        let count = mi.text.chars().filter(|c| !c.is_whitespace()).count();
+        match characters
+            .iter_mut()
+            .find(|(size, _)| *size == mi.font_size)
+        {
+            Some((_, total)) => *total += count,
+            None => characters.push((mi.font_size, count)),
+        }
+    }
+    characters
+        .into_iter()
+        .max_by_key(|(_, count)| *count)
+        .map_or(0.0, |(size, _)| size)
+}
+
+fn is_italic_font(font_name: &str) -> bool {
+    CanonicalFont::parse(font_name).italic
+}
+
+/// Lines starting "1.", "2.3.1", "Item 7.", "Part II", "Section 4" and the like.
+fn is_numbered_heading(text: &str) -> bool {
+    let mut words = text.split_whitespace();
+    let Some(first) = words.next() else {
+        return false;
+    };
+    let keyword = matches!(
+        first.to_lowercase().as_str(),
+        "item" | "part" | "section" | "article" | "chapter" | "note"
+    );
+    let label = if keyword {
+        words.next().unwrap_or("")
+    } else {
+        first
+    };
+    let label = label.trim_end_matches(['.', ':', ')']);
+    let numeric = !label.is_empty()
+        && label
+            .split('.')
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()));
+    // "7A", "IV" only count after a keyword, so "I think..." isn't numbered
+    let labelled = keyword
+        && !label.is_empty()
+        && (label.starts_with(|c: char| c.is_ascii_digit())
+            && label.chars().all(|c| c.is_ascii_alphanumeric())
+            || label.chars().all(|c| matches!(c, 'I' | 'V' | 'X' | 'L')));
+    numeric || labelled
+}
+
+/// Normalization applied to both the search string and element text before matching.
+#[derive(Debug, Clone, Default)]
+pub struct MatchOptions {
+    pub ignore_case: bool,
+    /// Map non-breaking and other Unicode spaces to ' ', collapse runs and trim.
+    pub normalize_whitespace: bool,
+    pub strip_soft_hyphens: bool,
+    /// Baseline distance within which elements are joined into one line.
+    pub line_join_threshold: JoinThreshold,
+}
+
+pub fn normalize_text(text: &str, options: &MatchOptions) -> String {
+    let mut normalized: String = if options.strip_soft_hyphens {
+        text.chars().filter(|c| *c != '\u{00AD}').collect()
+    } else {
+        text.to_string()
+    };
+    if options.normalize_whitespace {
+        normalized = normalized
+            .split(|c: char| c.is_whitespace() || matches!(c, '\u{00A0}' | '\u{2007}' | '\u{202F}'))
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<&str>>()
+            .join(" ");
+    }
+    if options.ignore_case {
+        normalized = normalized.to_lowercase();
+    }
+    normalized
+}
+
+/// Byte range of the first occurrence of `search_string` in `text`, compared under
+/// `options` but reported in offsets into the original `text`.
+pub fn match_span(
+    text: &str,
+    search_string: &str,
+    options: &MatchOptions,
+) -> Option<std::ops::Range<usize>> {
+    let search_string = normalize_text(search_string, options);
+    if search_string.is_empty() {
+        return None;
+    }
+    // Normalize as `normalize_text` does, remembering the source span of every
+    // byte written
+    let mut normalized = String::new();
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+    let mut pending_space: Option<(usize, usize)> = None;
+    for (offset, c) in text.char_indices() {
+        let span = (offset, offset + c.len_utf8());
+        if options.strip_soft_hyphens && c == '\u{00AD}' {
+            continue;
+        }
+        if options.normalize_whitespace
+            && (c.is_whitespace() || matches!(c, '\u{00A0}' | '\u{2007}' | '\u{202F}'))
+        {
+            if !normalized.is_empty() && pending_space.is_none() {
+                pending_space = Some(span);
+            }
+            continue;
+        }
+        if let Some(space) = pending_space.take() {
+            normalized.push(' ');
+            spans.push(space);
+        }
+        let mut push = |c: char| {
+            normalized.push(c);
+            spans.extend(std::iter::repeat_n(span, c.len_utf8()));
+        };
+        if options.ignore_case {
+            c.to_lowercase().for_each(&mut push);
+        } else {
+            push(c);
+        }
+    }
+    let start = normalized.find(&search_string)?;
+    let end = start + search_string.len();
+    Some(spans[start].0..spans[end - 1].1)
+}
+
+/// Match against whole lines so headings split across several text runs are found.
+/// Each matching line is reported as its first element.
+pub fn perform_line_matching(
+    text_elements: &[TextElement],
+    search_string: &str,
+) -> Vec<TextElement> {
+    perform_line_matching_with_options(text_elements, search_string, &MatchOptions::default())
+}
+
+pub fn perform_line_matching_with_options(
+    text_elements: &[TextElement],
+    search_string: &str,
+    options: &MatchOptions,
+) -> Vec<TextElement> {
+    let search_string = normalize_text(search_string, options);
+    group_text_into_lines_with_threshold(text_elements, options.line_join_threshold)
+        .into_iter()
+        .filter(|line| normalize_text(&line.text, options).contains(&search_string))
+        .map(|line| text_elements[line.elements[0]].clone())
+        .collect()
+}
+
+/// Like `perform_line_matching_with_options`, keeping the lines `pattern`
+/// matches. Lines are compared as they are, not normalized.
+pub fn perform_line_regex_matching(
+    text_elements: &[TextElement],
+    pattern: &Regex,
+    options: &MatchOptions,
+) -> Vec<TextElement> {
+    group_text_into_lines_with_threshold(text_elements, options.line_join_threshold)
+        .into_iter()
+        .filter(|line| pattern.is_match(&line.text))
+        .map(|line| text_elements[line.elements[0]].clone())
+        .collect()
+}
+
+/// What a Section's `match` or `end_match` looks for. A string is found as text;
+/// `["pattern", threshold, "regex"]` is a regex. The threshold is not used here.
+#[derive(Debug, Clone)]
+pub enum MatchPattern {
+    Text(String),
+    Regex(Regex),
+}
+
+impl MatchPattern {
+    /// Read `key` from a Section. Template validation already rejects bad
+    /// patterns, so this only fails for hand-built elements.
+    pub fn from_element(element: &Element, key: &str) -> Result<Option<Self>, regex::Error> {
+        Ok(match element.attributes.get(key) {
+            Some(Value::String(text)) => Some(MatchPattern::Text(text.clone())),
+            Some(Value::Array(values)) => match values.as_slice() {
+                [Value::String(pattern), _, Value::String(mode)] if mode == "regex" => {
+                    Some(MatchPattern::Regex(Regex::new(pattern)?))
+                }
+                [Value::String(text), ..] => Some(MatchPattern::Text(text.clone())),
+                _ => None,
+            },
+            _ => None,
+        })
+    }
+
+    /// The pattern as written.
+    pub fn as_str(&self) -> &str {
+        match self {
+            MatchPattern::Text(text) => text,
+            MatchPattern::Regex(pattern) => pattern.as_str(),
+        }
+    }
+
+    /// Byte range of the first match in `text`, as `match_span` reports it.
+    pub fn span(&self, text: &str, options: &MatchOptions) -> Option<std::ops::Range<usize>> {
+        match self {
+            MatchPattern::Text(search) => match_span(text, search, options),
+            MatchPattern::Regex(pattern) => pattern.find(text).map(|m| m.range()),
+        }
+    }
+
+    /// The first element of every line of `text_elements` the pattern matches.
+    pub fn matching_lines(
+        &self,
+        text_elements: &[TextElement],
+        options: &MatchOptions,
+    ) -> Vec<TextElement> {
+        match self {
+            MatchPattern::Text(search) => {
+                perform_line_matching_with_options(text_elements, search, options)
+            }
+            MatchPattern::Regex(pattern) => {
+                perform_line_regex_matching(text_elements, pattern, options)
+            }
+        }
+    }
+
+    /// Outline entries whose title matches, as `matching_outline_entries` finds them.
+    pub fn outline_entries<'a>(
+        &self,
+        outlines: &'a [OutlineEntry],
+        options: &MatchOptions,
+    ) -> Vec<&'a OutlineEntry> {
+        match self {
+            MatchPattern::Text(search) => matching_outline_entries(outlines, search, options),
+            MatchPattern::Regex(pattern) => outlines
+                .iter()
+                .filter(|entry| pattern.is_match(&entry.title))
+                .collect(),
+        }
+    }
+}
+
+/// Rebuild the rows and columns of a table from the text elements inside it.
+///
+/// Elements are grouped into rows by baseline. Columns come from splitting the sorted
+/// x positions at gaps wider than twice the median font size, or at the `columns - 1`
+/// widest gaps when the column count is known. A row with text only in its first
+/// column is taken to be a wrapped label and merged into the row below it.
+pub fn reconstruct_table(
+    text_elements: &[TextElement],
+    columns: Option<usize>,
+) -> Vec<Vec<String>> {
+    reconstruct_table_with_rules(text_elements, columns, &[])
+}
+
+/// `reconstruct_table`, with the columns split at the vertical rules drawn
+/// between cells when there are any. Rules from `get_pdf_paths` on other pages,
+/// above or below the table, or around its outside are ignored.
+pub fn reconstruct_table_with_rules(
+    text_elements: &[TextElement],
+    columns: Option<usize>,
+    rules: &[PathElement],
+) -> Vec<Vec<String>> {
+    if text_elements.is_empty() {
+        return Vec::new();
+    }
+
+    let mut boundaries = rule_boundaries(text_elements, rules)
+        .unwrap_or_else(|| gap_boundaries(text_elements, columns));
+    boundaries.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let column_count = boundaries.len() + 1;
+
+    // Rows are read top to bottom regardless of the order cells were drawn in
+    let mut sorted_elements = text_elements.to_vec();
+    sort_reading_order(&mut sorted_elements);
+
+    let mut rows = Vec::new();
+    let mut wrapped_label: Option<Vec<String>> = None;
+    for line in group_text_into_lines(&sorted_elements, DEFAULT_LINE_JOIN_THRESHOLD) {
+        let mut row = wrapped_label
+            .take()
+            .unwrap_or_else(|| vec![String::new(); column_count]);
+        for &index in &line.elements {
+            let mi = &sorted_elements[index];
+            let column = boundaries.iter().filter(|b| mi.position.0 > **b).count();
+            let cell = &mut row[column];
+            if !cell.is_empty() {
+                cell.push(' ');
+            }
+            cell.push_str(mi.text.trim());
+        }
+
+        if column_count > 1 && row[1..].iter().all(String::is_empty) {
+            wrapped_label = Some(row);
+        } else {
+            rows.push(row);
+        }
+    }
+    rows.extend(wrapped_label);
+
+    rows
+}
+
+/// Column boundaries at the gaps between cell positions: every gap wider than
+/// twice the median font size, or the `columns - 1` widest.
+fn gap_boundaries(text_elements: &[TextElement], columns: Option<usize>) -> Vec<f32> {
+    let mut xs: Vec<f32> = text_elements.iter().map(|mi| mi.position.0).collect();
+    xs.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    // (width, midpoint) of the gap between each pair of neighbouring positions
+    let mut gaps: Vec<(f32, f32)> = xs
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0], (pair[0] + pair[1]) / 2.0))
+        .collect();
+    match columns {
+        Some(columns) => {
+            gaps.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+            gaps.iter()
+                .take(columns.saturating_sub(1))
+                .filter(|gap| gap.0 > 0.0)
+                .map(|gap| gap.1)
+                .collect()
+        }
+        None => {
+            let tolerance = 2.0 * font_size_percentile(text_elements, 0.5);
+            gaps.iter()
+                .filter(|gap| gap.0 > tolerance)
+                .map(|gap| gap.1)
+                .collect()
+        }
+    }
+}
+
+/// Column boundaries at the vertical rules crossing the table's rows between its
+/// leftmost and rightmost cells, or None when no rule does.
+fn rule_boundaries(text_elements: &[TextElement], rules: &[PathElement]) -> Option<Vec<f32>> {
+    let page = text_elements[0].page_number;
+    let (mut left, mut right) = (f32::MAX, f32::MIN);
+    let (mut bottom, mut top) = (f32::MAX, f32::MIN);
+    for mi in text_elements {
+        left = left.min(mi.position.0);
+        right = right.max(mi.position.0);
+        bottom = bottom.min(mi.position.1);
+        top = top.max(mi.position.1 + mi.font_size);
+    }
+
+    let mut xs: Vec<f32> = rules
+        .iter()
+        .filter(|rule| rule.page_number == page && rule.is_vertical_rule())
+        .filter(|rule| rule.bbox.1 < top && rule.bbox.3 > bottom)
+        .map(|rule| (rule.bbox.0 + rule.bbox.2) / 2.0)
+        .filter(|x| *x > left && *x < right)
+        .collect();
+    xs.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    // A double rule marks one boundary
+    xs.dedup_by(|x, previous| *x - *previous <= MAX_RULE_THICKNESS);
+    (!xs.is_empty()).then_some(xs)
+}
+
 pub fn perform_matching(text_elements: Vec<TextElement>, search_string: &str) -> Vec<TextElement> {
+    perform_matching_with_options(text_elements, search_string, &MatchOptions::default())
+}
+
+pub fn perform_matching_with_options(
+    text_elements: Vec<TextElement>,
+    search_string: &str,
+    options: &MatchOptions,
+) -> Vec<TextElement> {
+    let search_string = normalize_text(search_string, options);
     text_elements
         .into_iter()
-        .filter(|mi| mi.text.contains(search_string))
+        .filter(|mi| normalize_text(&mi.text, options).contains(&search_string))
+        .collect()
+}
+
+pub fn perform_regex_matching(
+    text_elements: Vec<TextElement>,
+    pattern: &Regex,
+) -> Vec<TextElement> {
+    text_elements
+        .into_iter()
+        .filter(|mi| pattern.is_match(&mi.text))
         .collect()
 }
 
@@ -34,6 +744,450 @@ pub fn select_best_match(matched_elements: Vec<TextElement>) -> Option<TextEleme
     })
 }
 
+/// Text render mode (`Tr`) that paints nothing, as used for OCR layers.
+pub const INVISIBLE_RENDER_MODE: u8 = 3;
+
+/// Distance in points within which two runs of the same text count as one.
+const DUPLICATE_DISTANCE: f32 = 2.0;
+
+/// Drop text drawn twice at the same spot, such as a visible run with an OCR copy
+/// behind it. Visible copies win over invisible ones. With `include_invisible` false,
+/// invisible text is dropped even when it is the only copy.
+pub fn dedupe_text_layers(
+    text_elements: &[TextElement],
+    include_invisible: bool,
+) -> Vec<TextElement> {
+    let is_visible = |mi: &TextElement| mi.render_mode != INVISIBLE_RENDER_MODE;
+    let same_run = |a: &TextElement, b: &TextElement| {
+        a.page_number == b.page_number
+            && a.text.trim() == b.text.trim()
+            && (a.position.0 - b.position.0).abs() <= DUPLICATE_DISTANCE
+            && (a.position.1 - b.position.1).abs() <= DUPLICATE_DISTANCE
+    };
+
+    // Visible text first, so an invisible duplicate always finds it
+    let mut by_visibility: Vec<(usize, &TextElement)> = text_elements.iter().enumerate().collect();
+    by_visibility.sort_by_key(|(_, mi)| !is_visible(mi));
+
+    let mut kept: HashMap<(u32, &str), Vec<&TextElement>> = HashMap::new();
+    let mut keep = vec![false; text_elements.len()];
+    for (index, mi) in by_visibility {
+        if !include_invisible && !is_visible(mi) {
+            continue;
+        }
+        let seen = kept.entry((mi.page_number, mi.text.trim())).or_default();
+        if !seen.iter().any(|other| same_run(mi, other)) {
+            seen.push(mi);
+            keep[index] = true;
+        }
+    }
+
+    text_elements
+        .iter()
+        .zip(keep)
+        .filter(|(_, keep)| *keep)
+        .map(|(mi, _)| mi.clone())
+        .collect()
+}
+
+/// How a superscript or subscript run is written when merged into its base text.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ScriptStyle {
+    /// "revenue¹", "H₂", falling back to brackets when a character has no Unicode form.
+    #[default]
+    Unicode,
+    /// "revenue[1]", "H[2]"
+    Bracketed,
+}
+
+/// Largest font size, relative to the text before it, of a superscript or subscript.
+const SCRIPT_MAX_RELATIVE_SIZE: f32 = 0.7;
+
+/// Smallest and largest baseline offset, relative to the base font size, of a
+/// superscript or subscript. Small caps are small too but sit on the baseline.
+const SCRIPT_MIN_RISE: f32 = 0.1;
+const SCRIPT_MAX_RISE: f32 = 0.5;
+
+/// Merge footnote markers and other superscript or subscript runs into the element
+/// before them. A run counts as one when it starts to the right of the previous
+/// element, its font is under 70% of that element's size, and its baseline is raised
+/// or lowered by between a tenth and a half of that size.
+pub fn merge_script_runs(text_elements: &[TextElement], style: ScriptStyle) -> Vec<TextElement> {
+    let mut merged: Vec<TextElement> = Vec::with_capacity(text_elements.len());
+    for mi in text_elements {
+        if let Some(base) = merged.last_mut() {
+            let rise = mi.position.1 - base.position.1;
+            if base.page_number == mi.page_number
+                && mi.position.0 > base.position.0
+                && mi.font_size < base.font_size * SCRIPT_MAX_RELATIVE_SIZE
+                && rise.abs() >= base.font_size * SCRIPT_MIN_RISE
+                && rise.abs() <= base.font_size * SCRIPT_MAX_RISE
+            {
+                let text = mi.text.trim();
+                base.text.truncate(base.text.trim_end().len());
+                base.text.push_str(&render_script(text, rise > 0.0, style));
+                continue;
+            }
+        }
+        merged.push(mi.clone());
+    }
+    merged
+}
+
+fn render_script(text: &str, superscript: bool, style: ScriptStyle) -> String {
+    const SUPERSCRIPTS: &str = "⁰¹²³⁴⁵⁶⁷⁸⁹⁺⁻⁽⁾";
+    const SUBSCRIPTS: &str = "₀₁₂₃₄₅₆₇₈₉₊₋₍₎";
+    let scripts = if superscript {
+        SUPERSCRIPTS
+    } else {
+        SUBSCRIPTS
+    };
+
+    let unicode: Option<String> = (style == ScriptStyle::Unicode)
+        .then(|| {
+            text.chars()
+                .map(|c| {
+                    "0123456789+-()"
+                        .find(c)
+                        .and_then(|index| scripts.chars().nth(index))
+                })
+                .collect()
+        })
+        .flatten();
+    unicode.unwrap_or_else(|| format!("[{text}]"))
+}
+
+/// Share of the page height, at the top and at the bottom, searched for running
+/// headers and footers.
+const HEADER_FOOTER_BAND: f32 = 0.1;
+
+/// Share of pages a header or footer text must repeat on.
+const HEADER_FOOTER_MIN_PAGES: f32 = 0.6;
+
+/// Flag running headers and footers: text near the top or bottom of the page that
+/// repeats on most pages. Numbers are ignored when comparing so page numbers match.
+/// Pages missing from `pages` are taken to be `DEFAULT_PAGE_GEOMETRY`. Returns one
+/// flag per element.
+pub fn find_headers_footers(
+    text_elements: &[TextElement],
+    pages: &BTreeMap<u32, PageGeometry>,
+) -> Vec<bool> {
+    let mut flags = vec![false; text_elements.len()];
+    let page_numbers: BTreeSet<u32> = text_elements.iter().map(|mi| mi.page_number).collect();
+    if page_numbers.len() < 3 {
+        return flags;
+    }
+
+    let options = MatchOptions {
+        ignore_case: true,
+        normalize_whitespace: true,
+        ..Default::default()
+    };
+    let key = |mi: &TextElement| -> Option<String> {
+        let geometry = pages.get(&mi.page_number).unwrap_or(&DEFAULT_PAGE_GEOMETRY);
+        let band = geometry.height * HEADER_FOOTER_BAND;
+        let in_band = mi.position.1 >= geometry.bottom + geometry.height - band
+            || mi.position.1 <= geometry.bottom + band;
+        let mut text = String::new();
+        for c in normalize_text(&mi.text, &options).chars() {
+            // Any run of digits becomes one '#', so "9" and "10" compare equal
+            if !c.is_ascii_digit() {
+                text.push(c);
+            } else if !text.ends_with('#') {
+                text.push('#');
+            }
+        }
+        (in_band && !text.is_empty()).then_some(text)
+    };
+
+    let mut pages_by_text: HashMap<String, BTreeSet<u32>> = HashMap::new();
+    for mi in text_elements {
+        if let Some(text) = key(mi) {
+            pages_by_text
+                .entry(text)
+                .or_default()
+                .insert(mi.page_number);
+        }
+    }
+
+    let min_pages = page_numbers.len() as f32 * HEADER_FOOTER_MIN_PAGES;
+    for (flag, mi) in flags.iter_mut().zip(text_elements) {
+        *flag = key(mi).is_some_and(|text| pages_by_text[&text].len() as f32 > min_pages);
+    }
+    flags
+}
+
+/// The elements that aren't running headers or footers.
+pub fn strip_headers_footers(
+    text_elements: &[TextElement],
+    pages: &BTreeMap<u32, PageGeometry>,
+) -> Vec<TextElement> {
+    text_elements
+        .iter()
+        .zip(find_headers_footers(text_elements, pages))
+        .filter(|(_, is_header_footer)| !is_header_footer)
+        .map(|(mi, _)| mi.clone())
+        .collect()
+}
+
+/// Minimum number of lines on a page before it can be taken for a table of contents.
+const MIN_TOC_LINES: usize = 5;
+
+/// Score removed from candidates on probable table-of-contents pages.
+pub const DEFAULT_TOC_PENALTY: f32 = 20.0;
+
+/// How `select_best_match_with_toc` treats candidates on table-of-contents pages.
+#[derive(Debug, Clone, Copy)]
+pub enum TocPolicy {
+    Penalize(f32),
+    Skip,
+}
+
+/// Pages that look like a table of contents: at least half of their lines end in a
+/// page number.
+pub fn find_toc_pages(text_elements: &[TextElement]) -> BTreeSet<u32> {
+    let mut sorted_elements = text_elements.to_vec();
+    sort_reading_order(&mut sorted_elements);
+
+    // page -> (lines, lines ending in a page number)
+    let mut counts: BTreeMap<u32, (usize, usize)> = Default::default();
+    for line in group_text_into_lines(&sorted_elements, DEFAULT_LINE_JOIN_THRESHOLD) {
+        let count = counts.entry(line.page_number).or_default();
+        count.0 += 1;
+        if ends_in_page_number(&line.text) {
+            count.1 += 1;
+        }
+    }
+
+    counts
+        .into_iter()
+        .filter(|(_, (lines, entries))| *lines >= MIN_TOC_LINES && entries * 2 >= *lines)
+        .map(|(page, _)| page)
+        .collect()
+}
+
+/// True for lines like "Risk Factors 12" or "Risk Factors.....12".
+fn ends_in_page_number(text: &str) -> bool {
+    let text = text.trim_end();
+    let label = text.trim_end_matches(|c: char| c.is_ascii_digit());
+    (1..=4).contains(&(text.len() - label.len()))
+        && label.ends_with(|c: char| c.is_whitespace() || c == '.')
+}
+
+/// Like `select_best_match`, but demotes or drops candidates on `toc_pages` so the
+/// table-of-contents entry for a heading doesn't win over the heading itself.
+pub fn select_best_match_with_toc(
+    matched_elements: Vec<TextElement>,
+    toc_pages: &BTreeSet<u32>,
+    policy: TocPolicy,
+) -> Option<TextElement> {
+    select_best(matched_elements, toc_pages, policy, &[], &[])
+}
+
+/// Score added to a candidate that a matching bookmark points at.
+pub const OUTLINE_BONUS: f32 = 30.0;
+
+/// Score added per inbound link to a candidate, counting at most
+/// `MAX_COUNTED_REFERENCES` links.
+pub const REFERENCE_BONUS: f32 = 10.0;
+const MAX_COUNTED_REFERENCES: usize = 3;
+
+/// How far below a destination's top a candidate baseline may sit, and how far
+/// above it, in points.
+const DESTINATION_BELOW_TOLERANCE: f32 = 50.0;
+const DESTINATION_ABOVE_TOLERANCE: f32 = 5.0;
+
+/// Outline entries whose title contains `search_string` or is contained in it,
+/// compared after normalization.
+pub fn matching_outline_entries<'a>(
+    outlines: &'a [OutlineEntry],
+    search_string: &str,
+    options: &MatchOptions,
+) -> Vec<&'a OutlineEntry> {
+    let search = normalize_text(search_string, options);
+    outlines
+        .iter()
+        .filter(|entry| {
+            let title = normalize_text(&entry.title, options);
+            !title.trim().is_empty() && (title.contains(&search) || search.contains(title.trim()))
+        })
+        .collect()
+}
+
+/// Whether a destination on `page` with view top `y` lands on `element`.
+fn lands_on(page: u32, y: Option<f32>, element: &TextElement) -> bool {
+    page == element.page_number
+        && y.is_none_or(|top| {
+            let y = element.position.1;
+            y <= top + DESTINATION_ABOVE_TOLERANCE && y >= top - DESTINATION_BELOW_TOLERANCE
+        })
+}
+
+/// Index of the topmost element a destination lands on.
+fn landing_index(text_elements: &[TextElement], page: u32, y: Option<f32>) -> Option<usize> {
+    text_elements
+        .iter()
+        .enumerate()
+        .filter(|(_, te)| lands_on(page, y, te))
+        .max_by(|(_, a), (_, b)| {
+            a.position
+                .1
+                .partial_cmp(&b.position.1)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .map(|(index, _)| index)
+}
+
+fn is_bookmarked(element: &TextElement, bookmarks: &[&OutlineEntry]) -> bool {
+    bookmarks
+        .iter()
+        .any(|entry| lands_on(entry.page, entry.y, element))
+}
+
+/// The first text element at or below each bookmark's destination, so a section
+/// can still be anchored when its in-text heading is worded differently.
+pub fn outline_candidates(
+    text_elements: &[TextElement],
+    bookmarks: &[&OutlineEntry],
+) -> Vec<TextElement> {
+    bookmarks
+        .iter()
+        .filter_map(|entry| landing_index(text_elements, entry.page, entry.y))
+        .map(|index| text_elements[index].clone())
+        .collect()
+}
+
+/// How many of `link_targets` land on each element, parallel to `text_elements`.
+/// A link counts towards the topmost element at or just below its destination.
+pub fn reference_counts(text_elements: &[TextElement], link_targets: &[LinkTarget]) -> Vec<usize> {
+    let mut counts = vec![0; text_elements.len()];
+    for target in link_targets {
+        if let Some(index) = landing_index(text_elements, target.page, target.y) {
+            counts[index] += 1;
+        }
+    }
+    counts
+}
+
+/// Like `select_best_match_with_toc`, but candidates that a matching bookmark
+/// points at get `OUTLINE_BONUS`, and the elements bookmarks point at are
+/// considered even if their text didn't match.
+pub fn select_best_match_with_outlines(
+    matched_elements: Vec<TextElement>,
+    text_elements: &[TextElement],
+    toc_pages: &BTreeSet<u32>,
+    policy: TocPolicy,
+    bookmarks: &[&OutlineEntry],
+) -> Option<TextElement> {
+    select_best_match_with_references(
+        matched_elements,
+        text_elements,
+        toc_pages,
+        policy,
+        bookmarks,
+        &[],
+    )
+}
+
+/// Like `select_best_match_with_outlines`, but also adds `REFERENCE_BONUS` for
+/// each internal link that lands on a candidate.
+pub fn select_best_match_with_references(
+    matched_elements: Vec<TextElement>,
+    text_elements: &[TextElement],
+    toc_pages: &BTreeSet<u32>,
+    policy: TocPolicy,
+    bookmarks: &[&OutlineEntry],
+    link_targets: &[LinkTarget],
+) -> Option<TextElement> {
+    rank_candidates(
+        matched_elements,
+        text_elements,
+        toc_pages,
+        policy,
+        bookmarks,
+        link_targets,
+    )
+    .into_iter()
+    .next()
+    .map(|candidate| candidate.element)
+}
+
+/// A match candidate and the score the best-match selectors gave it.
+#[derive(Debug, Clone)]
+pub struct ScoredCandidate {
+    pub element: TextElement,
+    pub score: f32,
+}
+
+/// Every candidate `select_best_match_with_references` considers, best first.
+/// Candidates dropped by `TocPolicy::Skip` are left out.
+pub fn rank_candidates(
+    mut matched_elements: Vec<TextElement>,
+    text_elements: &[TextElement],
+    toc_pages: &BTreeSet<u32>,
+    policy: TocPolicy,
+    bookmarks: &[&OutlineEntry],
+    link_targets: &[LinkTarget],
+) -> Vec<ScoredCandidate> {
+    matched_elements.extend(outline_candidates(text_elements, bookmarks));
+    rank(matched_elements, toc_pages, policy, bookmarks, link_targets)
+}
+
+fn select_best(
+    matched_elements: Vec<TextElement>,
+    toc_pages: &BTreeSet<u32>,
+    policy: TocPolicy,
+    bookmarks: &[&OutlineEntry],
+    link_targets: &[LinkTarget],
+) -> Option<TextElement> {
+    rank(matched_elements, toc_pages, policy, bookmarks, link_targets)
+        .into_iter()
+        .next()
+        .map(|candidate| candidate.element)
+}
+
+fn rank(
+    matched_elements: Vec<TextElement>,
+    toc_pages: &BTreeSet<u32>,
+    policy: TocPolicy,
+    bookmarks: &[&OutlineEntry],
+    link_targets: &[LinkTarget],
+) -> Vec<ScoredCandidate> {
+    let references = reference_counts(&matched_elements, link_targets);
+    let score = |index: usize, mi: &TextElement| {
+        let mut score = score_match(mi);
+        if let TocPolicy::Penalize(penalty) = policy {
+            if toc_pages.contains(&mi.page_number) {
+                score -= penalty;
+            }
+        }
+        if is_bookmarked(mi, bookmarks) {
+            score += OUTLINE_BONUS;
+        }
+        score + REFERENCE_BONUS * references[index].min(MAX_COUNTED_REFERENCES) as f32
+    };
+
+    let mut candidates: Vec<ScoredCandidate> = matched_elements
+        .into_iter()
+        .enumerate()
+        .filter(|(_, mi)| {
+            !matches!(policy, TocPolicy::Skip) || !toc_pages.contains(&mi.page_number)
+        })
+        .map(|(index, mi)| ScoredCandidate {
+            score: score(index, &mi),
+            element: mi,
+        })
+        .collect();
+    // Ties go to the later candidate, as with `max_by`
+    candidates.reverse();
+    candidates.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    candidates
+}
+
 fn score_match(mi: &TextElement) -> f32 {
     let mut score = mi.font_size;
 
@@ -47,16 +1201,640 @@ fn score_match(mi: &TextElement) -> f32 {
     score
 }
 
-fn extract_section_content(all_text_elements: &[TextElement], best_match: &TextElement) -> String {
+/// Collect the text of the section starting at `best_match`.
+///
+/// The section ends before the next natural boundary: an element styled like the
+/// start marker (a sibling heading), or one larger than both the start marker and
+/// the document's 90th percentile font size (a parent heading). Without either the
+/// section runs to the end of the document.
+pub fn extract_section_content(
+    all_text_elements: &[TextElement],
+    best_match: &TextElement,
+) -> String {
+    extract_section_content_with_options(all_text_elements, best_match, &SectionOptions::default())
+}
+
+/// How `extract_section_content_with_options` orders and joins section text.
+#[derive(Debug, Clone)]
+pub struct SectionOptions {
+    pub order: ReadingOrder,
+    /// Rejoin words hyphenated across a line break ("manage-" / "ment").
+    pub dehyphenate: bool,
+    /// Lines to keep or drop from the section body.
+    pub filter: ChunkFilter,
+    /// Pages the section body may come from.
+    pub pages: PageRange,
+    /// Area of each page the section body may come from.
+    pub region: Option<Region>,
+    /// Page sizes to place `region` on. Pages missing from it are taken to be
+    /// `DEFAULT_PAGE_GEOMETRY`.
+    pub page_geometry: BTreeMap<u32, PageGeometry>,
+    /// Byte offset into the start marker's line where the heading ends, usually
+    /// the end of its `match_span`. The line is the marker and the elements after
+    /// it on its baseline, joined as `group_text_into_lines` joins them. Text
+    /// after the offset opens the body.
+    pub start_offset: Option<usize>,
+    /// Element that ends the section, such as a heading found by `end_match`, in
+    /// place of the next natural boundary. Used only when it comes after the start.
+    pub end_marker: Option<TextElement>,
+    /// Byte offset into the element that ends the section where the next heading
+    /// starts. Text before it closes the body.
+    pub end_offset: Option<usize>,
+    /// Baseline distance within which elements join the start marker's line.
+    pub line_join_threshold: JoinThreshold,
+}
+
+impl Default for SectionOptions {
+    fn default() -> Self {
+        SectionOptions {
+            order: ReadingOrder::default(),
+            dehyphenate: true,
+            filter: ChunkFilter::default(),
+            pages: PageRange::default(),
+            region: None,
+            page_geometry: BTreeMap::new(),
+            start_offset: None,
+            end_marker: None,
+            end_offset: None,
+            line_join_threshold: JoinThreshold::default(),
+        }
+    }
+}
+
+/// Inclusive, 1-based page bounds from an element's `pageStart` and `pageEnd`.
+/// Missing bounds are open.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PageRange {
+    pub start: Option<u32>,
+    pub end: Option<u32>,
+}
+
+impl PageRange {
+    pub fn from_element(element: &Element) -> Self {
+        let page = |key: &str| match element.attributes.get(key) {
+            Some(Value::Number(page)) => u32::try_from(*page).ok(),
+            _ => None,
+        };
+        PageRange {
+            start: page("pageStart"),
+            end: page("pageEnd"),
+        }
+    }
+
+    /// Clamp bounds past the end of a `page_count` page document, with a warning.
+    pub fn clamp(self, page_count: u32) -> Self {
+        let clamp = |bound: Option<u32>, key: &str| match bound {
+            Some(page) if page > page_count => {
+                warn!("{key} {page} is past the last page ({page_count}), clamping");
+                Some(page_count)
+            }
+            bound => bound,
+        };
+        PageRange {
+            start: clamp(self.start, "pageStart"),
+            end: clamp(self.end, "pageEnd"),
+        }
+    }
+
+    /// Pages in both ranges, e.g. a TextChunk's range inside its Section's.
+    pub fn intersect(self, other: PageRange) -> Self {
+        PageRange {
+            start: self.start.max(other.start),
+            end: match (self.end, other.end) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            },
+        }
+    }
+
+    pub fn contains(&self, page: u32) -> bool {
+        self.start.is_none_or(|start| page >= start) && self.end.is_none_or(|end| page <= end)
+    }
+}
+
+/// Drop candidates outside `range`, e.g. before choosing among repeated headings.
+pub fn restrict_to_pages(text_elements: Vec<TextElement>, range: PageRange) -> Vec<TextElement> {
+    text_elements
+        .into_iter()
+        .filter(|mi| range.contains(mi.page_number))
+        .collect()
+}
+
+/// A page area from a `region=[x0, y0, x1, y1]` attribute, in fractions of the
+/// page measured from its top-left corner, so `[0.5, 0, 1, 0.5]` is the top-right
+/// quarter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Region {
+    pub x0: f32,
+    pub y0: f32,
+    pub x1: f32,
+    pub y1: f32,
+}
+
+impl Region {
+    pub fn from_element(element: &Element) -> Option<Self> {
+        let Some(Value::Array(values)) = element.attributes.get("region") else {
+            return None;
+        };
+        let fractions: Vec<f32> = values
+            .iter()
+            .map(|value| value.as_number().map(|fraction| fraction as f32))
+            .collect::<Option<_>>()?;
+        let [x0, y0, x1, y1] = fractions[..] else {
+            return None;
+        };
+        Some(Region { x0, y0, x1, y1 })
+    }
+
+    /// Whether an element starts inside the region on a page of `geometry`. Text
+    /// widths aren't known, so an element's start point stands in for its box.
+    pub fn contains(&self, mi: &TextElement, geometry: &PageGeometry) -> bool {
+        let x = (mi.position.0 - geometry.left) / geometry.width;
+        let y = (geometry.bottom + geometry.height - mi.position.1) / geometry.height;
+        (self.x0..=self.x1).contains(&x) && (self.y0..=self.y1).contains(&y)
+    }
+}
+
+/// Drop elements outside `region`. Pages missing from `pages` are taken to be
+/// `DEFAULT_PAGE_GEOMETRY`.
+pub fn restrict_to_region(
+    text_elements: Vec<TextElement>,
+    region: &Region,
+    pages: &BTreeMap<u32, PageGeometry>,
+) -> Vec<TextElement> {
+    text_elements
+        .into_iter()
+        .filter(|mi| {
+            let geometry = pages.get(&mi.page_number).unwrap_or(&DEFAULT_PAGE_GEOMETRY);
+            region.contains(mi, geometry)
+        })
+        .collect()
+}
+
+/// Largest difference in any RGB channel for two colours to count as the same.
+pub const COLOR_TOLERANCE: u8 = 32;
+
+/// Whether `color` is within `tolerance` of `target` in every channel. Unknown
+/// colours match nothing.
+pub fn color_matches(color: Option<(u8, u8, u8)>, target: (u8, u8, u8), tolerance: u8) -> bool {
+    color.is_some_and(|(r, g, b)| {
+        r.abs_diff(target.0) <= tolerance
+            && g.abs_diff(target.1) <= tolerance
+            && b.abs_diff(target.2) <= tolerance
+    })
+}
+
+/// Elements whose fill colour is within `tolerance` of `target`.
+pub fn elements_by_color(
+    text_elements: &[TextElement],
+    target: (u8, u8, u8),
+    tolerance: u8,
+) -> Vec<TextElement> {
+    text_elements
+        .iter()
+        .filter(|mi| color_matches(mi.color, target, tolerance))
+        .cloned()
+        .collect()
+}
+
+fn color_attribute(element: &Element, key: &str) -> Option<(u8, u8, u8)> {
+    match element.attributes.get(key) {
+        Some(Value::String(color)) => parse_hex_color(color),
+        _ => None,
+    }
+}
+
+/// Style requirements from a Section's `minFontSize`, `maxFontSize`, `fontName`,
+/// `bold` and `color`, for telling a heading from body text that repeats its
+/// words. The default accepts everything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FontConstraints {
+    pub min_size: Option<f32>,
+    pub max_size: Option<f32>,
+    /// Matched case-insensitively anywhere in the font name, so "Arial" accepts
+    /// "ABCDEF+Arial-BoldMT".
+    pub font_name: Option<String>,
+    pub bold: Option<bool>,
+    /// Fill colour, matched within `COLOR_TOLERANCE`.
+    pub color: Option<(u8, u8, u8)>,
+}
+
+impl FontConstraints {
+    pub fn from_element(element: &Element) -> Self {
+        let size = |key: &str| {
+            element
+                .attributes
+                .get(key)
+                .and_then(Value::as_number)
+                .map(|size| size as f32)
+        };
+        FontConstraints {
+            min_size: size("minFontSize"),
+            max_size: size("maxFontSize"),
+            font_name: match element.attributes.get("fontName") {
+                Some(Value::String(name)) => Some(name.to_lowercase()),
+                _ => None,
+            },
+            bold: match element.attributes.get("bold") {
+                Some(Value::Boolean(bold)) => Some(*bold),
+                _ => None,
+            },
+            color: color_attribute(element, "color"),
+        }
+    }
+
+    pub fn accepts(&self, mi: &TextElement) -> bool {
+        let font_name = mi.font_name.as_deref().unwrap_or("");
+        self.min_size.is_none_or(|min| mi.font_size >= min)
+            && self.max_size.is_none_or(|max| mi.font_size <= max)
+            && self
+                .font_name
+                .as_ref()
+                .is_none_or(|name| font_name.to_lowercase().contains(name))
+            && self.bold.is_none_or(|bold| is_bold_font(font_name) == bold)
+            && self
+                .color
+                .is_none_or(|color| color_matches(mi.color, color, COLOR_TOLERANCE))
+    }
+}
+
+/// Drop candidates whose font doesn't meet `constraints`.
+pub fn restrict_to_fonts(
+    text_elements: Vec<TextElement>,
+    constraints: &FontConstraints,
+) -> Vec<TextElement> {
+    text_elements
+        .into_iter()
+        .filter(|mi| constraints.accepts(mi))
+        .collect()
+}
+
+/// Filters from a TextChunk's `includePattern`, `excludePattern` and
+/// `excludeColor`. The default keeps everything.
+#[derive(Debug, Clone, Default)]
+pub struct ChunkFilter {
+    /// When set, only lines matching it are kept.
+    pub include: Option<Regex>,
+    /// Lines matching it are dropped.
+    pub exclude: Option<Regex>,
+    /// Elements drawn in this colour, such as grey disclaimers, are dropped.
+    pub exclude_color: Option<(u8, u8, u8)>,
+}
+
+impl ChunkFilter {
+    /// Compile the patterns on a TextChunk element. Template validation already
+    /// rejects bad patterns, so this only fails for hand-built elements.
+    pub fn from_element(element: &Element) -> Result<Self, regex::Error> {
+        let pattern = |key: &str| match element.attributes.get(key) {
+            Some(Value::String(pattern)) => Regex::new(pattern).map(Some),
+            _ => Ok(None),
+        };
+        Ok(ChunkFilter {
+            include: pattern("includePattern")?,
+            exclude: pattern("excludePattern")?,
+            exclude_color: color_attribute(element, "excludeColor"),
+        })
+    }
+
+    pub fn keeps(&self, text: &str) -> bool {
+        self.include.as_ref().is_none_or(|re| re.is_match(text))
+            && !self.exclude.as_ref().is_some_and(|re| re.is_match(text))
+    }
+
+    /// Drop elements in the excluded colour, then the elements of every line
+    /// the patterns reject. Returns the elements kept, in order, and how many
+    /// were dropped.
+    pub fn filter_lines(&self, text_elements: &[TextElement]) -> (Vec<TextElement>, usize) {
+        let elements: Vec<TextElement> = match self.exclude_color {
+            Some(color) => text_elements
+                .iter()
+                .filter(|mi| !color_matches(mi.color, color, COLOR_TOLERANCE))
+                .cloned()
+                .collect(),
+            None => text_elements.to_vec(),
+        };
+        if self.include.is_none() && self.exclude.is_none() {
+            let filtered = text_elements.len() - elements.len();
+            return (elements, filtered);
+        }
+        let mut kept = Vec::new();
+        for line in group_text_into_lines(&elements, DEFAULT_LINE_JOIN_THRESHOLD) {
+            if self.keeps(&line.text) {
+                kept.extend(line.elements.iter().map(|&index| elements[index].clone()));
+            }
+        }
+        let filtered = text_elements.len() - kept.len();
+        (kept, filtered)
+    }
+}
+
+pub fn extract_section_content_with_options(
+    all_text_elements: &[TextElement],
+    best_match: &TextElement,
+    options: &SectionOptions,
+) -> String {
     // Sort text elements by page number and position
     let mut sorted_elements = all_text_elements.to_vec();
-    sorted_elements.sort_by(|a, b| {
+    sort_in_order(&mut sorted_elements, options.order);
+
+    // Find the index of the best match. `==` only compares style, so match on
+    // location and text instead.
+    let start_index = sorted_elements
+        .iter()
+        .position(|mi| is_same_element(mi, best_match))
+        .unwrap();
+    let limit = sorted_elements.len();
+    read_section(&sorted_elements, start_index, limit, options).0
+}
+
+/// The text of the section that `sorted_elements[start_index]` opens, ending at
+/// `limit` if no boundary comes first, and the index range of its body.
+fn read_section(
+    sorted_elements: &[TextElement],
+    start_index: usize,
+    limit: usize,
+    options: &SectionOptions,
+) -> (String, Range<usize>) {
+    let best_match = &sorted_elements[start_index];
+
+    // A heading that shares its run with the first sentence, or a next heading
+    // that shares one with the last, is cut at the offset so only the body is kept
+    let (heading, first, body_start) = match options.start_offset {
+        Some(offset) => split_heading_line(
+            sorted_elements,
+            start_index,
+            offset,
+            options.line_join_threshold,
+        ),
+        None => (
+            vec![sorted_elements[start_index].clone()],
+            None,
+            start_index + 1,
+        ),
+    };
+
+    let heading_size = font_size_percentile(sorted_elements, 0.9);
+    let limit = limit.max(body_start);
+    let candidates = &sorted_elements[body_start..limit];
+    let end_marker = options
+        .end_marker
+        .as_ref()
+        .and_then(|end| candidates.iter().position(|mi| is_same_element(mi, end)));
+    let end_index = end_marker
+        .or_else(|| {
+            candidates.iter().position(|mi| {
+                mi == best_match
+                    || (mi.font_size > best_match.font_size && mi.font_size > heading_size)
+            })
+        })
+        .map_or(limit, |offset| body_start + offset);
+
+    let last = options
+        .end_offset
+        .zip(sorted_elements.get(end_index))
+        .and_then(|(offset, end)| split_element(end, offset).0);
+
+    // Collect text from the best match up to the next section, keeping the
+    // heading itself whatever the filter says
+    let body: Vec<TextElement> = first
+        .into_iter()
+        .chain(sorted_elements[body_start..end_index].iter().cloned())
+        .chain(last)
+        .filter(|mi| options.pages.contains(mi.page_number))
+        .filter(|mi| {
+            options.region.is_none_or(|region| {
+                let geometry = options
+                    .page_geometry
+                    .get(&mi.page_number)
+                    .unwrap_or(&DEFAULT_PAGE_GEOMETRY);
+                region.contains(mi, geometry)
+            })
+        })
+        .collect();
+    let (body, _) = options.filter.filter_lines(&body);
+    let section: Vec<TextElement> = heading.into_iter().chain(body).collect();
+    let mut section_text = String::new();
+    for (index, mi) in section.iter().enumerate() {
+        match section.get(index + 1) {
+            Some(next) if options.dehyphenate && is_line_break_hyphen(mi, next) => {
+                section_text.push_str(mi.text.trim_end().trim_end_matches('-'));
+            }
+            _ => {
+                section_text.push_str(&mi.text);
+                section_text.push(' ');
+            }
+        }
+    }
+
+    (section_text, body_start..end_index)
+}
+
+/// Whether `a` and `b` are the same element. `==` only compares style, so this
+/// compares location and text instead.
+fn is_same_element(a: &TextElement, b: &TextElement) -> bool {
+    a.page_number == b.page_number && a.position == b.position && a.text == b.text
+}
+
+/// The index of the element of `line` that byte `offset` of its text falls in,
+/// and the offset within that element. An offset on the space inserted between
+/// two elements falls at the start of the second.
+fn element_at_offset(
+    text_elements: &[TextElement],
+    line: &TextLine,
+    offset: usize,
+) -> (usize, usize) {
+    let mut line_len = 0;
+    let mut previous: Option<&TextElement> = None;
+    for &index in &line.elements {
+        let mi = &text_elements[index];
+        if previous.is_some_and(|previous| {
+            !previous.text.ends_with(char::is_whitespace)
+                && !mi.text.starts_with(char::is_whitespace)
+        }) {
+            line_len += 1;
+        }
+        if offset < line_len + mi.text.len() {
+            return (index, offset.saturating_sub(line_len));
+        }
+        line_len += mi.text.len();
+        previous = Some(mi);
+    }
+    let last = *line.elements.last().unwrap();
+    (last, text_elements[last].text.len())
+}
+
+/// Split the line that `sorted_elements[start]` opens at byte `offset` of its text,
+/// joined as `group_text_into_lines_with_threshold` joins it. Returns the heading
+/// elements, the rest of the element the offset falls in, and the index the body
+/// goes on from.
+fn split_heading_line(
+    sorted_elements: &[TextElement],
+    start: usize,
+    offset: usize,
+    line_join_threshold: JoinThreshold,
+) -> (Vec<TextElement>, Option<TextElement>, usize) {
+    let marker = &sorted_elements[start];
+    let mut heading: Vec<TextElement> = Vec::new();
+    let mut line_len = 0;
+    for (index, mi) in sorted_elements.iter().enumerate().skip(start) {
+        if let Some(previous) = heading.last() {
+            if mi.page_number != marker.page_number
+                || (mi.position.1 - marker.position.1).abs()
+                    > line_join_threshold.resolve(mi.font_size)
+            {
+                return (heading, None, index);
+            }
+            if !previous.text.ends_with(char::is_whitespace)
+                && !mi.text.starts_with(char::is_whitespace)
+            {
+                line_len += 1;
+            }
+            if offset <= line_len {
+                return (heading, None, index);
+            }
+        }
+        if offset <= line_len + mi.text.len() {
+            let (before, after) = split_element(mi, offset - line_len);
+            heading.extend(before);
+            return (heading, after, index + 1);
+        }
+        line_len += mi.text.len();
+        heading.push(mi.clone());
+    }
+    (heading, None, sorted_elements.len())
+}
+
+/// The text of `mi` before and after byte `offset`, trimmed, as elements in its
+/// place. An empty side is `None`; an offset inside a character keeps `mi` whole.
+fn split_element(mi: &TextElement, offset: usize) -> (Option<TextElement>, Option<TextElement>) {
+    if !mi.text.is_char_boundary(offset) {
+        return (Some(mi.clone()), None);
+    }
+    let (before, after) = mi.text.split_at(offset);
+    let part = |text: &str| {
+        (!text.is_empty()).then(|| TextElement {
+            text: text.to_string(),
+            ..mi.clone()
+        })
+    };
+    (part(before.trim_end()), part(after.trim_start()))
+}
+
+/// True when `mi` ends its line with a word broken by a hyphen that `next`, on the
+/// following line, continues in lowercase. "non-" before "GAAP" is left alone.
+fn is_line_break_hyphen(mi: &TextElement, next: &TextElement) -> bool {
+    let text = mi.text.trim_end();
+    let new_line = next.page_number != mi.page_number
+        || (next.position.1 - mi.position.1).abs() > DEFAULT_LINE_JOIN_THRESHOLD;
+    new_line
+        && text
+            .strip_suffix('-')
+            .is_some_and(|word| word.ends_with(char::is_alphabetic))
+        && next.text.starts_with(char::is_lowercase)
+}
+
+/// How elements on a page are put into reading order.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ReadingOrder {
+    /// Top to bottom, then left to right, across the whole page.
+    #[default]
+    Raster,
+    /// Each detected column top to bottom, columns left to right.
+    Columns,
+}
+
+/// Minimum horizontal gap between element start positions, in multiples of the median
+/// font size, for the gap to be taken as a column gutter.
+const COLUMN_GAP_EM: f32 = 10.0;
+
+fn sort_in_order(text_elements: &mut [TextElement], order: ReadingOrder) {
+    sort_reading_order(text_elements);
+    if order == ReadingOrder::Columns {
+        for page in text_elements.chunk_by_mut(|a, b| a.page_number == b.page_number) {
+            sort_page_columns(page);
+        }
+    }
+}
+
+/// Reorder one page's elements column by column. Columns are split where the sorted
+/// start positions jump by more than `COLUMN_GAP_EM`, so this works for left-aligned
+/// text; a full-width line is read with the column it starts in.
+fn sort_page_columns(page: &mut [TextElement]) {
+    let mut xs: Vec<f32> = page.iter().map(|mi| mi.position.0).collect();
+    xs.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let min_gap = COLUMN_GAP_EM * font_size_percentile(page, 0.5);
+    let gutters: Vec<f32> = xs
+        .windows(2)
+        .filter(|pair| pair[1] - pair[0] > min_gap)
+        .map(|pair| (pair[0] + pair[1]) / 2.0)
+        .collect();
+    if gutters.is_empty() {
+        return;
+    }
+
+    // Stable, so the raster order is kept within each column
+    page.sort_by_key(|mi| gutters.iter().filter(|g| mi.position.0 > **g).count());
+}
+
+/// Every element of a document in reading order and the classified lines built
+/// from them, as written by `--dump-layout`. Line `elements` are indices into
+/// `elements`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LayoutDump {
+    pub elements: Vec<TextElement>,
+    pub lines: Vec<LayoutLine>,
+}
+
+/// A line and how [`classify_lines`] read it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LayoutLine {
+    #[serde(flatten)]
+    pub line: TextLine,
+    pub kind: LineKind,
+}
+
+/// Put `text_elements` in reading order, group them into lines and classify
+/// each line.
+pub fn layout_dump(text_elements: &[TextElement], order: ReadingOrder) -> LayoutDump {
+    let mut elements = text_elements.to_vec();
+    sort_in_order(&mut elements, order);
+    let lines = group_text_into_lines(&elements, DEFAULT_LINE_JOIN_THRESHOLD);
+    let kinds = classify_lines(&elements, &lines);
+    LayoutDump {
+        lines: lines
+            .into_iter()
+            .zip(kinds)
+            .map(|(line, kind)| LayoutLine { line, kind })
+            .collect(),
+        elements,
+    }
+}
+
+/// The text of each page as one string per line, in `order`.
+pub fn page_lines(
+    text_elements: &[TextElement],
+    order: ReadingOrder,
+) -> BTreeMap<u32, Vec<String>> {
+    let mut sorted_elements = text_elements.to_vec();
+    sort_in_order(&mut sorted_elements, order);
+
+    let mut pages: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+    for line in group_text_into_lines(&sorted_elements, DEFAULT_LINE_JOIN_THRESHOLD) {
+        pages.entry(line.page_number).or_default().push(line.text);
+    }
+    pages
+}
+
+/// Order elements by page, then top to bottom, then left to right.
+fn sort_reading_order(text_elements: &mut [TextElement]) {
+    text_elements.sort_by(|a, b| {
         a.page_number
             .cmp(&b.page_number)
             .then_with(|| {
-                a.position
+                // PDF y grows upwards, so the top of the page comes first
+                b.position
                     .1
-                    .partial_cmp(&b.position.1)
+                    .partial_cmp(&a.position.1)
                     .unwrap_or(std::cmp::Ordering::Equal)
             })
             .then_with(|| {
@@ -66,22 +1844,233 @@ fn extract_section_content(all_text_elements: &[TextElement], best_match: &TextE
                     .unwrap_or(std::cmp::Ordering::Equal)
             })
     });
+}
 
-    // Find the index of the best match
-    let start_index = sorted_elements
-        .iter()
-        .position(|mi| mi == best_match)
-        .unwrap();
+fn font_size_percentile(text_elements: &[TextElement], percentile: f32) -> f32 {
+    let mut sizes: Vec<f32> = text_elements.iter().map(|mi| mi.font_size).collect();
+    if sizes.is_empty() {
+        return 0.0;
+    }
+    sizes.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let index = ((sizes.len() - 1) as f32 * percentile).round() as usize;
+    sizes[index]
+}
 
-    // Collect text from the best match onwards
-    let mut section_text = String::new();
-    for mi in &sorted_elements[start_index..] {
-        // Optionally, stop if you detect the start of the next section
-        section_text.push_str(&mi.text);
-        section_text.push(' ');
+/// A template Section found in a document.
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateSection {
+    /// The Section's `as` name, if it has one.
+    pub name: Option<String>,
+    /// The whole line the Section's `match` was found on.
+    pub heading: String,
+    pub page: u32,
+    pub text: String,
+}
+
+/// How `extract_template_sections_with_options` finds and reads each Section.
+#[derive(Debug, Clone, Default)]
+pub struct TemplateOptions {
+    pub matching: MatchOptions,
+    pub order: ReadingOrder,
+}
+
+/// Find every Section of `root` with a `match`, nested ones included, in the
+/// cleaned-up `text_elements` of `doc`, and extract its text. A nested Section is
+/// only looked for in the body of the Section around it. Sections that match
+/// nothing are left out. A Section ends where the first line after its
+/// heading matches its `end_match`, or at the next natural boundary when it has
+/// none or nothing matches. A Section's font and colour attributes, `region`,
+/// `pageStart` and `pageEnd` narrow where it may start, and the page bounds also
+/// limit its body; the first TextChunk inside it filters the body, by pattern and
+/// colour, and bounds it by page and region. Fails only for a hand-built template whose patterns don't
+/// compile.
+pub fn extract_template_sections(
+    doc: &Document,
+    text_elements: &[TextElement],
+    root: &Root,
+) -> Result<Vec<TemplateSection>, DelverError> {
+    extract_template_sections_with_options(doc, text_elements, root, &TemplateOptions::default())
+}
+
+pub fn extract_template_sections_with_options(
+    doc: &Document,
+    text_elements: &[TextElement],
+    root: &Root,
+    options: &TemplateOptions,
+) -> Result<Vec<TemplateSection>, DelverError> {
+    let toc_pages = find_toc_pages(text_elements);
+    let outlines = get_outline_entries(doc);
+    let link_targets = collect_link_targets(doc);
+    let page_count = doc.get_pages().len() as u32;
+    let page_geometry = get_page_geometry(doc);
+    // Match on the lines the section is read from, so the heading's offsets
+    // line up with the elements it is split at
+    let mut sorted_elements = text_elements.to_vec();
+    sort_in_order(&mut sorted_elements, options.order);
+    let text_elements = &sorted_elements[..];
+    let lines =
+        group_text_into_lines_with_threshold(text_elements, options.matching.line_join_threshold);
+
+    // Find `section` among the elements in `within`, returning it and the range
+    // of its body
+    let find = |section: &Element,
+                within: Range<usize>|
+     -> Result<Option<(TemplateSection, Range<usize>)>, DelverError> {
+        let invalid_regex = |e: regex::Error| TemplateError {
+            line: 0,
+            column: 0,
+            element: Some(section.name.clone()),
+            message: format!("invalid regex: {e}"),
+        };
+        let Some(pattern) = MatchPattern::from_element(section, "match").map_err(invalid_regex)?
+        else {
+            return Ok(None);
+        };
+        let name = match section.attributes.get("as") {
+            Some(Value::String(name)) => Some(name.clone()),
+            _ => None,
+        };
+        let pages = PageRange::from_element(section).clamp(page_count);
+        let matched: Vec<TextElement> = lines
+            .iter()
+            .filter(|line| within.contains(&line.elements[0]))
+            .filter(|line| pattern.span(&line.text, &options.matching).is_some())
+            .map(|line| text_elements[line.elements[0]].clone())
+            .collect();
+        let matched = restrict_to_fonts(
+            restrict_to_pages(matched, pages),
+            &FontConstraints::from_element(section),
+        );
+        let matched = match Region::from_element(section) {
+            Some(region) => restrict_to_region(matched, &region, &page_geometry),
+            None => matched,
+        };
+        let mut bookmarks = pattern.outline_entries(&outlines, &options.matching);
+        bookmarks.retain(|entry| pages.contains(entry.page));
+        let Some(best_match) = select_best_match_with_references(
+            matched,
+            &text_elements[within.clone()],
+            &toc_pages,
+            TocPolicy::Penalize(DEFAULT_TOC_PENALTY),
+            &bookmarks,
+            &link_targets,
+        ) else {
+            warn!(
+                "No match for Section {}",
+                name.as_deref().unwrap_or(pattern.as_str())
+            );
+            return Ok(None);
+        };
+
+        // The match was made against the whole line, which can run past the
+        // element reported for it. A bookmark can land part way along a line, in
+        // which case only the element is the heading.
+        let start = within.start
+            + text_elements[within.clone()]
+                .iter()
+                .position(|mi| is_same_element(mi, &best_match))
+                .unwrap();
+        let line_index = lines
+            .iter()
+            .position(|line| line.elements.contains(&start))
+            .unwrap();
+        let heading = match &lines[line_index] {
+            line if line.elements[0] == start => &line.text,
+            _ => &best_match.text,
+        };
+
+        // The first line after the heading that matches `end_match` ends the
+        // section where the match starts
+        let end = MatchPattern::from_element(section, "end_match")
+            .map_err(invalid_regex)?
+            .and_then(|end_pattern| {
+                lines[line_index + 1..]
+                    .iter()
+                    .take_while(|line| line.elements[0] < within.end)
+                    .find_map(|line| {
+                        end_pattern
+                            .span(&line.text, &options.matching)
+                            .map(|span| element_at_offset(text_elements, line, span.start))
+                    })
+            });
+
+        let chunk = section
+            .children
+            .iter()
+            .find(|child| child.name == "TextChunk");
+        let body_pages = match chunk {
+            Some(chunk) => pages.intersect(PageRange::from_element(chunk).clamp(page_count)),
+            None => pages,
+        };
+        let filter = match chunk {
+            Some(chunk) => ChunkFilter::from_element(chunk).map_err(|e| TemplateError {
+                element: Some(chunk.name.clone()),
+                ..invalid_regex(e)
+            })?,
+            None => ChunkFilter::default(),
+        };
+        let section_options = SectionOptions {
+            order: options.order,
+            filter,
+            pages: body_pages,
+            region: chunk.and_then(Region::from_element),
+            page_geometry: page_geometry.clone(),
+            start_offset: pattern
+                .span(heading, &options.matching)
+                .map(|span| span.end),
+            end_marker: end.map(|(index, _)| text_elements[index].clone()),
+            end_offset: end.map(|(_, offset)| offset),
+            line_join_threshold: options.matching.line_join_threshold,
+            ..Default::default()
+        };
+        let (text, body) = read_section(text_elements, start, within.end, &section_options);
+        Ok(Some((
+            TemplateSection {
+                name,
+                heading: heading.clone(),
+                page: best_match.page_number,
+                text,
+            },
+            body,
+        )))
+    };
+
+    // A nested Section is only looked for in the body of the one around it.
+    // Sections are visited parents first, in template order.
+    let mut extracted = Vec::new();
+    let mut pending: Vec<(&Element, Range<usize>)> = root
+        .elements
+        .iter()
+        .rev()
+        .map(|element| (element, 0..text_elements.len()))
+        .collect();
+    while let Some((element, within)) = pending.pop() {
+        let mut body = within.clone();
+        if element.name == "Section" {
+            if let Some((section, range)) = find(element, within)? {
+                extracted.push(section);
+                body = range;
+            }
+        }
+        pending.extend(
+            element
+                .children
+                .iter()
+                .rev()
+                .map(|child| (child, body.clone())),
+        );
     }
+    Ok(extracted)
+}
 
-    section_text
+/// Every Section in `elements`, parents before their children.
+pub fn collect_sections<'a>(elements: &'a [Element], sections: &mut Vec<&'a Element>) {
+    for element in elements {
+        if element.name == "Section" {
+            sections.push(element);
+        }
+        collect_sections(&element.children, sections);
+    }
 }
 
 pub fn extract_sections(doc: &Document, sections: &[&str]) -> Vec<(String, String)> {
@@ -0,0 +1,6 @@
+pub mod builder;
+pub mod dom;
+pub mod error;
+pub mod fonts;
+pub mod layout;
+pub mod parse;
@@ -1,17 +1,17 @@
 use std::collections::{BTreeMap, HashMap};
 use std::fmt::Debug;
+use std::io::Read;
+use std::time::{Duration, Instant};
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
+use delver::dom::*;
+use delver::error::DelverError;
+use delver::layout::*;
+use delver::parse::*;
 use lopdf::{Dictionary, Document, Encoding, Error as LopdfError, Object, Result as LopdfResult};
-pub mod dom;
-pub mod layout;
-pub mod parse;
-use crate::dom::*;
-use crate::layout::*;
-use crate::parse::*;
 
 use log::{debug, error, warn};
 
@@ -21,65 +21,427 @@ use log::{debug, error, warn};
     version,
     about,
     long_about = "Extract TOC and write to file.",
-    arg_required_else_help = true
+    arg_required_else_help = true,
+    args_conflicts_with_subcommands = true
 )]
 pub struct Args {
-    pub pdf_path: PathBuf,
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
+    /// PDF to read, or `-` for stdin.
+    pub pdf_path: Option<PathBuf>,
+
+    /// Template file. Defaults to 10k.tmpl.
+    #[clap(long, conflicts_with = "template_str")]
+    pub template: Option<PathBuf>,
+
+    /// Template given inline instead of as a file.
+    #[clap(long)]
+    pub template_str: Option<String>,
 
     /// Optional output directory. If omitted the directory of the PDF file will be used.
     #[clap(short, long)]
     pub output: Option<PathBuf>,
 
-    /// Optional pretty print output.
+    /// Pretty-print the JSON output instead of writing it compactly.
     #[clap(short, long)]
     pub pretty: bool,
 
     /// Optional password for encrypted PDFs
-    #[clap(long, default_value_t = String::from(""))]
-    pub password: String,
+    #[clap(long, global = true)]
+    pub password: Option<String>,
+
+    /// Baseline distance for joining text into lines, in points or relative to the
+    /// font size (e.g. 1.2em).
+    #[clap(long, default_value = "5")]
+    pub line_join_threshold: JoinThreshold,
+
+    /// Read multi-column pages column by column instead of straight across.
+    #[clap(long)]
+    pub columns: bool,
+
+    /// Keep running headers and footers in the extracted text.
+    #[clap(long)]
+    pub keep_headers_footers: bool,
+
+    /// Write merged footnote markers and subscripts as "[1]" instead of "¹".
+    #[clap(long)]
+    pub bracket_scripts: bool,
+
+    /// Ignore invisible text, such as the OCR layer of a scanned page.
+    #[clap(long)]
+    pub skip_invisible_text: bool,
+
+    /// Fail on the first page that can't be read instead of skipping it.
+    #[clap(long)]
+    pub strict: bool,
+
+    /// Only read these pages, e.g. 1-80,120-130. Sections end at the last
+    /// selected page.
+    #[clap(long)]
+    pub pages: Option<PageSelection>,
+
+    /// Replace curly quotes and en and em dashes with ASCII in extracted text.
+    #[clap(long)]
+    pub ascii_punctuation: bool,
+
+    /// Horizontal jump, in ems, that splits a line of text into separate
+    /// elements, e.g. the cells of a table row.
+    #[clap(long, default_value = "1.5")]
+    pub column_gap: f32,
+
+    /// Write every line and element, with positions, fonts and line kinds, to
+    /// this JSON file.
+    #[clap(long)]
+    pub dump_layout: Option<PathBuf>,
+
+    /// Print how long each stage took to stderr.
+    #[clap(long)]
+    pub timings: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Check a template and, given a PDF, list the candidates each Section would
+    /// start and end at, without extracting anything.
+    Validate {
+        #[clap(long)]
+        template: PathBuf,
+
+        #[clap(long)]
+        pdf: Option<PathBuf>,
+    },
+    /// Print the text of every page as JSON, one string per line, without a
+    /// template.
+    Text {
+        /// PDF to read, or `-` for stdin.
+        pdf: PathBuf,
+
+        /// Pretty-print the JSON output.
+        #[clap(short, long)]
+        pretty: bool,
+    },
+    /// List the lines that look like headings, with their scores and levels,
+    /// as a starting point for writing a template.
+    Headings {
+        pdf: PathBuf,
+
+        /// Lowest score to list.
+        #[clap(long)]
+        min_score: Option<f32>,
+    },
+}
+
+/// Template used when neither `--template` nor `--template-str` is given.
+const DEFAULT_TEMPLATE: &str = "10k.tmpl";
+
+/// Wall time of each stage of a run, printed with `--timings`.
+#[derive(Debug, Default)]
+struct Timings {
+    stages: Vec<(&'static str, Duration)>,
+    /// Per-page extraction times; empty with `--strict`.
+    pages: Vec<Duration>,
+}
+
+impl Timings {
+    fn record(&mut self, stage: &'static str, start: Instant) {
+        self.stages.push((stage, start.elapsed()));
+    }
+
+    fn print(&self) {
+        let ms = |duration: &Duration| duration.as_secs_f64() * 1000.0;
+        eprintln!("{:<14} {:>10}", "stage", "ms");
+        for (stage, duration) in &self.stages {
+            eprintln!("{:<14} {:>10.3}", stage, ms(duration));
+        }
+        if let Some(max) = self.pages.iter().max() {
+            let mean = self.pages.iter().map(ms).sum::<f64>() / self.pages.len() as f64;
+            eprintln!("{:<14} {:>10.3}", "page (max)", ms(max));
+            eprintln!("{:<14} {:>10.3}", "page (mean)", mean);
+        }
+    }
 }
 
+/// Candidates listed per boundary by `validate`.
+const VALIDATE_CANDIDATES: usize = 5;
+
 impl Args {
     pub fn parse_args() -> Self {
         Args::parse()
     }
 }
 
-fn main() -> Result<(), lopdf::Error> {
-    println!("Starting PDF processing");
-
-    // Read and parse the template file
-    let template_str = std::fs::read_to_string("10k.tmpl").expect("Failed to read template file");
+fn main() -> Result<(), DelverError> {
+    let args = Args::parse_args();
+    match &args.command {
+        Some(Command::Validate { template, pdf }) => {
+            return validate(template, pdf.as_deref(), &args)
+        }
+        Some(Command::Text { pdf, pretty }) => return print_text(pdf, *pretty, &args),
+        Some(Command::Headings { pdf, min_score }) => {
+            return print_headings(pdf, *min_score, &args)
+        }
+        None => {}
+    }
+    let Some(pdf_path) = &args.pdf_path else {
+        eprintln!("A PDF path is required");
+        std::process::exit(2);
+    };
+    eprintln!("Starting PDF processing");
+    let mut timings = Timings::default();
 
-    let dom = parse_template(&template_str);
-    println!("Parsed template: {:?}", dom);
+    // Read and parse the template
+    let start = Instant::now();
+    let (template_name, template) = match &args.template_str {
+        Some(template_str) => (
+            "--template-str".to_string(),
+            parse_template_auto(template_str).map_err(DelverError::from),
+        ),
+        None => {
+            let path = args
+                .template
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_TEMPLATE));
+            (path.display().to_string(), load_template(&path))
+        }
+    };
+    let dom = match template {
+        Ok(dom) => dom,
+        Err(DelverError::Template(e)) => {
+            eprintln!("Invalid template {template_name} at {e}");
+            std::process::exit(1);
+        }
+        Err(e) => return Err(e),
+    };
+    debug!("Parsed template: {:?}", dom);
+    timings.record("template", start);
 
-    let pdf_path = "tests/3M_2015_10k.pdf";
-    let doc = Document::load(pdf_path)?;
+    let start = Instant::now();
+    let doc = load_document(pdf_path, &args)?;
+    timings.record("load", start);
 
     // Extract text elements with metadata
-    let text_elements = get_pdf_text(&doc)?;
+    let start = Instant::now();
+    let text_options = TextOptions {
+        pages: args.pages.clone(),
+        normalization: TextNormalization {
+            punctuation: args.ascii_punctuation,
+            ..Default::default()
+        },
+        column_gap: args.column_gap,
+    };
+    let text_elements = if args.strict {
+        get_pdf_text_with_options(&doc, &text_options)?
+    } else {
+        let report = get_pdf_text_report_with_options(&doc, &text_options);
+        for warning in &report.warnings {
+            eprintln!("Skipped page {}: {}", warning.page, warning.message);
+        }
+        timings.pages = report.page_durations;
+        report.text_elements
+    };
+    timings.record("text", start);
 
-    // Define the search string from your template
-    let search_string = "Discussion and Analysis of Financial Condition and Results of Operations";
+    let start = Instant::now();
+    let script_style = if args.bracket_scripts {
+        ScriptStyle::Bracketed
+    } else {
+        ScriptStyle::Unicode
+    };
+    let text_elements = dedupe_text_layers(&text_elements, !args.skip_invisible_text);
+    let text_elements = merge_script_runs(&text_elements, script_style);
+    let text_elements = if args.keep_headers_footers {
+        text_elements
+    } else {
+        strip_headers_footers(&text_elements, &get_page_geometry(&doc))
+    };
+    timings.record("cleanup", start);
 
-    // Perform matching
-    let matched_elements = perform_matching(text_elements, search_string);
+    let order = if args.columns {
+        ReadingOrder::Columns
+    } else {
+        ReadingOrder::Raster
+    };
+    if let Some(path) = &args.dump_layout {
+        let dump = layout_dump(&text_elements, order);
+        std::fs::write(path, serde_json::to_string_pretty(&dump)?)?;
+        eprintln!(
+            "Wrote layout of {} lines to {}",
+            dump.lines.len(),
+            path.display()
+        );
+    }
 
-    // Apply heuristics to select the best match
-    if let Some(best_match) = select_best_match(matched_elements) {
-        println!(
-            "Best match found on page {}: {}",
-            best_match.page_number, best_match.text
+    // Find and extract every Section of the template
+    let start = Instant::now();
+    let template_options = TemplateOptions {
+        matching: MatchOptions {
+            line_join_threshold: args.line_join_threshold,
+            ..Default::default()
+        },
+        order,
+    };
+    let sections =
+        extract_template_sections_with_options(&doc, &text_elements, &dom, &template_options)?;
+    for section in &sections {
+        eprintln!(
+            "Section {} found on page {}: {} ({} characters)",
+            section.name.as_deref().unwrap_or("(unnamed)"),
+            section.page,
+            section.heading,
+            section.text.len()
         );
-        println!("Font size: {}", best_match.font_size);
-        println!("Position: {:?}", best_match.position);
+    }
+    timings.record("sections", start);
+
+    let start = Instant::now();
+    let json = if args.pretty {
+        serde_json::to_string_pretty(&sections)?
+    } else {
+        serde_json::to_string(&sections)?
+    };
+    println!("{json}");
+    timings.record("serialization", start);
 
-        // Proceed to extract the section content starting from this match
-        // You may need to implement additional logic to collect the section content
+    if args.timings {
+        timings.print();
+    }
+    Ok(())
+}
+
+fn read_stdin() -> Result<Vec<u8>, DelverError> {
+    let mut bytes = Vec::new();
+    std::io::stdin().read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Load `pdf_path`, or stdin for `-`, decrypting it with `--password`.
+fn load_document(pdf_path: &Path, args: &Args) -> Result<Document, DelverError> {
+    let options = PdfOptions {
+        password: args.password.clone(),
+    };
+    if pdf_path.as_os_str() == "-" {
+        load_pdf_bytes_with_options(&read_stdin()?, &options)
     } else {
-        println!("No matching section found.");
+        load_pdf_with_options(pdf_path, &options)
     }
+}
 
+/// Write the plain text of `pdf_path` to stdout.
+fn print_text(pdf_path: &Path, pretty: bool, args: &Args) -> Result<(), DelverError> {
+    let text = extract_document_text(&load_document(pdf_path, args)?);
+    let json = if pretty {
+        serde_json::to_string_pretty(&text)?
+    } else {
+        serde_json::to_string(&text)?
+    };
+    println!("{json}");
+    Ok(())
+}
+
+/// Print the heading candidates in `pdf_path`, one per line, indented by level.
+fn print_headings(pdf_path: &Path, min_score: Option<f32>, args: &Args) -> Result<(), DelverError> {
+    let doc = load_document(pdf_path, args)?;
+    let report = get_pdf_text_report(&doc);
+    for warning in &report.warnings {
+        eprintln!("Skipped page {}: {}", warning.page, warning.message);
+    }
+    let text_elements = strip_headers_footers(
+        &dedupe_text_layers(&report.text_elements, true),
+        &get_page_geometry(&doc),
+    );
+    let mut config = HeadingConfig::default();
+    if let Some(min_score) = min_score {
+        config.min_score = min_score;
+    }
+    for heading in detect_headings(&text_elements, &get_outline_entries(&doc), &config) {
+        println!(
+            "{:>6.1}  page {:<4} {}{}",
+            heading.score,
+            heading.element.page_number,
+            "  ".repeat(heading.level - 1),
+            heading.text
+        );
+    }
+    Ok(())
+}
+
+/// Parse `template_path`, then report the top start and end candidates for each
+/// Section in `pdf_path`.
+fn validate(template_path: &Path, pdf_path: Option<&Path>, args: &Args) -> Result<(), DelverError> {
+    let dom = match load_template(template_path) {
+        Ok(dom) => dom,
+        Err(DelverError::Template(e)) => {
+            let element = e.element.as_deref().unwrap_or("template");
+            eprintln!("{}: {element}: {e}", template_path.display());
+            std::process::exit(1);
+        }
+        Err(e) => return Err(e),
+    };
+    println!("{}: template is valid", template_path.display());
+
+    let Some(pdf_path) = pdf_path else {
+        return Ok(());
+    };
+    let doc = load_document(pdf_path, args)?;
+    let report = get_pdf_text_report(&doc);
+    for warning in &report.warnings {
+        eprintln!("Skipped page {}: {}", warning.page, warning.message);
+    }
+    let page_geometry = get_page_geometry(&doc);
+    let text_elements = strip_headers_footers(
+        &dedupe_text_layers(&report.text_elements, true),
+        &page_geometry,
+    );
+    let toc_pages = find_toc_pages(&text_elements);
+    let outlines = get_outline_entries(&doc);
+    let link_targets = collect_link_targets(&doc);
+    let match_options = MatchOptions::default();
+
+    let mut sections = Vec::new();
+    collect_sections(&dom.elements, &mut sections);
+    for section in sections {
+        let fonts = FontConstraints::from_element(section);
+        let region = Region::from_element(section);
+        let label = match section.attributes.get("as") {
+            Some(Value::String(alias)) => alias.clone(),
+            _ => "(unnamed)".to_string(),
+        };
+        println!("Section {label}");
+        for (boundary, key) in [("start", "match"), ("end", "end_match")] {
+            // The template was validated, so its regexes compile
+            let Ok(Some(pattern)) = MatchPattern::from_element(section, key) else {
+                continue;
+            };
+            let mut matched = restrict_to_fonts(
+                pattern.matching_lines(&text_elements, &match_options),
+                &fonts,
+            );
+            if let (Some(region), "start") = (&region, boundary) {
+                matched = restrict_to_region(matched, region, &page_geometry);
+            }
+            let bookmarks = pattern.outline_entries(&outlines, &match_options);
+            let candidates = rank_candidates(
+                matched,
+                &text_elements,
+                &toc_pages,
+                TocPolicy::Penalize(DEFAULT_TOC_PENALTY),
+                &bookmarks,
+                &link_targets,
+            );
+            println!(
+                "  {boundary} \"{}\": {} candidates",
+                pattern.as_str(),
+                candidates.len()
+            );
+            for candidate in candidates.iter().take(VALIDATE_CANDIDATES) {
+                let snippet: String = candidate.element.text.chars().take(60).collect();
+                println!(
+                    "    {:>6.1}  page {:<4} {}",
+                    candidate.score, candidate.element.page_number, snippet
+                );
+            }
+        }
+    }
     Ok(())
 }
@@ -1,18 +1,31 @@
 use indexmap::IndexMap;
-use std::collections::BTreeMap;
+#[cfg(feature = "font-parsing")]
+use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::Debug;
 use std::fs::File;
-use std::io::{Error, ErrorKind, Write};
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::time::Instant;
+use std::sync::{Arc, LazyLock};
+#[cfg(feature = "font-parsing")]
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
 
 use log::{debug, error, warn};
 
+use lopdf::content::{Content, Operation};
+use lopdf::encryption::DecryptionError;
 use lopdf::{
-    Dictionary, Document, Encoding, Error as LopdfError, Object, Outline, Result as LopdfResult,
-    Toc,
+    decode_text_string, dictionary, Dictionary, Document, Encoding, Error as LopdfError, Object,
+    ObjectId, Outline, Reader, Result as LopdfResult, Stream, Toc,
 };
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::error::DelverError;
+#[cfg(feature = "font-parsing")]
+use crate::fonts::{program_metrics, GlyphMapping};
+use crate::fonts::{registered_metrics, FontMetrics};
+use crate::layout::{page_lines, ReadingOrder};
 use serde::{Deserialize, Serialize};
 use serde_json;
 use shellexpand;
@@ -39,14 +52,12 @@ static IGNORE: &[&str] = &[
     "PTEX.FileName",
     "PTEX.PageNumber",
     "PTEX.InfoDict",
-    "FontDescriptor",
     "ExtGState",
-    "MediaBox",
-    "Annot",
 ];
 
 fn filter_func(object_id: (u32, u16), object: &mut Object) -> Option<((u32, u16), Object)> {
-    if IGNORE.contains(&object.type_name().unwrap_or_default()) {
+    // Form XObjects carry text of their own and are needed for extraction.
+    if IGNORE.contains(&object.type_name().unwrap_or_default()) && !is_form_xobject(object) {
         return None;
     }
     if let Ok(d) = object.as_dict_mut() {
@@ -55,9 +66,6 @@ fn filter_func(object_id: (u32, u16), object: &mut Object) -> Option<((u32, u16)
         d.remove(b"Creator");
         d.remove(b"ProcSet");
         d.remove(b"Procset");
-        d.remove(b"XObject");
-        d.remove(b"MediaBox");
-        d.remove(b"Annots");
         if d.is_empty() {
             return None;
         }
@@ -65,28 +73,108 @@ fn filter_func(object_id: (u32, u16), object: &mut Object) -> Option<((u32, u16)
     Some((object_id, object.to_owned()))
 }
 
+fn is_form_xobject(object: &Object) -> bool {
+    object.as_stream().is_ok_and(|stream| {
+        stream
+            .dict
+            .get(b"Subtype")
+            .and_then(Object::as_name_str)
+            .ok()
+            == Some("Form")
+    })
+}
+
+/// Maximum depth of nested Form XObjects followed when collecting text.
+const MAX_FORM_DEPTH: usize = 16;
+
+const IDENTITY_MATRIX: [f32; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct PdfText {
     pub text: BTreeMap<u32, Vec<String>>, // Key is page number
     pub errors: Vec<String>,
+    /// Size and rotation of every page, so positions can be placed on it.
+    pub pages: BTreeMap<u32, PageGeometry>,
+}
+
+/// Plain text of a PDF in memory, one string per line in reading order, with
+/// an error for each page that couldn't be read. No template is involved.
+pub fn extract_text(pdf_bytes: &[u8]) -> Result<PdfText, DelverError> {
+    Ok(extract_document_text(&load_pdf_bytes(pdf_bytes)?))
+}
+
+/// Like `extract_text`, for a document that is already loaded.
+pub fn extract_document_text(doc: &Document) -> PdfText {
+    let report = get_pdf_text_report(doc);
+    PdfText {
+        text: page_lines(&report.text_elements, ReadingOrder::default()),
+        errors: report
+            .warnings
+            .iter()
+            .map(|warning| format!("page {}: {}", warning.page, warning.message))
+            .collect(),
+        pages: get_page_geometry(doc),
+    }
 }
 
 #[cfg(not(feature = "async"))]
-pub fn load_pdf<P: AsRef<Path>>(path: P) -> Result<Document, Error> {
-    Document::load_filtered(path, filter_func)
-        .map_err(|e| Error::new(ErrorKind::Other, e.to_string()))
+pub fn load_pdf<P: AsRef<Path>>(path: P) -> Result<Document, DelverError> {
+    Ok(Document::load_filtered(path, filter_func)?)
 }
 
 #[cfg(feature = "async")]
-fn load_pdf<P: AsRef<Path>>(path: P) -> Result<Document, Error> {
+fn load_pdf<P: AsRef<Path>>(path: P) -> Result<Document, DelverError> {
     Ok(Builder::new_current_thread()
         .build()
         .unwrap()
-        .block_on(async move {
-            Document::load_filtered(path, filter_func)
-                .await
-                .map_err(|e| Error::new(ErrorKind::Other, e.to_string()))
-        })?)
+        .block_on(async move { Document::load_filtered(path, filter_func).await })?)
+}
+
+/// Options controlling how a PDF is opened.
+#[derive(Debug, Clone, Default)]
+pub struct PdfOptions {
+    /// Password for encrypted PDFs. When omitted the empty user password is tried.
+    pub password: Option<String>,
+}
+
+pub fn load_pdf_with_options<P: AsRef<Path>>(
+    path: P,
+    options: &PdfOptions,
+) -> Result<Document, DelverError> {
+    open_pdf(load_pdf(path)?, options)
+}
+
+/// Load a PDF already in memory, e.g. read from stdin, with the same filtering
+/// as `load_pdf`.
+pub fn load_pdf_bytes(bytes: &[u8]) -> Result<Document, DelverError> {
+    Ok(Reader {
+        buffer: bytes,
+        document: Document::new(),
+    }
+    .read(Some(filter_func))?)
+}
+
+pub fn load_pdf_bytes_with_options(
+    bytes: &[u8],
+    options: &PdfOptions,
+) -> Result<Document, DelverError> {
+    open_pdf(load_pdf_bytes(bytes)?, options)
+}
+
+fn open_pdf(mut doc: Document, options: &PdfOptions) -> Result<Document, DelverError> {
+    if doc.is_encrypted() {
+        decrypt_pdf(&mut doc, options.password.as_deref().unwrap_or(""))?;
+    }
+    Ok(doc)
+}
+
+fn decrypt_pdf(doc: &mut Document, password: &str) -> Result<(), DelverError> {
+    doc.decrypt(password).map_err(|e| match e {
+        LopdfError::Decryption(DecryptionError::IncorrectPassword) => {
+            DelverError::IncorrectPassword
+        }
+        e => DelverError::PdfLoad(e),
+    })
 }
 
 /// Struct for how the text is tokenized
@@ -104,7 +192,75 @@ struct TextState {
     text_matrix: [f32; 6],
     text_line_matrix: [f32; 6],
     position: (f32, f32),
+    /// Distance along the line from `position` to where `text_buffer` starts,
+    /// once a column jump has split the line.
+    run_offset: f32,
+    /// `Tz`, as a fraction.
+    horizontal_scaling: f32,
+    /// `TL`, the distance `T*`, `'` and `"` move down to the next line.
+    leading: f32,
+    /// `Tc`, added after every glyph. Negative values tighten the text.
+    char_spacing: f32,
+    /// `Tw`, added after every single-byte space.
+    word_spacing: f32,
+    /// How far the text in `text_buffer` advances, spacing included, before
+    /// horizontal scaling.
+    run_width: f32,
+    /// Widths of the current font, when registered.
+    metrics: Option<Arc<FontMetrics>>,
     text_buffer: String,
+    render_mode: u8,
+    /// `Ts`, the distance text is raised above the baseline.
+    rise: f32,
+    /// How the text in `text_buffer` was drawn.
+    run_style: RunStyle,
+}
+
+/// How a run of text is drawn. Text shown in a different style starts a new
+/// element, so a bold word or a superscript isn't reported in the style of what
+/// follows it.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct RunStyle {
+    font_name: Option<String>,
+    /// The `Tf` size scaled by the text matrix and CTM, i.e. as drawn on the page.
+    font_size: f32,
+    /// 1 for raised text, -1 for lowered and 0 on the baseline.
+    rise: i8,
+    color: Option<(u8, u8, u8)>,
+}
+
+impl TextState {
+    /// Take the font, size, spacing, scaling, leading, render mode and rise from
+    /// `other`. They are text state, which outlives text objects and is saved
+    /// and restored with the rest of the graphics state.
+    fn set_parameters(&mut self, other: &TextState) {
+        self.font_name = other.font_name.clone();
+        self.font_size = other.font_size;
+        self.metrics = other.metrics.clone();
+        self.render_mode = other.render_mode;
+        self.horizontal_scaling = other.horizontal_scaling;
+        self.leading = other.leading;
+        self.char_spacing = other.char_spacing;
+        self.word_spacing = other.word_spacing;
+        self.rise = other.rise;
+    }
+
+    /// The style text shown now would be drawn in.
+    fn style(&self, color: Option<(u8, u8, u8)>, ctm: &[f32; 6]) -> RunStyle {
+        let [_, _, c, d, _, _] = multiply_matrices(&self.text_matrix, ctm);
+        RunStyle {
+            font_name: self.font_name.clone(),
+            font_size: self.font_size * (c * c + d * d).sqrt(),
+            rise: if self.rise > 0.0 {
+                1
+            } else if self.rise < 0.0 {
+                -1
+            } else {
+                0
+            },
+            color,
+        }
+    }
 }
 
 impl Default for TextState {
@@ -115,18 +271,37 @@ impl Default for TextState {
             text_matrix: [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
             text_line_matrix: [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
             position: (0.0, 0.0),
+            run_offset: 0.0,
+            horizontal_scaling: 1.0,
+            leading: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            run_width: 0.0,
+            metrics: None,
             text_buffer: String::new(),
+            render_mode: 0,
+            rise: 0.0,
+            run_style: RunStyle::default(),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TextElement {
     pub text: String,
     pub page_number: u32,
     pub font_size: f32,
+    /// The font's `BaseFont`, or its resource name when it has none.
     pub font_name: Option<String>,
     pub position: (f32, f32), // (x, y) coordinates
+    pub render_mode: u8,      // Tr operand; 3 is invisible
+    /// Fill colour as RGB, with gray and CMYK approximated. None when it isn't
+    /// known, e.g. for pattern fills.
+    #[serde(default)]
+    pub color: Option<(u8, u8, u8)>,
+    /// The text as decoded, kept when `TextNormalization` changed it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_text: Option<String>,
 }
 
 impl PartialEq for TextElement {
@@ -135,6 +310,11 @@ impl PartialEq for TextElement {
     }
 }
 
+/// TJ adjustment, in thousandths of an em, from which a gap reads as a space.
+/// A fifth of an em is narrower than the space of common fonts and wider than
+/// kerning.
+const WORD_GAP_ADJUSTMENT: f32 = 200.0;
+
 fn collect_text(
     text_buffer: &mut String,
     encoding: &Encoding,
@@ -150,8 +330,18 @@ fn collect_text(
             Object::Array(arr) => {
                 collect_text(text_buffer, encoding, arr, page_number)?;
             }
-            Object::Integer(i) => {
-                // Handle text positioning adjustments if necessary
+            Object::Integer(_) | Object::Real(_) => {
+                // Some generators never draw spaces and push words apart
+                // with TJ adjustments instead
+                let adjustment = operand.as_float().unwrap_or(0.0);
+                if -adjustment >= WORD_GAP_ADJUSTMENT
+                    && text_buffer
+                        .chars()
+                        .last()
+                        .is_some_and(|c| !c.is_whitespace())
+                {
+                    text_buffer.push(' ');
+                }
             }
             _ => {}
         }
@@ -159,19 +349,27 @@ fn collect_text(
     Ok(())
 }
 
-fn get_page_text_elements(
+/// The text and paths a page draws, with Form XObjects followed, in page space.
+#[derive(Default)]
+struct PageContent {
+    text_elements: Vec<TextElement>,
+    paths: Vec<PathElement>,
+}
+
+fn get_page_content(
     doc: &Document,
     page_number: u32,
     page_id: (u32, u16),
-) -> Result<Vec<TextElement>, LopdfError> {
-    let mut text_elements = Vec::new();
-    let mut text_state = TextState::default();
+    column_gap: f32,
+    font_cache: &FontCache,
+) -> Result<PageContent, LopdfError> {
+    let mut content = PageContent::default();
 
     let content_data = match doc.get_and_decode_page_content(page_id) {
         Ok(content) => content,
         Err(e) => {
             error!("Failed to decode content for page {}: {}", page_number, e);
-            panic!("Failed to decode content for page {}", e);
+            return Err(e);
         }
     };
 
@@ -184,19 +382,503 @@ fn get_page_text_elements(
         }
     };
 
-    let encodings: BTreeMap<Vec<u8>, Encoding> = fonts
+    let mut encodings = BTreeMap::new();
+    let mut metrics = BTreeMap::new();
+    let mut base_fonts = BTreeMap::new();
+    for (name, font) in fonts {
+        let encoding = font_encoding(doc, &name, font, page_number)?;
+        if let Some(font_metrics) = font_metrics(doc, font, &encoding, font_cache) {
+            metrics.insert(name.clone(), font_metrics);
+        }
+        if let Some(base_font) = base_font(font) {
+            base_fonts.insert(name.clone(), base_font);
+        }
+        encodings.insert(name, encoding);
+    }
+
+    let mut xobjects = BTreeMap::new();
+    let (resource_dict, resource_ids) = doc.get_page_resources(page_id)?;
+    if let Some(resources) = resource_dict {
+        collect_xobjects(doc, resources, &mut xobjects);
+    }
+    for resource_id in resource_ids {
+        if let Ok(resources) = doc.get_dictionary(resource_id) {
+            collect_xobjects(doc, resources, &mut xobjects);
+        }
+    }
+
+    let resources = ContentResources {
+        encodings,
+        xobjects,
+        metrics,
+        base_fonts,
+        column_gap,
+        font_cache,
+    };
+    collect_content(
+        doc,
+        &content_data.operations,
+        &resources,
+        page_number,
+        &IDENTITY_MATRIX,
+        0,
+        &mut content,
+    )?;
+
+    Ok(content)
+}
+
+/// Stands in for fonts whose encoding can't be decoded.
+static STANDARD_ENCODING_FONT: LazyLock<Dictionary> = LazyLock::new(|| {
+    dictionary! {
+        "Type" => "Font",
+        "Encoding" => "StandardEncoding",
+    }
+});
+
+/// The `BaseFont` name of `font`, e.g. "ABCDEF+Helvetica-Bold".
+fn base_font(font: &Dictionary) -> Option<String> {
+    match font.get(b"BaseFont") {
+        Ok(Object::Name(name)) => Some(String::from_utf8_lossy(name).into_owned()),
+        _ => None,
+    }
+}
+
+/// The encoding of `font`, falling back to StandardEncoding for encoding names
+/// lopdf can't decode, so an in-house font costs at worst its own text rather
+/// than the whole page.
+fn font_encoding<'a>(
+    doc: &'a Document,
+    name: &[u8],
+    font: &'a Dictionary,
+    page_number: u32,
+) -> LopdfResult<Encoding<'a>> {
+    let encoding = font.get_font_encoding(doc)?;
+    // Those encodings fail on every string, even an empty one
+    if encoding.bytes_to_string(&[]).is_ok() {
+        return Ok(encoding);
+    }
+    warn!(
+        "Unsupported encoding {:?} for font {} on page {}, using StandardEncoding",
+        encoding,
+        String::from_utf8_lossy(name),
+        page_number
+    );
+    STANDARD_ENCODING_FONT.get_font_encoding(doc)
+}
+
+/// Fonts and XObjects a content stream can refer to by name.
+struct ContentResources<'a> {
+    encodings: BTreeMap<Vec<u8>, Encoding<'a>>,
+    xobjects: BTreeMap<Vec<u8>, (u32, u16)>,
+    /// Registered metrics of the fonts that have them.
+    metrics: BTreeMap<Vec<u8>, Arc<FontMetrics>>,
+    /// `BaseFont` of the fonts that name one, reported as the elements' font name.
+    base_fonts: BTreeMap<Vec<u8>, String>,
+    /// `TextOptions::column_gap`, carried into nested forms.
+    column_gap: f32,
+    font_cache: &'a FontCache,
+}
+
+/// Embedded font programs decoded so far, by the id of their stream, so a font
+/// shared by every page of a document is only decompressed once.
+#[derive(Default)]
+struct FontCache {
+    #[cfg(feature = "font-parsing")]
+    programs: Mutex<HashMap<ObjectId, Option<Arc<Vec<u8>>>>>,
+}
+
+#[cfg(feature = "font-parsing")]
+impl FontCache {
+    fn program(&self, doc: &Document, id: ObjectId) -> Option<Arc<Vec<u8>>> {
+        let mut programs = self.programs.lock().unwrap_or_else(PoisonError::into_inner);
+        programs
+            .entry(id)
+            .or_insert_with(|| {
+                let stream = doc.get_object(id).and_then(Object::as_stream).ok()?;
+                stream.get_plain_content().ok().map(Arc::new)
+            })
+            .clone()
+    }
+}
+
+/// Metrics from the `/Widths` array of `font`, which are authoritative for it,
+/// then from its embedded program, or else those registered for its BaseFont.
+fn font_metrics(
+    doc: &Document,
+    font: &Dictionary,
+    encoding: &Encoding,
+    font_cache: &FontCache,
+) -> Option<Arc<FontMetrics>> {
+    width_array_metrics(doc, font, encoding)
+        .or_else(|| embedded_metrics(doc, font, encoding, font_cache))
+        .map(Arc::new)
+        .or_else(|| {
+            font.get(b"BaseFont")
+                .and_then(Object::as_name_str)
+                .ok()
+                .and_then(registered_metrics)
+        })
+}
+
+/// Metrics from the `/FirstChar`, `/Widths` and `/FontDescriptor` entries of a
+/// simple font. Composite fonts keep their widths in `/W` of a descendant font
+/// and get None, as do fonts whose codes don't map to a single character.
+fn width_array_metrics(
+    doc: &Document,
+    font: &Dictionary,
+    encoding: &Encoding,
+) -> Option<FontMetrics> {
+    if matches!(encoding, Encoding::UnicodeMapEncoding(_)) {
+        return None;
+    }
+    let number = |obj: &Object| doc.dereference(obj).ok()?.1.as_float().ok();
+    let first_char = font.get(b"FirstChar").ok().and_then(number)? as i64;
+    let (_, widths) = doc.dereference(font.get(b"Widths").ok()?).ok()?;
+
+    let mut metrics = FontMetrics::default();
+    apply_descriptor(doc, font, &mut metrics);
+    for (code, width) in (first_char..).zip(widths.as_array().ok()?) {
+        let (Ok(code), Some(width)) = (u8::try_from(code), number(width)) else {
+            continue;
+        };
+        if let Some(c) = single_char(encoding, &[code]) {
+            metrics.widths.insert(c, width);
+        }
+    }
+    Some(metrics)
+}
+
+/// Override `metrics` with the Ascent, Descent and MissingWidth of the
+/// `/FontDescriptor` of `font`, where it gives them.
+fn apply_descriptor(doc: &Document, font: &Dictionary, metrics: &mut FontMetrics) {
+    let Ok(descriptor) = font
+        .get_deref(b"FontDescriptor", doc)
+        .and_then(Object::as_dict)
+    else {
+        return;
+    };
+    let entry = |key: &[u8]| {
+        let (_, value) = doc.dereference(descriptor.get(key).ok()?).ok()?;
+        value.as_float().ok()
+    };
+    metrics.ascent = entry(b"Ascent").unwrap_or(metrics.ascent);
+    metrics.descent = entry(b"Descent").unwrap_or(metrics.descent);
+    metrics.missing_width = entry(b"MissingWidth").unwrap_or(metrics.missing_width);
+}
+
+/// The character `code` decodes to, when it is exactly one.
+fn single_char(encoding: &Encoding, code: &[u8]) -> Option<char> {
+    let decoded = encoding.bytes_to_string(code).ok()?;
+    let mut chars = decoded.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+/// Metrics read from the TrueType or OpenType program embedded in `font` as
+/// `/FontFile2` or `/FontFile3`. Composite fonts are read through their
+/// descendant CIDFont when their codes are CIDs (Identity-H or -V).
+#[cfg(feature = "font-parsing")]
+fn embedded_metrics(
+    doc: &Document,
+    font: &Dictionary,
+    encoding: &Encoding,
+    font_cache: &FontCache,
+) -> Option<FontMetrics> {
+    let composite = font.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Type0");
+    let glyph_font = if composite {
+        if !matches!(
+            font.get(b"Encoding").and_then(Object::as_name),
+            Ok(b"Identity-H" | b"Identity-V")
+        ) {
+            return None;
+        }
+        let descendants = font
+            .get_deref(b"DescendantFonts", doc)
+            .and_then(Object::as_array)
+            .ok()?;
+        doc.dereference(descendants.first()?)
+            .and_then(|(_, descendant)| descendant.as_dict())
+            .ok()?
+    } else {
+        font
+    };
+    let descriptor = glyph_font
+        .get_deref(b"FontDescriptor", doc)
+        .and_then(Object::as_dict)
+        .ok()?;
+    let program_id = [b"FontFile2".as_slice(), b"FontFile3"]
         .into_iter()
-        .map(|(name, font)| font.get_font_encoding(doc).map(|it| (name, it)))
-        .collect::<LopdfResult<BTreeMap<Vec<u8>, Encoding>>>()?;
+        .find_map(|key| descriptor.get(key).and_then(Object::as_reference).ok())?;
+    let program = font_cache.program(doc, program_id)?;
+
+    let cid_to_gid = match glyph_font.get_deref(b"CIDToGIDMap", doc) {
+        Ok(Object::Stream(stream)) => Some(stream.get_plain_content().ok()?),
+        _ => None,
+    };
+    let mapping = match (&cid_to_gid, composite) {
+        (_, false) => GlyphMapping::Cmap,
+        (map, true) => GlyphMapping::CidToGid(map.as_deref()),
+    };
+    let mut metrics = program_metrics(&program, mapping, |code| {
+        if composite {
+            single_char(encoding, &code.to_be_bytes())
+        } else {
+            single_char(encoding, &[u8::try_from(code).ok()?])
+        }
+    })?;
+    apply_descriptor(doc, glyph_font, &mut metrics);
+    Some(metrics)
+}
+
+#[cfg(not(feature = "font-parsing"))]
+fn embedded_metrics(
+    _doc: &Document,
+    _font: &Dictionary,
+    _encoding: &Encoding,
+    _font_cache: &FontCache,
+) -> Option<FontMetrics> {
+    None
+}
+
+fn collect_xobjects(
+    doc: &Document,
+    resources: &Dictionary,
+    xobjects: &mut BTreeMap<Vec<u8>, (u32, u16)>,
+) {
+    let Ok((_, Object::Dictionary(dict))) = resources
+        .get(b"XObject")
+        .and_then(|obj| doc.dereference(obj))
+    else {
+        return;
+    };
+    for (name, value) in dict.iter() {
+        if let Ok(id) = value.as_reference() {
+            xobjects.entry(name.clone()).or_insert(id);
+        }
+    }
+}
+
+/// Resources declared by a Form XObject, or `None` when it inherits those of its parent.
+fn form_resources<'a>(
+    doc: &'a Document,
+    form: &'a Stream,
+    page_number: u32,
+    column_gap: f32,
+    font_cache: &'a FontCache,
+) -> LopdfResult<Option<ContentResources<'a>>> {
+    let Ok((_, Object::Dictionary(resources))) = form
+        .dict
+        .get(b"Resources")
+        .and_then(|obj| doc.dereference(obj))
+    else {
+        return Ok(None);
+    };
+
+    let mut encodings = BTreeMap::new();
+    let mut metrics = BTreeMap::new();
+    let mut base_fonts = BTreeMap::new();
+    if let Ok((_, Object::Dictionary(fonts))) =
+        resources.get(b"Font").and_then(|obj| doc.dereference(obj))
+    {
+        for (name, value) in fonts.iter() {
+            if let Ok((_, Object::Dictionary(font))) = doc.dereference(value) {
+                let encoding = font_encoding(doc, name, font, page_number)?;
+                if let Some(font_metrics) = font_metrics(doc, font, &encoding, font_cache) {
+                    metrics.insert(name.clone(), font_metrics);
+                }
+                if let Some(base_font) = base_font(font) {
+                    base_fonts.insert(name.clone(), base_font);
+                }
+                encodings.insert(name.clone(), encoding);
+            }
+        }
+    }
+
+    let mut xobjects = BTreeMap::new();
+    collect_xobjects(doc, resources, &mut xobjects);
+
+    Ok(Some(ContentResources {
+        encodings,
+        xobjects,
+        metrics,
+        base_fonts,
+        column_gap,
+        font_cache,
+    }))
+}
+
+/// Run the operators of a Form XObject invoked with `Do`, placing its text and
+/// paths in page space through the form's `/Matrix` and the invoking transform.
+fn collect_form_content(
+    doc: &Document,
+    name: &[u8],
+    resources: &ContentResources,
+    page_number: u32,
+    ctm: &[f32; 6],
+    depth: usize,
+    page_content: &mut PageContent,
+) -> Result<(), LopdfError> {
+    let Some(form) = resources
+        .xobjects
+        .get(name)
+        .and_then(|id| doc.get_object(*id).ok())
+        .and_then(|obj| obj.as_stream().ok())
+    else {
+        return Ok(());
+    };
+    // Only forms carry content streams; images are skipped here.
+    if form.dict.get(b"Subtype").and_then(Object::as_name_str).ok() != Some("Form") {
+        return Ok(());
+    }
+    if depth >= MAX_FORM_DEPTH {
+        warn!(
+            "Form XObject nesting deeper than {} on page {}, skipping",
+            MAX_FORM_DEPTH, page_number
+        );
+        return Ok(());
+    }
+
+    let matrix = form
+        .dict
+        .get(b"Matrix")
+        .and_then(Object::as_array)
+        .ok()
+        .and_then(|values| matrix_operands(values))
+        .unwrap_or(IDENTITY_MATRIX);
+    let form_ctm = multiply_matrices(&matrix, ctm);
+
+    let content = Content::decode(&form.get_plain_content()?)?;
+    let form_resources = form_resources(
+        doc,
+        form,
+        page_number,
+        resources.column_gap,
+        resources.font_cache,
+    )?;
+    collect_content(
+        doc,
+        &content.operations,
+        form_resources.as_ref().unwrap_or(resources),
+        page_number,
+        &form_ctm,
+        depth + 1,
+        page_content,
+    )
+}
+
+/// The six numbers of a `cm` or `Tm` operator or a `/Matrix` array.
+fn matrix_operands(operands: &[Object]) -> Option<[f32; 6]> {
+    let numbers: Vec<f32> = operands
+        .iter()
+        .map(|operand| operand.as_float().ok())
+        .collect::<Option<_>>()?;
+    numbers.try_into().ok()
+}
+
+/// Concatenate two PDF matrices: the result applies `first`, then `second`.
+fn multiply_matrices(first: &[f32; 6], second: &[f32; 6]) -> [f32; 6] {
+    let [a0, a1, a2, a3, a4, a5] = *first;
+    let [b0, b1, b2, b3, b4, b5] = *second;
+    [
+        a0 * b0 + a1 * b2,
+        a0 * b1 + a1 * b3,
+        a2 * b0 + a3 * b2,
+        a2 * b1 + a3 * b3,
+        a4 * b0 + a5 * b2 + b4,
+        a4 * b1 + a5 * b3 + b5,
+    ]
+}
+
+fn transform_point(matrix: &[f32; 6], (x, y): (f32, f32)) -> (f32, f32) {
+    (
+        matrix[0] * x + matrix[2] * y + matrix[4],
+        matrix[1] * x + matrix[3] * y + matrix[5],
+    )
+}
+
+/// The fill colour set by a `g`, `rg`, `k`, `sc` or `scn` operator, as RGB. The
+/// component count picks the colour space; anything else (patterns, separations)
+/// gives None.
+fn fill_color(operands: &[Object]) -> Option<(u8, u8, u8)> {
+    let components: Vec<f32> = operands
+        .iter()
+        .map(|operand| operand.as_float().ok())
+        .collect::<Option<_>>()?;
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    match components[..] {
+        [gray] => Some((channel(gray), channel(gray), channel(gray))),
+        [r, g, b] => Some((channel(r), channel(g), channel(b))),
+        [c, m, y, k] => Some((
+            channel((1.0 - c) * (1.0 - k)),
+            channel((1.0 - m) * (1.0 - k)),
+            channel((1.0 - y) * (1.0 - k)),
+        )),
+        _ => None,
+    }
+}
+
+const BLACK: Option<(u8, u8, u8)> = Some((0, 0, 0));
+
+fn collect_content(
+    doc: &Document,
+    operations: &[Operation],
+    resources: &ContentResources,
+    page_number: u32,
+    ctm: &[f32; 6],
+    depth: usize,
+    content: &mut PageContent,
+) -> Result<(), LopdfError> {
+    let encodings = &resources.encodings;
+    let mut text_state = TextState::default();
 
     let mut current_encoding: Option<&Encoding> = None;
+    // The CTM, fill colour, line width and text state are graphics state,
+    // saved and restored by q and Q
+    let base_ctm = *ctm;
+    let mut ctm = base_ctm;
+    let mut color = BLACK;
+    let mut line_width = 1.0;
+    let mut saved_states = Vec::new();
+    let mut path = PathBuilder::default();
 
-    for (i, op) in content_data.operations.iter().enumerate() {
+    for (i, op) in operations.iter().enumerate() {
         match op.operator.as_ref() {
+            "q" => {
+                saved_states.push((ctm, color, line_width, text_state.clone(), current_encoding))
+            }
+            "Q" => match saved_states.pop() {
+                Some((saved_ctm, saved_color, saved_width, saved_text, saved_encoding)) => {
+                    (ctm, color, line_width) = (saved_ctm, saved_color, saved_width);
+                    text_state.set_parameters(&saved_text);
+                    current_encoding = saved_encoding;
+                }
+                None => (ctm, color, line_width) = (base_ctm, BLACK, 1.0),
+            },
+            "cm" => {
+                if let Some(matrix) = matrix_operands(&op.operands) {
+                    ctm = multiply_matrices(&matrix, &ctm);
+                }
+            }
+            "w" => {
+                if let Some(Ok(width)) = op.operands.first().map(Object::as_float) {
+                    line_width = width;
+                }
+            }
+            "m" | "l" | "c" | "v" | "y" | "re" | "h" | "n" | "S" | "s" | "f" | "F" | "f*" | "B"
+            | "B*" | "b" | "b*" => {
+                path.apply(op, &ctm, line_width, page_number, &mut content.paths);
+            }
+            "g" | "rg" | "k" | "sc" | "scn" => color = fill_color(&op.operands),
+            // Selecting a colour space resets the colour to its initial value
+            "cs" => color = BLACK,
             "BT" => {
-                text_state = TextState::default();
-                text_state.text_buffer = String::new();
-                text_state.position = (0.0, 0.0);
+                // Only the matrices and the text shown start afresh
+                let mut text_object = TextState::default();
+                text_object.set_parameters(&text_state);
+                text_state = text_object;
             }
             "Tf" => {
                 if let (Some(Object::Name(font_name)), Some(font_size_obj)) =
@@ -210,37 +892,134 @@ fn get_page_text_elements(
                             0.0
                         }
                     };
-                    text_state.font_name = Some(String::from_utf8_lossy(font_name).into_owned());
+                    text_state.font_name = Some(
+                        resources
+                            .base_fonts
+                            .get(font_name)
+                            .cloned()
+                            .unwrap_or_else(|| String::from_utf8_lossy(font_name).into_owned()),
+                    );
                     text_state.font_size = font_size;
                     current_encoding = encodings.get(font_name);
+                    text_state.metrics = resources.metrics.get(font_name).cloned();
+                }
+            }
+            "Tc" | "Tw" => {
+                if let Some(Ok(spacing)) = op.operands.first().map(Object::as_float) {
+                    if op.operator == "Tc" {
+                        text_state.char_spacing = spacing;
+                    } else {
+                        text_state.word_spacing = spacing;
+                    }
                 }
             }
-            "Tj" | "TJ" | "'" | "\"" => {
-                if let Some(encoding) = current_encoding {
-                    collect_text(
-                        &mut text_state.text_buffer,
-                        encoding,
-                        &op.operands,
+            "Ts" => {
+                if let Some(Ok(rise)) = op.operands.first().map(Object::as_float) {
+                    text_state.rise = rise;
+                }
+            }
+            "Tz" => {
+                if let Some(Ok(scale)) = op.operands.first().map(Object::as_float) {
+                    text_state.horizontal_scaling = scale / 100.0;
+                }
+            }
+            "Tr" => {
+                if let Some(Object::Integer(mode)) = op.operands.first() {
+                    text_state.render_mode = (*mode).clamp(0, 7) as u8;
+                }
+            }
+            "Tj" | "'" | "\"" => {
+                // ' and " move to the next line first, and " sets the word and
+                // character spacing from its first two operands
+                if op.operator == "\"" {
+                    if let [word_spacing, char_spacing, _] = op.operands.as_slice() {
+                        text_state.word_spacing = word_spacing.as_float().unwrap_or(0.0);
+                        text_state.char_spacing = char_spacing.as_float().unwrap_or(0.0);
+                    }
+                }
+                if op.operator != "Tj" {
+                    let next_line = (0.0, -text_state.leading);
+                    move_text(
+                        &mut text_state,
+                        next_line,
+                        resources.column_gap,
                         page_number,
-                    )?;
-                } else {
-                    warn!("No current encoding for text extraction at operation {}", i);
+                        &ctm,
+                        &mut content.text_elements,
+                    );
                 }
+                let style = text_state.style(color, &ctm);
+                restyle(
+                    &mut text_state,
+                    &style,
+                    page_number,
+                    &ctm,
+                    &mut content.text_elements,
+                );
+                show_text(
+                    &mut text_state,
+                    &style,
+                    current_encoding,
+                    op.operands
+                        .last()
+                        .map(std::slice::from_ref)
+                        .unwrap_or_default(),
+                    page_number,
+                    i,
+                )?;
             }
-            "ET" => {
-                if !text_state.text_buffer.is_empty() {
-                    let text_element = TextElement {
-                        text: text_state.text_buffer.clone(),
+            "TJ" => {
+                let parts = match op.operands.first() {
+                    Some(Object::Array(parts)) => parts.as_slice(),
+                    _ => op.operands.as_slice(),
+                };
+                let style = text_state.style(color, &ctm);
+                restyle(
+                    &mut text_state,
+                    &style,
+                    page_number,
+                    &ctm,
+                    &mut content.text_elements,
+                );
+                for part in parts {
+                    // Table rows are often one text object that jumps from
+                    // cell to cell with large adjustments
+                    let jump = -part.as_float().unwrap_or(0.0) / 1000.0;
+                    if jump >= resources.column_gap {
+                        let width = estimated_width(&text_state);
+                        finish_run(
+                            &mut text_state,
+                            page_number,
+                            &ctm,
+                            &mut content.text_elements,
+                        );
+                        text_state.run_offset +=
+                            width + jump * text_state.font_size * text_state.horizontal_scaling;
+                        continue;
+                    }
+                    // Smaller adjustments kern or space the text within the run
+                    if text_state.text_buffer.is_empty() {
+                        text_state.run_offset +=
+                            jump * text_state.font_size * text_state.horizontal_scaling;
+                    } else {
+                        text_state.run_width += jump * text_state.font_size;
+                    }
+                    show_text(
+                        &mut text_state,
+                        &style,
+                        current_encoding,
+                        std::slice::from_ref(part),
                         page_number,
-                        font_size: text_state.font_size,
-                        font_name: text_state.font_name.clone(),
-                        position: text_state.position,
-                    };
-                    text_elements.push(text_element);
+                        i,
+                    )?;
                 }
-                // Reset text buffer
-                text_state.text_buffer.clear();
             }
+            "ET" => finish_run(
+                &mut text_state,
+                page_number,
+                &ctm,
+                &mut content.text_elements,
+            ),
             "Td" | "TD" => {
                 let args = &op.operands;
                 if args.len() == 2 {
@@ -255,29 +1034,46 @@ fn get_page_text_elements(
                         Object::Real(f) => *f,
                         _ => 0.0,
                     };
-
-                    text_state.position.0 += tx;
-                    text_state.position.1 += ty;
+                    if op.operator == "TD" {
+                        text_state.leading = -ty;
+                    }
+                    move_text(
+                        &mut text_state,
+                        (tx, ty),
+                        resources.column_gap,
+                        page_number,
+                        &ctm,
+                        &mut content.text_elements,
+                    );
                 }
             }
+            "TL" => {
+                if let Some(Ok(leading)) = op.operands.first().map(Object::as_float) {
+                    text_state.leading = leading;
+                }
+            }
+            "T*" => {
+                let next_line = (0.0, -text_state.leading);
+                move_text(
+                    &mut text_state,
+                    next_line,
+                    resources.column_gap,
+                    page_number,
+                    &ctm,
+                    &mut content.text_elements,
+                );
+            }
             "Tm" => {
-                let args = &op.operands;
-                if args.len() == 6 {
-                    // Convert all matrix values handling both Integer and Real
-                    let matrix: Vec<f32> = args
-                        .iter()
-                        .map(|arg| match arg {
-                            Object::Integer(i) => *i as f32,
-                            Object::Real(f) => *f,
-                            _ => 0.0,
-                        })
-                        .collect();
-
-                    text_state.text_matrix = [
-                        matrix[0], matrix[1], matrix[2], matrix[3], matrix[4], matrix[5],
-                    ];
+                if let Some(matrix) = matrix_operands(&op.operands) {
+                    text_state.text_matrix = matrix;
                     // The last two elements of the matrix are the translation
                     text_state.position = (matrix[4], matrix[5]);
+                    text_state.run_offset = 0.0;
+                }
+            }
+            "Do" => {
+                if let Some(Object::Name(name)) = op.operands.first() {
+                    collect_form_content(doc, name, resources, page_number, &ctm, depth, content)?;
                 }
             }
             _ => {
@@ -286,50 +1082,374 @@ fn get_page_text_elements(
         }
     }
 
-    if !text_state.text_buffer.is_empty() {
-        let text_element = TextElement {
-            text: text_state.text_buffer.clone(),
-            page_number,
-            font_size: text_state.font_size,
-            font_name: text_state.font_name.clone(),
-            position: text_state.position,
+    finish_run(
+        &mut text_state,
+        page_number,
+        &ctm,
+        &mut content.text_elements,
+    );
+
+    Ok(())
+}
+
+/// Move to the start of a line offset by `(tx, ty)` from the current one, as
+/// `Td` does. The offset is in text space, so it is scaled and rotated by the
+/// text matrix: `[1 0 0 1 tx ty] × Tlm`. Text shown so far ends there when the
+/// move is to another line or jumps well past it along this one.
+fn move_text(
+    text_state: &mut TextState,
+    (tx, ty): (f32, f32),
+    column_gap: f32,
+    page_number: u32,
+    ctm: &[f32; 6],
+    text_elements: &mut Vec<TextElement>,
+) {
+    let [a, b, c, d, ..] = text_state.text_matrix;
+    // Measured along the baseline as the move is, so a scaled matrix scales both
+    let scale = (a * a + b * b).sqrt();
+    let jump = (tx - text_state.run_offset - estimated_width(text_state)) * scale;
+    if ty != 0.0 || jump >= column_gap * text_state.font_size * scale {
+        finish_run(text_state, page_number, ctm, text_elements);
+    }
+
+    text_state.position.0 += tx * a + ty * c;
+    text_state.position.1 += tx * b + ty * d;
+    text_state.run_offset = 0.0;
+}
+
+/// Width of a character in ems, for guessing how far shown text reaches
+/// without the font's widths.
+const AVERAGE_CHAR_WIDTH: f32 = 0.5;
+
+/// Roughly how far along the line the text in `text_buffer` reaches. Tracking
+/// can pull glyphs closer, but the text never reaches backwards.
+fn estimated_width(text_state: &TextState) -> f32 {
+    text_state.run_width.max(0.0) * text_state.horizontal_scaling
+}
+
+/// Width of `c` in ems, from the registered metrics when there are any.
+fn glyph_width(text_state: &TextState, c: char) -> f32 {
+    text_state
+        .metrics
+        .as_ref()
+        .map_or(AVERAGE_CHAR_WIDTH, |metrics| metrics.width(c) / 1000.0)
+}
+
+fn show_text(
+    text_state: &mut TextState,
+    style: &RunStyle,
+    encoding: Option<&Encoding>,
+    operands: &[Object],
+    page_number: u32,
+    operation_index: usize,
+) -> LopdfResult<()> {
+    if text_state.text_buffer.is_empty() {
+        text_state.run_style = style.clone();
+    }
+    let Some(encoding) = encoding else {
+        warn!(
+            "No current encoding for text extraction at operation {}",
+            operation_index
+        );
+        return Ok(());
+    };
+    let shown_from = text_state.text_buffer.len();
+    collect_text(&mut text_state.text_buffer, encoding, operands, page_number)?;
+
+    let strings: Vec<&[u8]> = operands.iter().filter_map(|o| o.as_str().ok()).collect();
+    if !strings.is_empty() {
+        let shown = &text_state.text_buffer[shown_from..];
+        let glyphs = shown.chars().count();
+        let advance: f32 = shown.chars().map(|c| glyph_width(text_state, c)).sum();
+        // Word spacing applies to the single byte 32, not to a multi-byte
+        // code that happens to decode to a space
+        let word_spaces = match encoding {
+            Encoding::UnicodeMapEncoding(_) => 0,
+            _ => strings
+                .iter()
+                .map(|bytes| bytes.iter().filter(|&&b| b == b' ').count())
+                .sum(),
         };
-        text_elements.push(text_element);
+        text_state.run_width += advance * text_state.font_size
+            + glyphs as f32 * text_state.char_spacing
+            + word_spaces as f32 * text_state.word_spacing;
+    }
+    Ok(())
+}
+
+/// End the current run if it was drawn in a different style than `style`; the
+/// next run carries on from where it was estimated to stop.
+fn restyle(
+    text_state: &mut TextState,
+    style: &RunStyle,
+    page_number: u32,
+    ctm: &[f32; 6],
+    text_elements: &mut Vec<TextElement>,
+) {
+    if text_state.text_buffer.is_empty() || text_state.run_style == *style {
+        return;
     }
+    let width = estimated_width(text_state);
+    finish_run(text_state, page_number, ctm, text_elements);
+    text_state.run_offset += width;
+}
 
+/// Emit the text shown since the last run ended as one element.
+fn finish_run(
+    text_state: &mut TextState,
+    page_number: u32,
+    ctm: &[f32; 6],
+    text_elements: &mut Vec<TextElement>,
+) {
+    if text_state.text_buffer.is_empty() {
+        return;
+    }
+    // The offset runs along the baseline, which the text matrix may rotate or scale
+    let (x, y) = text_state.position;
+    let [a, b, ..] = text_state.text_matrix;
+    let offset = text_state.run_offset;
+    text_state.run_width = 0.0;
+    text_elements.push(TextElement {
+        text: std::mem::take(&mut text_state.text_buffer),
+        page_number,
+        font_size: text_state.run_style.font_size,
+        font_name: text_state.run_style.font_name.clone(),
+        position: transform_point(ctm, (x + a * offset, y + b * offset)),
+        render_mode: text_state.render_mode,
+        color: text_state.run_style.color,
+        raw_text: None,
+    });
+}
+
+/// A page skipped by `get_pdf_text_report` and the reason it couldn't be read.
+#[derive(Debug, Clone, Serialize)]
+pub struct PageWarning {
+    pub page: u32,
+    pub message: String,
+}
+
+/// Text elements from every readable page, plus a warning for each page that wasn't.
+#[derive(Debug, Default)]
+pub struct ParseReport {
+    pub text_elements: Vec<TextElement>,
+    pub warnings: Vec<PageWarning>,
+    /// Time spent extracting each page, in page order.
+    pub page_durations: Vec<Duration>,
+}
+
+/// Pages to extract, as inclusive 1-based ranges, parsed from e.g. "1-80,120-130".
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageSelection {
+    ranges: Vec<(u32, u32)>,
+}
+
+impl PageSelection {
+    pub fn contains(&self, page: u32) -> bool {
+        self.ranges
+            .iter()
+            .any(|&(start, end)| start <= page && page <= end)
+    }
+
+    /// The last selected page.
+    pub fn last(&self) -> u32 {
+        self.ranges.iter().map(|&(_, end)| end).max().unwrap_or(0)
+    }
+}
+
+impl std::str::FromStr for PageSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid page selection `{s}`, expected e.g. 1-80,120-130");
+        let page = |p: &str| match p.trim().parse::<u32>() {
+            Ok(page) if page >= 1 => Ok(page),
+            _ => Err(invalid()),
+        };
+        let ranges = s
+            .split(',')
+            .map(|range| {
+                let (start, end) = match range.split_once('-') {
+                    Some((start, end)) => (page(start)?, page(end)?),
+                    None => (page(range)?, page(range)?),
+                };
+                if start > end {
+                    return Err(invalid());
+                }
+                Ok((start, end))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(PageSelection { ranges })
+    }
+}
+
+/// Options controlling which text is extracted from a loaded document.
+#[derive(Debug, Clone)]
+pub struct TextOptions {
+    /// Only decode these pages; the rest are skipped entirely.
+    pub pages: Option<PageSelection>,
+    pub normalization: TextNormalization,
+    /// Horizontal jump, in ems, at which a `TJ` adjustment or `Td` move ends
+    /// one text element and starts the next, keeping table cells apart.
+    pub column_gap: f32,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        TextOptions {
+            pages: None,
+            normalization: TextNormalization::default(),
+            column_gap: 1.5,
+        }
+    }
+}
+
+/// Character clean-up applied to decoded text, so "ﬁnancial" reads and matches
+/// as "financial".
+#[derive(Debug, Clone)]
+pub struct TextNormalization {
+    /// Expand ligatures such as "ﬁ" and "ﬄ" into their letters.
+    pub ligatures: bool,
+    /// Replace curly quotes and en and em dashes with their ASCII forms.
+    pub punctuation: bool,
+    /// Remove zero-width spaces and joiners and byte order marks.
+    pub zero_width: bool,
+}
+
+impl Default for TextNormalization {
+    fn default() -> Self {
+        TextNormalization {
+            ligatures: true,
+            punctuation: false,
+            zero_width: true,
+        }
+    }
+}
+
+impl TextNormalization {
+    pub fn normalize(&self, text: &str) -> String {
+        let mut normalized = String::with_capacity(text.len());
+        for c in text.chars() {
+            let replacement = match c {
+                '\u{FB00}' if self.ligatures => "ff",
+                '\u{FB01}' if self.ligatures => "fi",
+                '\u{FB02}' if self.ligatures => "fl",
+                '\u{FB03}' if self.ligatures => "ffi",
+                '\u{FB04}' if self.ligatures => "ffl",
+                '\u{FB05}' | '\u{FB06}' if self.ligatures => "st",
+                '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{2032}' if self.punctuation => "'",
+                '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{2033}' if self.punctuation => "\"",
+                '\u{2013}' | '\u{2014}' | '\u{2212}' if self.punctuation => "-",
+                '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}'
+                    if self.zero_width =>
+                {
+                    ""
+                }
+                _ => {
+                    normalized.push(c);
+                    continue;
+                }
+            };
+            normalized.push_str(replacement);
+        }
+        normalized
+    }
+}
+
+/// A page's text elements with `options.normalization` applied.
+fn normalized_page_text(
+    doc: &Document,
+    page_number: u32,
+    page_id: ObjectId,
+    options: &TextOptions,
+    font_cache: &FontCache,
+) -> Result<Vec<TextElement>, LopdfError> {
+    let mut text_elements =
+        get_page_content(doc, page_number, page_id, options.column_gap, font_cache)?.text_elements;
+    for mi in &mut text_elements {
+        let normalized = options.normalization.normalize(&mi.text);
+        if normalized != mi.text {
+            mi.raw_text = Some(std::mem::replace(&mut mi.text, normalized));
+        }
+    }
     Ok(text_elements)
 }
 
-pub fn get_pdf_text(doc: &Document) -> Result<Vec<TextElement>, LopdfError> {
-    let mut all_text_elements = Vec::new();
+/// The pages `options` selects, as (page number, page id).
+fn selected_pages(doc: &Document, options: &TextOptions) -> Vec<(u32, ObjectId)> {
+    doc.get_pages()
+        .into_iter()
+        .filter(|(page_num, _)| {
+            options
+                .pages
+                .as_ref()
+                .is_none_or(|pages| pages.contains(*page_num))
+        })
+        .collect()
+}
 
-    let page_matches: Vec<Result<(u32, Vec<TextElement>), Error>> = doc
-        .get_pages()
-        .into_par_iter()
-        .map(
-            |(page_num, page_id): (u32, (u32, u16))| -> Result<(u32, Vec<TextElement>), Error> {
-                let text_elements =
-                    get_page_text_elements(doc, page_num, page_id).map_err(|e| {
-                        Error::new(
-                            ErrorKind::Other,
-                            format!(
-                                "Failed to extract text from page {page_num} id={page_id:?}: {e:?}"
-                            ),
-                        )
-                    })?;
-                Ok((page_num, text_elements))
-            },
-        )
-        .collect();
+/// Like `get_pdf_text`, but a page that fails to extract is skipped and reported
+/// instead of failing the whole document.
+pub fn get_pdf_text_report(doc: &Document) -> ParseReport {
+    get_pdf_text_report_with_options(doc, &TextOptions::default())
+}
 
-    // for (page_number, page_id) in pages.into_par_iter() {
-    //     let page_number = page_number; // Ensure the page number is the actual number
+pub fn get_pdf_text_report_with_options(doc: &Document, options: &TextOptions) -> ParseReport {
+    let font_cache = FontCache::default();
+    let mut pages: Vec<(u32, Result<Vec<TextElement>, LopdfError>, Duration)> =
+        selected_pages(doc, options)
+            .into_par_iter()
+            .map(|(page_num, page_id)| {
+                let start = Instant::now();
+                let result = normalized_page_text(doc, page_num, page_id, options, &font_cache);
+                (page_num, result, start.elapsed())
+            })
+            .collect();
+    pages.sort_by_key(|(page_num, _, _)| *page_num);
 
-    //     // Parse the content stream
-    //     let text_elements = get_page_text_elements(doc, page_number, page_id)?;
+    let mut report = ParseReport::default();
+    for (page, result, duration) in pages {
+        report.page_durations.push(duration);
+        match result {
+            Ok(text_elements) => report.text_elements.extend(text_elements),
+            Err(e) => {
+                warn!("Skipping page {}: {}", page, e);
+                report.warnings.push(PageWarning {
+                    page,
+                    message: e.to_string(),
+                });
+            }
+        }
+    }
+    report
+}
 
-    //     all_text_elements.extend(text_elements);
-    // }
+/// Extract text elements from every page, failing on the first page that can't be read.
+pub fn get_pdf_text(doc: &Document) -> Result<Vec<TextElement>, DelverError> {
+    get_pdf_text_with_options(doc, &TextOptions::default())
+}
+
+pub fn get_pdf_text_with_options(
+    doc: &Document,
+    options: &TextOptions,
+) -> Result<Vec<TextElement>, DelverError> {
+    let mut all_text_elements = Vec::new();
+    let font_cache = FontCache::default();
+
+    let page_matches: Vec<Result<(u32, Vec<TextElement>), DelverError>> = selected_pages(
+        doc, options,
+    )
+    .into_par_iter()
+    .map(
+        |(page_num, page_id): (u32, (u32, u16))| -> Result<(u32, Vec<TextElement>), DelverError> {
+            let text_elements = normalized_page_text(doc, page_num, page_id, options, &font_cache)
+                .map_err(|source| DelverError::PageExtraction {
+                    page: page_num,
+                    source,
+                })?;
+            Ok((page_num, text_elements))
+        },
+    )
+    .collect();
 
     for page_match in page_matches {
         all_text_elements.extend(page_match?.1);
@@ -338,12 +1458,564 @@ pub fn get_pdf_text(doc: &Document) -> Result<Vec<TextElement>, LopdfError> {
     Ok(all_text_elements)
 }
 
-pub fn pdf2toc<P: AsRef<Path> + Debug>(path: P, output: P, pretty: bool) -> Result<(), Error> {
+/// The visible area of a page (its CropBox, or MediaBox without one): the
+/// lower-left corner and size in points, before `rotation` is applied.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PageGeometry {
+    pub left: f32,
+    pub bottom: f32,
+    pub width: f32,
+    pub height: f32,
+    /// Clockwise degrees the page is turned for display: 0, 90, 180 or 270.
+    pub rotation: i64,
+}
+
+/// US Letter, assumed for pages without a usable MediaBox.
+pub const DEFAULT_PAGE_GEOMETRY: PageGeometry = PageGeometry {
+    left: 0.0,
+    bottom: 0.0,
+    width: 612.0,
+    height: 792.0,
+    rotation: 0,
+};
+
+/// Limit on the page tree levels climbed looking for an inherited attribute.
+const MAX_INHERIT_DEPTH: usize = 32;
+
+/// The geometry of every page. Boxes and rotation are inherited from the page
+/// tree when the page doesn't set its own.
+pub fn get_page_geometry(doc: &Document) -> BTreeMap<u32, PageGeometry> {
+    doc.get_pages()
+        .into_iter()
+        .map(|(page_num, page_id)| {
+            let page_box = |key: &[u8]| {
+                inherited_attribute(doc, page_id, key).and_then(|rect| rectangle(doc, rect))
+            };
+            let rotation = inherited_attribute(doc, page_id, b"Rotate")
+                .and_then(|rotate| rotate.as_i64().ok())
+                .unwrap_or(0);
+            let geometry = PageGeometry {
+                // Only quarter turns are allowed
+                rotation: (rotation / 90 * 90).rem_euclid(360),
+                ..page_box(b"CropBox")
+                    .or_else(|| page_box(b"MediaBox"))
+                    .unwrap_or(DEFAULT_PAGE_GEOMETRY)
+            };
+            (page_num, geometry)
+        })
+        .collect()
+}
+
+fn inherited_attribute<'a>(doc: &'a Document, page_id: ObjectId, key: &[u8]) -> Option<&'a Object> {
+    let mut node = doc.get_dictionary(page_id).ok()?;
+    for _ in 0..MAX_INHERIT_DEPTH {
+        if let Ok(value) = node.get_deref(key, doc) {
+            return Some(value);
+        }
+        node = node
+            .get_deref(b"Parent", doc)
+            .and_then(Object::as_dict)
+            .ok()?;
+    }
+    None
+}
+
+/// A `[x0 y0 x1 y1]` rectangle with its corners in either order.
+fn rectangle(doc: &Document, object: &Object) -> Option<PageGeometry> {
+    let corners: Vec<f32> = object
+        .as_array()
+        .ok()?
+        .iter()
+        .map(|value| doc.dereference(value).and_then(|(_, v)| v.as_float()).ok())
+        .collect::<Option<_>>()?;
+    let [x0, y0, x1, y1] = corners[..] else {
+        return None;
+    };
+    let geometry = PageGeometry {
+        left: x0.min(x1),
+        bottom: y0.min(y1),
+        width: (x1 - x0).abs(),
+        height: (y1 - y0).abs(),
+        rotation: 0,
+    };
+    (geometry.width > 0.0 && geometry.height > 0.0).then_some(geometry)
+}
+
+/// How a painted path is drawn, for telling table rules from other graphics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PathKind {
+    /// A horizontal or vertical line, or a rectangle no thicker than
+    /// `MAX_RULE_THICKNESS`, as drawn between table rows and columns.
+    Rule,
+    /// Any other axis-aligned rectangle, e.g. a cell border or shading.
+    Rect,
+    /// Curves, diagonal lines and rotated shapes.
+    Other,
+}
+
+/// A painted line or shape, in page space.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PathElement {
+    pub page_number: u32,
+    /// (x0, y0, x1, y1), lower-left to upper-right.
+    pub bbox: (f32, f32, f32, f32),
+    pub kind: PathKind,
+    /// Line width when stroked, or 0 for paths that are only filled.
+    pub stroke_width: f32,
+}
+
+impl PathElement {
+    /// A rule running up the page rather than across.
+    pub fn is_vertical_rule(&self) -> bool {
+        let (x0, y0, x1, y1) = self.bbox;
+        self.kind == PathKind::Rule && y1 - y0 > x1 - x0
+    }
+}
+
+/// Thickest filled rectangle still taken for a rule, in points.
+pub const MAX_RULE_THICKNESS: f32 = 2.0;
+
+/// Offset below which a line counts as horizontal or vertical, in points.
+const AXIS_TOLERANCE: f32 = 0.1;
+
+/// A subpath under construction, with its points already in page space.
+enum PathSegment {
+    Line((f32, f32), (f32, f32)),
+    Rect([(f32, f32); 4]),
+    Curve(Vec<(f32, f32)>),
+}
+
+impl PathSegment {
+    fn points(&self) -> Vec<(f32, f32)> {
+        match self {
+            PathSegment::Line(from, to) => vec![*from, *to],
+            PathSegment::Rect(corners) => corners.to_vec(),
+            PathSegment::Curve(points) => points.clone(),
+        }
+    }
+
+    fn kind(&self) -> PathKind {
+        let aligned = |(x0, y0): (f32, f32), (x1, y1): (f32, f32)| {
+            (x1 - x0).abs() < AXIS_TOLERANCE || (y1 - y0).abs() < AXIS_TOLERANCE
+        };
+        match self {
+            PathSegment::Line(from, to) if aligned(*from, *to) => PathKind::Rule,
+            PathSegment::Rect([a, b, c, _]) if aligned(*a, *b) && aligned(*b, *c) => {
+                let (x0, y0, x1, y1) = bounding_box(&self.points());
+                if (x1 - x0).min(y1 - y0) <= MAX_RULE_THICKNESS {
+                    PathKind::Rule
+                } else {
+                    PathKind::Rect
+                }
+            }
+            _ => PathKind::Other,
+        }
+    }
+}
+
+fn bounding_box(points: &[(f32, f32)]) -> (f32, f32, f32, f32) {
+    points.iter().fold(
+        (f32::MAX, f32::MAX, f32::MIN, f32::MIN),
+        |(x0, y0, x1, y1), &(x, y)| (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+    )
+}
+
+/// The lines and shapes painted on every page, including those drawn by Form
+/// XObjects, for detecting tables and separators. They are placed through the
+/// same transforms as the page's text. Clipping paths that aren't painted are
+/// skipped.
+pub fn get_pdf_paths(doc: &Document) -> Result<Vec<PathElement>, DelverError> {
+    let column_gap = TextOptions::default().column_gap;
+    let font_cache = FontCache::default();
+    let mut paths = Vec::new();
+    for (page_number, page_id) in doc.get_pages() {
+        let content = get_page_content(doc, page_number, page_id, column_gap, &font_cache)
+            .map_err(|source| DelverError::PageExtraction {
+                page: page_number,
+                source,
+            })?;
+        paths.extend(content.paths);
+    }
+    Ok(paths)
+}
+
+/// The subpaths of the path under construction, until a painting operator
+/// turns them into `PathElement`s or `n` discards them.
+#[derive(Default)]
+struct PathBuilder {
+    segments: Vec<PathSegment>,
+    current: (f32, f32),
+    subpath_start: (f32, f32),
+}
+
+impl PathBuilder {
+    /// Apply a path construction or painting operator drawn under `ctm`.
+    fn apply(
+        &mut self,
+        op: &Operation,
+        ctm: &[f32; 6],
+        line_width: f32,
+        page_number: u32,
+        paths: &mut Vec<PathElement>,
+    ) {
+        let numbers: Vec<f32> = op
+            .operands
+            .iter()
+            .map(|operand| operand.as_float().unwrap_or(0.0))
+            .collect();
+        let point = |i: usize| transform_point(ctm, (numbers[i], numbers[i + 1]));
+        match (op.operator.as_str(), numbers.len()) {
+            ("m", 2) => {
+                self.current = point(0);
+                self.subpath_start = self.current;
+            }
+            ("l", 2) => {
+                let to = point(0);
+                self.segments.push(PathSegment::Line(self.current, to));
+                self.current = to;
+            }
+            ("c", 6) => {
+                let points = vec![self.current, point(0), point(2), point(4)];
+                self.current = point(4);
+                self.segments.push(PathSegment::Curve(points));
+            }
+            ("v" | "y", 4) => {
+                let points = vec![self.current, point(0), point(2)];
+                self.current = point(2);
+                self.segments.push(PathSegment::Curve(points));
+            }
+            ("re", 4) => {
+                let (x, y, w, h) = (numbers[0], numbers[1], numbers[2], numbers[3]);
+                let corners = [(x, y), (x + w, y), (x + w, y + h), (x, y + h)];
+                self.segments.push(PathSegment::Rect(
+                    corners.map(|corner| transform_point(ctm, corner)),
+                ));
+                self.current = transform_point(ctm, (x, y));
+                self.subpath_start = self.current;
+            }
+            ("h", _) => self.close(),
+            ("S" | "s" | "f" | "F" | "f*" | "B" | "B*" | "b" | "b*", _) => {
+                let stroked = matches!(op.operator.as_str(), "S" | "s" | "B" | "B*" | "b" | "b*");
+                // s, b and b* close the subpath first, as h does
+                if matches!(op.operator.as_str(), "s" | "b" | "b*") {
+                    self.close();
+                }
+                // Line width scales with the CTM; for a skewed CTM this is an average
+                let [a, b, c, d, _, _] = *ctm;
+                let scale = (a * d - b * c).abs().sqrt();
+                for segment in self.segments.drain(..) {
+                    paths.push(PathElement {
+                        page_number,
+                        bbox: bounding_box(&segment.points()),
+                        kind: segment.kind(),
+                        stroke_width: if stroked { line_width * scale } else { 0.0 },
+                    });
+                }
+            }
+            // Ends a path that only clips
+            ("n", _) => self.segments.clear(),
+            _ => {}
+        }
+    }
+
+    fn close(&mut self) {
+        if self.current != self.subpath_start {
+            self.segments
+                .push(PathSegment::Line(self.current, self.subpath_start));
+        }
+        self.current = self.subpath_start;
+    }
+}
+
+/// A bookmark from the document outline and the position it jumps to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlineEntry {
+    pub title: String,
+    pub page: u32,
+    /// Top of the destination view, when the destination gives one.
+    pub y: Option<f32>,
+    /// Nesting level; top-level bookmarks are 0.
+    pub depth: usize,
+}
+
+/// Limit on destination indirection (references, names, `/D` wrappers).
+const MAX_DESTINATION_DEPTH: usize = 8;
+
+/// Limit on name tree nesting.
+const MAX_NAME_TREE_DEPTH: usize = 32;
+
+/// Everything needed to turn a destination into a page and position.
+struct Destinations {
+    page_numbers: BTreeMap<ObjectId, u32>,
+    named: IndexMap<String, Object>,
+}
+
+/// Walk the `/Outlines` tree and resolve each bookmark to a page. Bookmarks whose
+/// destination can't be resolved are skipped rather than failing the document.
+pub fn get_outline_entries(doc: &Document) -> Vec<OutlineEntry> {
+    let destinations = Destinations {
+        page_numbers: doc
+            .get_pages()
+            .into_iter()
+            .map(|(page_num, page_id)| (page_id, page_num))
+            .collect(),
+        named: get_named_destinations(doc),
+    };
+    let first = doc
+        .catalog()
+        .and_then(|catalog| catalog.get_deref(b"Outlines", doc))
+        .and_then(Object::as_dict)
+        .and_then(|outlines| outlines.get(b"First"))
+        .and_then(Object::as_reference)
+        .ok();
+
+    let mut entries = Vec::new();
+    let mut visited = BTreeSet::new();
+    collect_outline_items(doc, first, 0, &destinations, &mut visited, &mut entries);
+    entries
+}
+
+fn collect_outline_items(
+    doc: &Document,
+    mut item: Option<ObjectId>,
+    depth: usize,
+    destinations: &Destinations,
+    visited: &mut BTreeSet<ObjectId>,
+    entries: &mut Vec<OutlineEntry>,
+) {
+    while let Some(item_id) = item {
+        if !visited.insert(item_id) {
+            warn!("Outline item {:?} is linked twice, stopping", item_id);
+            break;
+        }
+        let Ok(dict) = doc.get_dictionary(item_id) else {
+            warn!("Outline item {:?} is not a dictionary", item_id);
+            break;
+        };
+
+        let destination = link_destination(doc, dict);
+        match destination.and_then(|dest| resolve_destination(doc, dest, destinations, 0)) {
+            Some((page, _, y)) => entries.push(OutlineEntry {
+                title: dict
+                    .get(b"Title")
+                    .map(decode_text_string)
+                    .ok()
+                    .and_then(Result::ok)
+                    .unwrap_or_default(),
+                page,
+                y,
+                depth,
+            }),
+            None => debug!("Outline item {:?} has no usable destination", item_id),
+        }
+
+        let child = dict.get(b"First").and_then(Object::as_reference).ok();
+        collect_outline_items(doc, child, depth + 1, destinations, visited, entries);
+        item = dict.get(b"Next").and_then(Object::as_reference).ok();
+    }
+}
+
+/// The `/Dest` of an outline item or link annotation, or the `/D` of its GoTo action.
+fn link_destination<'a>(doc: &'a Document, dict: &'a Dictionary) -> Option<&'a Object> {
+    dict.get(b"Dest").ok().or_else(|| {
+        let action = dict.get_deref(b"A", doc).and_then(Object::as_dict).ok()?;
+        match action.get(b"S").and_then(Object::as_name) {
+            Ok(b"GoTo") => action.get(b"D").ok(),
+            _ => None,
+        }
+    })
+}
+
+/// Resolve an explicit or named destination to a page number and, where the view
+/// gives them, its left and top coordinates.
+fn resolve_destination(
+    doc: &Document,
+    destination: &Object,
+    destinations: &Destinations,
+    depth: usize,
+) -> Option<(u32, Option<f32>, Option<f32>)> {
+    if depth > MAX_DESTINATION_DEPTH {
+        return None;
+    }
+    match destination {
+        Object::Reference(id) => {
+            resolve_destination(doc, doc.get_object(*id).ok()?, destinations, depth + 1)
+        }
+        Object::Name(name) | Object::String(name, _) => {
+            let target = destinations.named.get(&*String::from_utf8_lossy(name))?;
+            resolve_destination(doc, target, destinations, depth + 1)
+        }
+        Object::Dictionary(dict) => {
+            resolve_destination(doc, dict.get(b"D").ok()?, destinations, depth + 1)
+        }
+        Object::Array(array) => {
+            let page_id = array.first()?.as_reference().ok()?;
+            let page = *destinations.page_numbers.get(&page_id)?;
+            let (left, top) = match array.get(1).and_then(|view| view.as_name().ok()) {
+                Some(b"XYZ") => (array.get(2), array.get(3)),
+                Some(b"FitR") => (array.get(2), array.get(5)),
+                Some(b"FitH") | Some(b"FitBH") => (None, array.get(2)),
+                Some(b"FitV") | Some(b"FitBV") => (array.get(2), None),
+                _ => (None, None),
+            };
+            let coordinate = |value: Option<&Object>| value.and_then(|v| v.as_float().ok());
+            Some((page, coordinate(left), coordinate(top)))
+        }
+        _ => None,
+    }
+}
+
+/// Where an internal link annotation jumps to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkTarget {
+    pub page: u32,
+    /// Left and top of the destination view, when the destination gives them.
+    pub x: Option<f32>,
+    pub y: Option<f32>,
+}
+
+/// Destinations of the `/Link` annotations on every page, one per link. Links
+/// to other documents or URIs are ignored.
+pub fn collect_link_targets(doc: &Document) -> Vec<LinkTarget> {
+    let pages = doc.get_pages();
+    let destinations = Destinations {
+        page_numbers: pages
+            .iter()
+            .map(|(page_num, page_id)| (*page_id, *page_num))
+            .collect(),
+        named: get_named_destinations(doc),
+    };
+
+    let mut targets = Vec::new();
+    for page_id in pages.values() {
+        let Ok(annots) = doc
+            .get_dictionary(*page_id)
+            .and_then(|page| page.get_deref(b"Annots", doc))
+            .and_then(Object::as_array)
+        else {
+            continue;
+        };
+        for annot in annots {
+            let Ok(annot) = doc.dereference(annot).and_then(|(_, a)| a.as_dict()) else {
+                continue;
+            };
+            if !matches!(annot.get(b"Subtype").and_then(Object::as_name), Ok(b"Link")) {
+                continue;
+            }
+            if let Some((page, x, y)) = link_destination(doc, annot)
+                .and_then(|dest| resolve_destination(doc, dest, &destinations, 0))
+            {
+                targets.push(LinkTarget { page, x, y });
+            }
+        }
+    }
+    targets
+}
+
+/// Named destinations from both the legacy catalog `/Dests` dictionary and the
+/// PDF 1.2 `/Names` → `/Dests` name tree, with each value resolved to its explicit
+/// destination array where possible. Name tree entries win over legacy ones.
+pub fn get_named_destinations(doc: &Document) -> IndexMap<String, Object> {
+    let mut named = IndexMap::new();
+    let Ok(catalog) = doc.catalog() else {
+        return named;
+    };
+
+    if let Ok(dests) = catalog.get_deref(b"Dests", doc).and_then(Object::as_dict) {
+        for (name, value) in dests.iter() {
+            named.insert(String::from_utf8_lossy(name).to_string(), value.clone());
+        }
+    }
+    if let Ok(tree) = catalog
+        .get_deref(b"Names", doc)
+        .and_then(Object::as_dict)
+        .and_then(|names| names.get_deref(b"Dests", doc))
+    {
+        let mut visited = BTreeSet::new();
+        collect_name_tree(doc, tree, 0, &mut visited, &mut named);
+    }
+
+    for value in named.values_mut() {
+        if let Some(explicit) = explicit_destination(doc, value, 0) {
+            *value = explicit;
+        }
+    }
+    named
+}
+
+/// Flatten the `/Names` arrays of a name tree node and its `/Kids`.
+fn collect_name_tree(
+    doc: &Document,
+    node: &Object,
+    depth: usize,
+    visited: &mut BTreeSet<ObjectId>,
+    named: &mut IndexMap<String, Object>,
+) {
+    if depth > MAX_NAME_TREE_DEPTH {
+        warn!("Name tree is nested too deeply, stopping");
+        return;
+    }
+    let node = match node {
+        Object::Reference(id) => {
+            if !visited.insert(*id) {
+                warn!("Name tree node {:?} is linked twice, skipping", id);
+                return;
+            }
+            match doc.get_object(*id) {
+                Ok(node) => node,
+                Err(_) => return,
+            }
+        }
+        node => node,
+    };
+    let Ok(dict) = node.as_dict() else {
+        return;
+    };
+
+    if let Ok(names) = dict.get_deref(b"Names", doc).and_then(Object::as_array) {
+        for pair in names.chunks_exact(2) {
+            if let Ok(name) = pair[0].as_str() {
+                named.insert(String::from_utf8_lossy(name).to_string(), pair[1].clone());
+            }
+        }
+    }
+    if let Ok(kids) = dict.get_deref(b"Kids", doc).and_then(Object::as_array) {
+        for kid in kids {
+            collect_name_tree(doc, kid, depth + 1, visited, named);
+        }
+    }
+}
+
+/// Follow references and `/D` entries down to an explicit destination array.
+fn explicit_destination(doc: &Document, value: &Object, depth: usize) -> Option<Object> {
+    if depth > MAX_DESTINATION_DEPTH {
+        return None;
+    }
+    match value {
+        Object::Reference(id) => explicit_destination(doc, doc.get_object(*id).ok()?, depth + 1),
+        Object::Dictionary(dict) => explicit_destination(doc, dict.get(b"D").ok()?, depth + 1),
+        Object::Array(_) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+/// Write the table of contents from the outline of the PDF at `path` to
+/// `output` as JSON, indented when `pretty` is set.
+pub fn pdf2toc<P: AsRef<Path> + Debug>(
+    path: P,
+    output: P,
+    pretty: bool,
+) -> Result<(), DelverError> {
     println!("Load {path:?}");
     let doc = load_pdf(&path)?;
 
-    doc.get_toc()
-        .map_err(|e| Error::new(ErrorKind::Other, e.to_string()))?;
+    let toc = doc.get_toc()?;
+    let json = if pretty {
+        serde_json::to_string_pretty(&toc)?
+    } else {
+        serde_json::to_string(&toc)?
+    };
+    File::create(&output)?.write_all(json.as_bytes())?;
 
     // TODO: Support documents without Outlines
     // let mut destinations: IndexMap<String, Object> = IndexMap::new();
@@ -0,0 +1,56 @@
+use delver::builder::{ElementBuilder, TemplateBuilder};
+use delver::dom::parse_template;
+
+#[test]
+fn test_builder_matches_dsl() {
+    let dsl = std::fs::read_to_string("10k.tmpl").unwrap();
+    let built = TemplateBuilder::new()
+        .element(
+            ElementBuilder::section("section1")
+                .match_pattern("Section 1: Management Discussion & Analysis")
+                .child(
+                    ElementBuilder::section("section1_1")
+                        .match_pattern("Section 1.1: Risks")
+                        .child(
+                            ElementBuilder::section("section1_1b")
+                                .match_pattern("Section 1.1b: Fiscal Risks")
+                                .child(
+                                    ElementBuilder::text_chunk()
+                                        .chunk_size(500)
+                                        .overlap(150)
+                                        .add_meta(&["section1", "section1_1", "section1_1b"]),
+                                ),
+                        ),
+                ),
+        )
+        .build()
+        .unwrap();
+
+    assert_eq!(built, parse_template(&dsl).unwrap());
+}
+
+#[test]
+fn test_builder_validates_attributes() {
+    let built = TemplateBuilder::new()
+        .element(
+            ElementBuilder::section("mda")
+                .match_pattern("Management's Discussion")
+                .end_match("Item 8")
+                .threshold(0.85),
+        )
+        .build()
+        .unwrap();
+    assert_eq!(
+        built,
+        parse_template(
+            r#"Section(as="mda", match="Management's Discussion", end_match="Item 8", threshold=0.85)"#
+        )
+        .unwrap()
+    );
+
+    let error = TemplateBuilder::new()
+        .element(ElementBuilder::text_chunk().threshold(0.5))
+        .build()
+        .unwrap_err();
+    assert_eq!(error.element.as_deref(), Some("TextChunk"));
+}
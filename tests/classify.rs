@@ -0,0 +1,114 @@
+mod common;
+
+use common::element;
+use delver::layout::{
+    classify_lines, group_text_into_lines, LineKind, DEFAULT_LINE_JOIN_THRESHOLD,
+};
+use delver::parse::TextElement;
+
+fn classify(elements: &[TextElement]) -> Vec<(String, LineKind)> {
+    let lines = group_text_into_lines(elements, DEFAULT_LINE_JOIN_THRESHOLD);
+    let kinds = classify_lines(elements, &lines);
+    lines.into_iter().map(|line| line.text).zip(kinds).collect()
+}
+
+#[test]
+fn test_classify_lines() {
+    let elements = vec![
+        element("Annual Report", 1, "Helvetica", 20.0, (72.0, 740.0)),
+        element("Results of Operations", 1, "Helvetica", 16.0, (72.0, 710.0)),
+        element(
+            "Revenue grew in every segment during the year,",
+            1,
+            "Helvetica",
+            12.0,
+            (72.0, 690.0),
+        ),
+        element(
+            "led by strong demand in the Americas.",
+            1,
+            "Helvetica",
+            12.0,
+            (72.0, 676.0),
+        ),
+        element(
+            "• Industrial sales rose 4%",
+            1,
+            "Helvetica",
+            12.0,
+            (72.0, 660.0),
+        ),
+        element(
+            "(b) Safety sales rose 2%",
+            1,
+            "Helvetica",
+            12.0,
+            (72.0, 646.0),
+        ),
+        element(
+            "Table 1: Sales by segment",
+            1,
+            "Helvetica",
+            10.0,
+            (72.0, 630.0),
+        ),
+        element("Liquidity", 1, "Helvetica-Bold", 12.0, (72.0, 600.0)),
+        element(
+            "Cash on hand was higher than last year.",
+            1,
+            "Helvetica",
+            12.0,
+            (72.0, 586.0),
+        ),
+        element(
+            "Operating cash flow covered all capital spending for the year.",
+            1,
+            "Helvetica",
+            12.0,
+            (72.0, 572.0),
+        ),
+    ];
+
+    let kinds: Vec<LineKind> = classify(&elements)
+        .into_iter()
+        .map(|(_, kind)| kind)
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            LineKind::Heading,
+            LineKind::Heading,
+            LineKind::Body,
+            LineKind::Body,
+            LineKind::ListItem,
+            LineKind::ListItem,
+            LineKind::Caption,
+            LineKind::Heading,
+            LineKind::Body,
+            LineKind::Body,
+        ]
+    );
+}
+
+#[test]
+fn test_uniform_text_is_body() {
+    let elements = vec![
+        element(
+            "1.5 million units shipped.",
+            1,
+            "Helvetica",
+            12.0,
+            (72.0, 700.0),
+        ),
+        element(
+            "Table stakes for the industry rose.",
+            1,
+            "Helvetica",
+            12.0,
+            (72.0, 686.0),
+        ),
+    ];
+    for (text, kind) in classify(&elements) {
+        assert_eq!(kind, LineKind::Body, "{text}");
+    }
+}
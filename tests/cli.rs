@@ -0,0 +1,167 @@
+mod common;
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+use common::{build_pdf, text_ops};
+
+const HEADING: &str = "Discussion and Analysis of Financial Condition and Results of Operations";
+const TEMPLATE: &str = r#"Section(match="Discussion and Analysis", as="mdna")"#;
+
+fn pdf_bytes() -> Vec<u8> {
+    let mut page = text_ops(14, 72, 700, HEADING);
+    page.extend(text_ops(10, 72, 680, "Revenue grew."));
+    let mut doc = build_pdf(vec![page]);
+    let mut bytes = Vec::new();
+    doc.save_to(&mut bytes).unwrap();
+    bytes
+}
+
+/// Run the binary on the fixture PDF piped through stdin.
+fn run_on_stdin(args: &[&str]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_delver"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The binary may exit before reading stdin, e.g. on a bad template
+    let _ = child.stdin.take().unwrap().write_all(&pdf_bytes());
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_stdin_with_compact_output() {
+    let output = run_on_stdin(&["-", "--template-str", TEMPLATE]);
+    assert!(output.status.success(), "{output:?}");
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 1);
+    let sections: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    let section = &sections[0];
+    assert_eq!(section["name"], "mdna");
+    assert_eq!(section["page"], 1);
+    assert_eq!(section["heading"], HEADING);
+    assert!(section["text"].as_str().unwrap().contains("Revenue grew."));
+}
+
+#[test]
+fn test_pretty_output() {
+    let output = run_on_stdin(&["-", "--template-str", TEMPLATE, "--pretty"]);
+    assert!(output.status.success(), "{output:?}");
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.lines().count() > 1);
+    let sections: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(sections[0]["heading"], HEADING);
+}
+
+#[test]
+fn test_template_drives_extraction() {
+    let template = r#"
+        Section(match="Revenue grew", as="revenue")
+        Section(match="Not in this filing", as="missing")
+    "#;
+    let output = run_on_stdin(&["-", "--template-str", template]);
+    assert!(output.status.success(), "{output:?}");
+
+    let sections: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let sections = sections.as_array().unwrap();
+    assert_eq!(sections.len(), 1);
+    assert_eq!(sections[0]["name"], "revenue");
+    assert_eq!(sections[0]["heading"], "Revenue grew.");
+}
+
+#[test]
+fn test_template_flags_conflict() {
+    let output = run_on_stdin(&["-", "--template", "10k.tmpl", "--template-str", TEMPLATE]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used with"));
+}
+
+#[test]
+fn test_invalid_inline_template() {
+    let output = run_on_stdin(&["-", "--template-str", "Section(matc=\"x\")"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid template --template-str"));
+}
+
+#[test]
+fn test_timings_table() {
+    let output = run_on_stdin(&["-", "--template-str", TEMPLATE, "--timings"]);
+    assert!(output.status.success(), "{output:?}");
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    for stage in [
+        "template",
+        "load",
+        "text",
+        "cleanup",
+        "sections",
+        "serialization",
+        "page (max)",
+        "page (mean)",
+    ] {
+        let line = stderr
+            .lines()
+            .find(|line| line.starts_with(&format!("{stage} ")))
+            .unwrap_or_else(|| panic!("no {stage} timing in {stderr}"));
+        let ms: f64 = line.rsplit(' ').next().unwrap().parse().unwrap();
+        assert!(ms >= 0.0);
+    }
+
+    // Timings never change what is written to stdout
+    let plain = run_on_stdin(&["-", "--template-str", TEMPLATE]);
+    assert!(!String::from_utf8_lossy(&plain.stderr).contains("serialization"));
+    let timed: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let plain: serde_json::Value = serde_json::from_slice(&plain.stdout).unwrap();
+    assert_eq!(timed, plain);
+}
+
+#[test]
+fn test_text_subcommand() {
+    let output = run_on_stdin(&["text", "-"]);
+    assert!(output.status.success(), "{output:?}");
+
+    let text: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(text["text"]["1"][0], HEADING);
+    assert_eq!(text["text"]["1"][1], "Revenue grew.");
+    assert_eq!(text["errors"], serde_json::json!([]));
+}
+
+#[test]
+fn test_subcommands_take_password() {
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_delver"))
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let output = run(&["text", "tests/encrypted.pdf"]);
+    assert!(!output.status.success());
+
+    let output = run(&["text", "tests/encrypted.pdf", "--password", "secret"]);
+    assert!(output.status.success(), "{output:?}");
+    let text: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(text["text"]["1"][0], "Confidential Annual Report");
+
+    let output = run(&["headings", "--password", "secret", "tests/encrypted.pdf"]);
+    assert!(output.status.success(), "{output:?}");
+}
+
+#[test]
+fn test_headings_subcommand() {
+    let output = Command::new(env!("CARGO_BIN_EXE_delver"))
+        .args(["headings", "tests/example.pdf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let headings: Vec<&str> = stdout.lines().map(|line| &line[18..]).collect();
+    assert_eq!(
+        headings,
+        vec!["Hello World!", "  Subheading 1", "  Subheading 2"]
+    );
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use common::{build_pdf, text_ops};
+use delver::dom::parse_template;
+use delver::layout::{
+    elements_by_color, extract_template_sections, perform_line_matching, restrict_to_fonts,
+    ChunkFilter, FontConstraints, COLOR_TOLERANCE,
+};
+use delver::parse::{get_pdf_text, load_pdf_bytes, TextElement};
+use lopdf::content::Operation;
+
+fn colored(operator: &str, components: &[f32], mut ops: Vec<Operation>) -> Vec<Operation> {
+    let operands = components.iter().map(|c| (*c).into()).collect();
+    ops.insert(0, Operation::new(operator, operands));
+    ops
+}
+
+fn elements() -> Vec<TextElement> {
+    let mut page = vec![Operation::new("q", vec![])];
+    page.extend(colored(
+        "rg",
+        &[1.0, 0.0, 0.0],
+        text_ops(10, 72, 700, "Results"),
+    ));
+    page.push(Operation::new("Q", vec![]));
+    page.extend(text_ops(10, 72, 680, "Results improved this year."));
+    page.extend(colored(
+        "g",
+        &[0.5],
+        text_ops(8, 72, 660, "Past results are no guarantee."),
+    ));
+    page.extend(colored(
+        "k",
+        &[0.0, 1.0, 1.0, 0.0],
+        text_ops(10, 72, 640, "(1,204)"),
+    ));
+    let mut doc = build_pdf(vec![page]);
+    let mut bytes = Vec::new();
+    doc.save_to(&mut bytes).unwrap();
+    get_pdf_text(&load_pdf_bytes(&bytes).unwrap()).unwrap()
+}
+
+#[test]
+fn test_fill_color_is_recorded() {
+    let colors: Vec<_> = elements().iter().map(|mi| mi.color).collect();
+    assert_eq!(
+        colors,
+        vec![
+            Some((255, 0, 0)),
+            Some((0, 0, 0)),
+            Some((128, 128, 128)),
+            Some((255, 0, 0)),
+        ]
+    );
+}
+
+#[test]
+fn test_red_heading_is_told_from_black_text() {
+    let elements = elements();
+    let matched = perform_line_matching(&elements, "Results");
+    assert_eq!(matched.len(), 2);
+
+    let root = parse_template(r##"Section(match="Results", color="#ee1010") {}"##).unwrap();
+    let fonts = FontConstraints::from_element(&root.elements[0]);
+    let kept = restrict_to_fonts(matched, &fonts);
+    assert_eq!(kept.len(), 1);
+    assert_eq!(kept[0].text, "Results");
+
+    let red = elements_by_color(&elements, (255, 0, 0), COLOR_TOLERANCE);
+    let red: Vec<&str> = red.iter().map(|mi| mi.text.as_str()).collect();
+    assert_eq!(red, vec!["Results", "(1,204)"]);
+}
+
+#[test]
+fn test_exclude_color_drops_grey_text() {
+    let root = parse_template(r##"TextChunk(excludeColor="#808080")"##).unwrap();
+    let filter = ChunkFilter::from_element(&root.elements[0]).unwrap();
+    let (kept, dropped) = filter.filter_lines(&elements());
+    assert_eq!(dropped, 1);
+    assert!(kept.iter().all(|mi| !mi.text.starts_with("Past results")));
+}
+
+#[test]
+fn test_template_colors_reach_extraction() {
+    let mut page = text_ops(12, 72, 720, "Results at a glance");
+    page.push(Operation::new("q", vec![]));
+    page.extend(colored(
+        "rg",
+        &[1.0, 0.0, 0.0],
+        text_ops(10, 72, 700, "Results"),
+    ));
+    page.push(Operation::new("Q", vec![]));
+    page.push(Operation::new("q", vec![]));
+    page.extend(colored(
+        "g",
+        &[0.5],
+        text_ops(8, 72, 680, "Past results are no guarantee."),
+    ));
+    page.push(Operation::new("Q", vec![]));
+    page.extend(text_ops(9, 72, 660, "Revenue grew."));
+    let doc = build_pdf(vec![page]);
+    let elements = get_pdf_text(&doc).unwrap();
+    let extract = |template: &str| {
+        let root = parse_template(template).unwrap();
+        let sections = extract_template_sections(&doc, &elements, &root).unwrap();
+        (sections[0].heading.clone(), sections[0].text.clone())
+    };
+
+    assert_eq!(
+        extract(r#"Section(match="Results")"#).0,
+        "Results at a glance"
+    );
+    assert_eq!(
+        extract(r##"Section(match="Results", color="#ee1010")"##),
+        (
+            "Results".to_string(),
+            "Results Past results are no guarantee. Revenue grew. ".to_string()
+        )
+    );
+    assert_eq!(
+        extract(
+            r##"Section(match="Results", color="#ee1010") { TextChunk(excludeColor="#808080") }"##
+        )
+        .1,
+        "Results Revenue grew. "
+    );
+}
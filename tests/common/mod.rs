@@ -0,0 +1,100 @@
+#![allow(dead_code)]
+
+use delver::parse::TextElement;
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+
+/// A text element as the parser would report it, for tests that skip the PDF.
+pub fn element(
+    text: &str,
+    page_number: u32,
+    font: &str,
+    font_size: f32,
+    position: (f32, f32),
+) -> TextElement {
+    TextElement {
+        text: text.to_string(),
+        page_number,
+        font_size,
+        font_name: Some(font.to_string()),
+        position,
+        ..Default::default()
+    }
+}
+
+/// Operations drawing a single line of text with font `/F1`.
+pub fn text_ops(font_size: i64, x: i64, y: i64, text: &str) -> Vec<Operation> {
+    vec![
+        Operation::new("BT", vec![]),
+        Operation::new("Tf", vec!["F1".into(), font_size.into()]),
+        Operation::new("Td", vec![x.into(), y.into()]),
+        Operation::new("Tj", vec![Object::string_literal(text)]),
+        Operation::new("ET", vec![]),
+    ]
+}
+
+/// Add a Courier Type1 font to `doc`.
+pub fn add_courier_font(doc: &mut Document) -> ObjectId {
+    doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Courier",
+    })
+}
+
+/// Build a document with one page per entry of `pages`, using Courier as `/F1`.
+pub fn build_pdf(pages: Vec<Vec<Operation>>) -> Document {
+    let mut doc = Document::with_version("1.5");
+    let font_id = add_courier_font(&mut doc);
+    let resources = dictionary! {
+        "Font" => dictionary! { "F1" => font_id },
+    };
+    finish_pdf(doc, resources, pages)
+}
+
+/// Add the page tree and catalog to `doc`, sharing `resources` on every page.
+pub fn finish_pdf(
+    mut doc: Document,
+    resources: Dictionary,
+    pages: Vec<Vec<Operation>>,
+) -> Document {
+    let pages_id = doc.new_object_id();
+    let resources_id = doc.add_object(resources);
+
+    let kids: Vec<Object> = pages
+        .into_iter()
+        .map(|operations| {
+            let content = Content { operations };
+            let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+            doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "Contents" => content_id,
+            })
+            .into()
+        })
+        .collect();
+
+    let pages = dictionary! {
+        "Type" => "Pages",
+        "Count" => kids.len() as i64,
+        "Kids" => kids,
+        "Resources" => resources_id,
+        "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+    };
+    doc.objects.insert(pages_id, Object::Dictionary(pages));
+
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    doc.trailer.set("Root", catalog_id);
+    doc
+}
+
+/// Save `doc` under the system temp directory and return the path.
+pub fn save_temp_pdf(doc: &mut Document, name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("delver-{}-{name}", std::process::id()));
+    doc.save(&path).unwrap();
+    path
+}
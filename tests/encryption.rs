@@ -0,0 +1,30 @@
+use delver::parse::{get_pdf_text, load_pdf_with_options, PdfOptions};
+
+const ENCRYPTED_PDF: &str = "tests/encrypted.pdf";
+
+#[test]
+fn test_load_encrypted_pdf_with_password() {
+    let options = PdfOptions {
+        password: Some("secret".to_string()),
+    };
+    let doc = load_pdf_with_options(ENCRYPTED_PDF, &options).unwrap();
+
+    let text_elements = get_pdf_text(&doc).unwrap();
+    let text: Vec<&str> = text_elements.iter().map(|e| e.text.as_str()).collect();
+    assert_eq!(text, vec!["Confidential Annual Report"]);
+}
+
+#[test]
+fn test_load_encrypted_pdf_with_wrong_password() {
+    let options = PdfOptions {
+        password: Some("not-the-password".to_string()),
+    };
+    let err = load_pdf_with_options(ENCRYPTED_PDF, &options).unwrap_err();
+    assert!(err.to_string().contains("incorrect password"), "{err}");
+}
+
+#[test]
+fn test_load_encrypted_pdf_without_password() {
+    let err = load_pdf_with_options(ENCRYPTED_PDF, &PdfOptions::default()).unwrap_err();
+    assert!(err.to_string().contains("incorrect password"), "{err}");
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use common::{build_pdf, save_temp_pdf, text_ops};
+use delver::dom::load_template;
+use delver::error::DelverError;
+use delver::parse::{load_pdf, load_pdf_with_options, PdfOptions};
+
+#[test]
+fn test_malformed_template_is_template_error() {
+    let path = std::env::temp_dir().join(format!("delver-{}-malformed.tmpl", std::process::id()));
+    std::fs::write(&path, "Section(match=\"Risks\" {").unwrap();
+
+    let err = load_template(&path).unwrap_err();
+    assert!(
+        matches!(err, DelverError::Template(ref e) if e.line == 1),
+        "{err}"
+    );
+}
+
+#[test]
+fn test_missing_template_is_io_error() {
+    let err = load_template("does/not/exist.tmpl").unwrap_err();
+    assert!(matches!(err, DelverError::Io(_)), "{err}");
+}
+
+#[test]
+fn test_truncated_pdf_is_load_error() {
+    let mut doc = build_pdf(vec![text_ops(12, 72, 700, "Annual Report")]);
+    let path = save_temp_pdf(&mut doc, "complete.pdf");
+    let bytes = std::fs::read(&path).unwrap();
+    let truncated = path.with_extension("truncated.pdf");
+    std::fs::write(&truncated, &bytes[..bytes.len() / 3]).unwrap();
+
+    let err = load_pdf(&truncated).unwrap_err();
+    assert!(matches!(err, DelverError::PdfLoad(_)), "{err}");
+}
+
+#[test]
+fn test_wrong_password_is_its_own_variant() {
+    let options = PdfOptions {
+        password: Some("not-the-password".to_string()),
+    };
+    let err = load_pdf_with_options("tests/encrypted.pdf", &options).unwrap_err();
+    assert!(matches!(err, DelverError::IncorrectPassword), "{err}");
+}
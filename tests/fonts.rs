@@ -0,0 +1,481 @@
+mod common;
+
+use common::{element, finish_pdf, save_temp_pdf};
+use delver::builder::ElementBuilder;
+use delver::dom::parse_template;
+use delver::fonts::{load_afm, load_metrics_json, register_metrics, CanonicalFont, FontMetrics};
+use delver::layout::{
+    extract_template_sections, perform_line_matching, restrict_to_fonts, select_best_match,
+    FontConstraints,
+};
+use delver::parse::{get_pdf_text, load_pdf, TextElement};
+use lopdf::content::Operation;
+use lopdf::{dictionary, Dictionary, Document, Object};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Body text naming "Overview" sits high on the page, which outscores the real
+/// heading further down on position alone.
+fn document() -> Vec<TextElement> {
+    vec![
+        element(
+            "See the Overview below.",
+            1,
+            "Helvetica",
+            10.0,
+            (72.0, 150.0),
+        ),
+        element("Overview", 1, "ABCDEF+Helvetica-Bold", 16.0, (72.0, 400.0)),
+        element("The business.", 1, "Helvetica", 10.0, (72.0, 380.0)),
+    ]
+}
+
+#[test]
+fn test_font_constraints_reject_body_text() {
+    let elements = document();
+    let matched = perform_line_matching(&elements, "Overview");
+    assert_eq!(
+        select_best_match(matched.clone()).unwrap().text,
+        "See the Overview below."
+    );
+
+    for template in [
+        r#"Section(match="Overview", minFontSize=14) { TextChunk() }"#,
+        r#"Section(match="Overview", bold=true) { TextChunk() }"#,
+        r#"Section(match="Overview", fontName="helvetica-bold") { TextChunk() }"#,
+    ] {
+        let root = parse_template(template).unwrap();
+        let fonts = FontConstraints::from_element(&root.elements[0]);
+        let best = select_best_match(restrict_to_fonts(matched.clone(), &fonts)).unwrap();
+        assert_eq!(best.text, "Overview", "{template}");
+        assert_eq!(best.font_size, 16.0);
+    }
+
+    let root =
+        parse_template(r#"Section(match="Overview", maxFontSize=12, bold=false) {}"#).unwrap();
+    let fonts = FontConstraints::from_element(&root.elements[0]);
+    let kept = restrict_to_fonts(matched, &fonts);
+    assert_eq!(kept.len(), 1);
+    assert_eq!(kept[0].text, "See the Overview below.");
+}
+
+#[test]
+fn test_template_font_constraints_reach_extraction() {
+    let line = |font: &str, size: i64, y: i64, text: &str| {
+        vec![
+            Operation::new("BT", vec![]),
+            Operation::new("Tf", vec![font.into(), size.into()]),
+            Operation::new("Td", vec![72.into(), y.into()]),
+            Operation::new("Tj", vec![Object::string_literal(text)]),
+            Operation::new("ET", vec![]),
+        ]
+    };
+    let mut page = line("F1", 10, 150, "See the Overview below.");
+    page.extend(line("F2", 16, 400, "Overview"));
+    page.extend(line("F1", 10, 380, "The business."));
+    let mut doc = Document::with_version("1.5");
+    let regular = doc.add_object(type1_font("Helvetica"));
+    let bold = doc.add_object(type1_font("Helvetica-Bold"));
+    let resources = dictionary! {
+        "Font" => dictionary! { "F1" => regular, "F2" => bold },
+    };
+    let doc = finish_pdf(doc, resources, vec![page]);
+    let elements = get_pdf_text(&doc).unwrap();
+
+    let heading = |template: &str| {
+        let root = parse_template(template).unwrap();
+        extract_template_sections(&doc, &elements, &root).unwrap()[0]
+            .heading
+            .clone()
+    };
+    assert_eq!(
+        heading(r#"Section(match="Overview")"#),
+        "See the Overview below."
+    );
+    for template in [
+        r#"Section(match="Overview", minFontSize=14)"#,
+        r#"Section(match="Overview", bold=true)"#,
+        r#"Section(match="Overview", fontName="helvetica-bold")"#,
+    ] {
+        assert_eq!(heading(template), "Overview", "{template}");
+    }
+    assert_eq!(
+        heading(r#"Section(match="Overview", maxFontSize=12, bold=false)"#),
+        "See the Overview below."
+    );
+}
+
+#[test]
+fn test_font_constraints_from_builder() {
+    let section = ElementBuilder::section("overview")
+        .match_pattern("Overview")
+        .min_font_size(14.0)
+        .max_font_size(20.0)
+        .font_name("Helvetica")
+        .bold(true)
+        .build();
+    assert_eq!(
+        FontConstraints::from_element(&section),
+        FontConstraints {
+            min_size: Some(14.0),
+            max_size: Some(20.0),
+            font_name: Some("helvetica".to_string()),
+            bold: Some(true),
+            color: None,
+        }
+    );
+    assert_eq!(
+        FontConstraints::from_element(&ElementBuilder::section("any").build()),
+        FontConstraints::default()
+    );
+}
+
+#[test]
+fn test_canonical_font_names() {
+    // BaseFont, then family, weight, italic and condensed
+    let cases = [
+        ("Helvetica", "Helvetica", 400, false, false),
+        ("Helvetica-Bold", "Helvetica", 700, false, false),
+        ("Helvetica-Oblique", "Helvetica", 400, true, false),
+        ("Helvetica-BoldOblique", "Helvetica", 700, true, false),
+        ("Times-Roman", "Times", 400, false, false),
+        ("Times-Bold", "Times", 700, false, false),
+        ("Times-Italic", "Times", 400, true, false),
+        ("Times-BoldItalic", "Times", 700, true, false),
+        ("Courier", "Courier", 400, false, false),
+        ("Courier-BoldOblique", "Courier", 700, true, false),
+        ("Symbol", "Symbol", 400, false, false),
+        ("ZapfDingbats", "ZapfDingbats", 400, false, false),
+        ("ArialMT", "Arial", 400, false, false),
+        ("Arial-BoldMT", "Arial", 700, false, false),
+        ("Arial-ItalicMT", "Arial", 400, true, false),
+        ("Arial-BoldItalicMT", "Arial", 700, true, false),
+        ("ABCDEF+Arial-BoldMT", "Arial", 700, false, false),
+        ("Arial,Bold", "Arial", 700, false, false),
+        ("Arial,BoldItalic", "Arial", 700, true, false),
+        ("ArialBold", "Arial", 700, false, false),
+        ("ArialNarrow", "Arial", 400, false, true),
+        ("ArialNarrow-Bold", "Arial", 700, false, true),
+        ("Arial-Black", "Arial", 900, false, false),
+        ("TimesNewRomanPSMT", "Times New Roman", 400, false, false),
+        (
+            "TimesNewRomanPS-BoldMT",
+            "Times New Roman",
+            700,
+            false,
+            false,
+        ),
+        (
+            "TimesNewRomanPS-ItalicMT",
+            "Times New Roman",
+            400,
+            true,
+            false,
+        ),
+        (
+            "QWERTY+TimesNewRomanPS-BoldItalicMT",
+            "Times New Roman",
+            700,
+            true,
+            false,
+        ),
+        ("TimesNewRoman,Bold", "Times New Roman", 700, false, false),
+        ("Calibri", "Calibri", 400, false, false),
+        ("Calibri-Bold", "Calibri", 700, false, false),
+        ("Calibri-Light", "Calibri", 300, false, false),
+        ("ABCDEF+Calibri,Bold", "Calibri", 700, false, false),
+        ("Calibri,Italic", "Calibri", 400, true, false),
+        ("Cambria", "Cambria", 400, false, false),
+        ("Cambria-BoldItalic", "Cambria", 700, true, false),
+        ("Cambria,Bold", "Cambria", 700, false, false),
+        ("Garamond", "Garamond", 400, false, false),
+        ("Garamond-Bold", "Garamond", 700, false, false),
+        ("Garamond-Semibold", "Garamond", 600, false, false),
+        ("AGaramondPro-Regular", "AGaramondPro", 400, false, false),
+        ("EBGaramond-SemiBoldItalic", "EB Garamond", 600, true, false),
+        ("Georgia-Italic", "Georgia", 400, true, false),
+        ("Verdana-Bold", "Verdana", 700, false, false),
+        ("Tahoma", "Tahoma", 400, false, false),
+        ("SegoeUI-Semibold", "Segoe UI", 600, false, false),
+        ("HelveticaNeue-Light", "Helvetica Neue", 300, false, false),
+        (
+            "HelveticaNeue-CondensedBold",
+            "Helvetica Neue",
+            700,
+            false,
+            true,
+        ),
+        ("MyriadPro-BoldCond", "Myriad Pro", 700, false, true),
+        ("MinionPro-It", "Minion Pro", 400, true, false),
+        ("MinionPro-BoldIt", "Minion Pro", 700, true, false),
+        ("Frutiger-Light", "Frutiger", 300, false, false),
+        ("FranklinGothic-Demi", "Franklin Gothic", 600, false, false),
+        (
+            "FranklinGothic-Medium",
+            "Franklin Gothic",
+            500,
+            false,
+            false,
+        ),
+        ("Futura-ExtraBold", "Futura", 800, false, false),
+        ("Univers-Condensed", "Univers", 400, false, true),
+        ("GillSans-Heavy", "Gill Sans", 800, false, false),
+        ("CourierNewPSMT", "Courier New", 400, false, false),
+        ("CourierNewPS-BoldMT", "Courier New", 700, false, false),
+        ("Heiti-SC", "Heiti-SC", 400, false, false),
+        ("F1", "F1", 400, false, false),
+    ];
+    for (base_font, family, weight, italic, condensed) in cases {
+        assert_eq!(
+            CanonicalFont::parse(base_font),
+            CanonicalFont {
+                family: family.to_string(),
+                weight,
+                italic,
+                condensed,
+            },
+            "{base_font}"
+        );
+    }
+}
+
+#[test]
+fn test_canonical_font_string_form() {
+    let names: Vec<String> = [
+        "ABCDEF+TimesNewRomanPS-BoldItalicMT",
+        "Arial,Bold",
+        "ArialMT",
+        "MyriadPro-BoldCond",
+    ]
+    .iter()
+    .map(|name| CanonicalFont::parse(name).to_string())
+    .collect();
+    assert_eq!(
+        names,
+        vec![
+            "Times New Roman-BoldItalic",
+            "Arial-Bold",
+            "Arial",
+            "Myriad Pro-BoldCondensed",
+        ]
+    );
+}
+
+fn type1_font(base_font: &str) -> Dictionary {
+    dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => base_font,
+    }
+}
+
+static SAVED_PDFS: AtomicUsize = AtomicUsize::new(0);
+
+/// x of the cell after "Revenue" and a 6em jump, in `font` with `descriptor` as
+/// its indirect `/FontDescriptor`, once saved and loaded again.
+fn second_cell_x(font: Dictionary, descriptor: Option<Dictionary>) -> f32 {
+    second_cell_x_in(Document::with_version("1.5"), font, descriptor)
+}
+
+/// `second_cell_x` in `doc`, for fonts referring to objects already in it.
+fn second_cell_x_in(
+    mut doc: Document,
+    mut font: Dictionary,
+    descriptor: Option<Dictionary>,
+) -> f32 {
+    if let Some(descriptor) = descriptor {
+        font.set("FontDescriptor", doc.add_object(descriptor));
+    }
+    let font_id = doc.add_object(font);
+    let resources = dictionary! {
+        "Font" => dictionary! { "F1" => font_id },
+    };
+    let page = vec![
+        Operation::new("BT", vec![]),
+        Operation::new("Tf", vec!["F1".into(), 12.into()]),
+        Operation::new("Td", vec![72.into(), 700.into()]),
+        Operation::new(
+            "TJ",
+            vec![Object::Array(vec![
+                Object::string_literal("Revenue"),
+                (-6000).into(),
+                Object::string_literal("9"),
+            ])],
+        ),
+        Operation::new("ET", vec![]),
+    ];
+    let mut doc = finish_pdf(doc, resources, vec![page]);
+    let name = format!("widths-{}.pdf", SAVED_PDFS.fetch_add(1, Ordering::Relaxed));
+    let path = save_temp_pdf(&mut doc, &name);
+    let x = get_pdf_text(&load_pdf(&path).unwrap()).unwrap()[1]
+        .position
+        .0;
+    std::fs::remove_file(&path).unwrap();
+    x
+}
+
+#[test]
+fn test_registered_metrics_set_text_widths() {
+    // Half an em per character without metrics
+    assert_eq!(
+        second_cell_x(type1_font("ABCDEF+AcmeWide,Bold"), None),
+        72.0 + 42.0 + 72.0
+    );
+
+    let metrics =
+        load_metrics_json(r#"{ "missing_width": 1000, "widths": { "R": 500 } }"#.as_bytes())
+            .unwrap();
+    assert_eq!(metrics.ascent, FontMetrics::default().ascent);
+    register_metrics("AcmeWide-Bold", metrics);
+    // "R" at half an em and six characters at a full em
+    assert_eq!(
+        second_cell_x(type1_font("ABCDEF+AcmeWide,Bold"), None),
+        72.0 + 78.0 + 72.0
+    );
+    // Other faces of the family aren't covered
+    assert_eq!(
+        second_cell_x(type1_font("AcmeWide"), None),
+        72.0 + 42.0 + 72.0
+    );
+}
+
+#[test]
+fn test_load_afm() {
+    let path = std::env::temp_dir().join(format!("delver-{}-acme.afm", std::process::id()));
+    std::fs::write(
+        &path,
+        "StartFontMetrics 4.1
+FontName AcmeNarrow
+Ascender 700
+Descender -200
+StartCharMetrics 3
+C 32 ; WX 250 ; N space ; B 0 0 0 0 ;
+C 65 ; WX 600 ; N A ; B 10 0 590 700 ;
+C -1 ; WX 900 ; N ffi ; B 0 0 880 700 ;
+EndCharMetrics
+EndFontMetrics
+",
+    )
+    .unwrap();
+    let metrics = load_afm(&path).unwrap();
+    assert_eq!((metrics.ascent, metrics.descent), (700.0, -200.0));
+    assert_eq!(metrics.widths.len(), 2);
+    assert_eq!(metrics.width(' '), 250.0);
+    assert_eq!(metrics.width('A'), 600.0);
+    assert_eq!(metrics.width('B'), metrics.missing_width);
+
+    std::fs::write(&path, "StartFontMetrics 4.1\nEndFontMetrics\n").unwrap();
+    assert!(load_afm(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_width_array_sets_text_widths() {
+    let mut font = type1_font("ABCDEF+AcmeMono");
+    font.set("FirstChar", 82);
+    font.set("Widths", vec![1000.into()]);
+    let descriptor = dictionary! { "Type" => "FontDescriptor", "MissingWidth" => 250 };
+    // "R" at a full em and six characters at a quarter
+    assert_eq!(
+        second_cell_x(font.clone(), Some(descriptor.clone())),
+        72.0 + 30.0 + 72.0
+    );
+
+    // The font's own widths win over registered metrics, which only fill in
+    // for fonts without them
+    register_metrics(
+        "AcmeMono",
+        FontMetrics {
+            missing_width: 1000.0,
+            ..Default::default()
+        },
+    );
+    assert_eq!(second_cell_x(font, Some(descriptor)), 72.0 + 30.0 + 72.0);
+    assert_eq!(
+        second_cell_x(type1_font("ABCDEF+AcmeMono"), None),
+        72.0 + 84.0 + 72.0
+    );
+}
+
+/// A TrueType program with a glyph per advance, in a 1000 unit em, and a Mac
+/// Roman cmap from single-byte codes to glyph ids.
+#[cfg(feature = "font-parsing")]
+fn truetype_program(advances: &[u16], cmap: &[(u8, u8)]) -> Vec<u8> {
+    let glyphs = advances.len() as u16;
+    let mut head = vec![0u8; 54];
+    head[0..4].copy_from_slice(&0x0001_0000u32.to_be_bytes());
+    head[12..16].copy_from_slice(&0x5F0F_3CF5u32.to_be_bytes());
+    head[18..20].copy_from_slice(&1000u16.to_be_bytes());
+    let mut hhea = vec![0u8; 36];
+    hhea[0..4].copy_from_slice(&0x0001_0000u32.to_be_bytes());
+    hhea[4..6].copy_from_slice(&800i16.to_be_bytes());
+    hhea[6..8].copy_from_slice(&(-200i16).to_be_bytes());
+    hhea[34..36].copy_from_slice(&glyphs.to_be_bytes());
+    let mut maxp = 0x0000_5000u32.to_be_bytes().to_vec();
+    maxp.extend(glyphs.to_be_bytes());
+    let hmtx: Vec<u8> = advances
+        .iter()
+        .flat_map(|advance| [advance.to_be_bytes(), [0, 0]].concat())
+        .collect();
+    // Version 0 with one (1, 0) format 0 subtable
+    let mut cmap_table = vec![0, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 12];
+    cmap_table.extend([0, 0, 1, 6, 0, 0]);
+    let mut glyph_ids = [0u8; 256];
+    for &(code, glyph) in cmap {
+        glyph_ids[code as usize] = glyph;
+    }
+    cmap_table.extend(glyph_ids);
+
+    // Table records are sorted by tag
+    let tables = [
+        (b"cmap", cmap_table),
+        (b"head", head),
+        (b"hhea", hhea),
+        (b"hmtx", hmtx),
+        (b"maxp", maxp),
+    ];
+    let mut program = 0x0001_0000u32.to_be_bytes().to_vec();
+    program.extend((tables.len() as u16).to_be_bytes());
+    program.extend([0u8; 6]);
+    let mut offset = 12 + 16 * tables.len();
+    let mut data = Vec::new();
+    for (tag, table) in &tables {
+        program.extend(*tag);
+        program.extend([0u8; 4]);
+        program.extend((offset as u32).to_be_bytes());
+        program.extend((table.len() as u32).to_be_bytes());
+        data.extend(table);
+        while data.len() % 4 != 0 {
+            data.push(0);
+        }
+        offset = 12 + 16 * tables.len() + data.len();
+    }
+    program.extend(data);
+    program
+}
+
+#[cfg(feature = "font-parsing")]
+#[test]
+fn test_embedded_truetype_sets_text_widths() {
+    let mut doc = Document::with_version("1.5");
+    // .notdef, then "R" a full em wide and the rest of "Revenue" a quarter
+    let program = truetype_program(
+        &[500, 1000, 250],
+        &[(b'R', 1), (b'e', 2), (b'v', 2), (b'n', 2), (b'u', 2)],
+    );
+    let program_id = doc.add_object(lopdf::Stream::new(dictionary! {}, program));
+    let descriptor = dictionary! {
+        "Type" => "FontDescriptor",
+        "FontName" => "ABCDEF+AcmeSans",
+        "FontFile2" => program_id,
+    };
+    // A subset without a Widths array
+    let font = dictionary! {
+        "Type" => "Font",
+        "Subtype" => "TrueType",
+        "BaseFont" => "ABCDEF+AcmeSans",
+    };
+    assert_eq!(
+        second_cell_x_in(doc, font, Some(descriptor)),
+        72.0 + 30.0 + 72.0
+    );
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::element;
+use delver::layout::{extract_section_content, find_headers_footers, strip_headers_footers};
+use delver::parse::{PageGeometry, TextElement, DEFAULT_PAGE_GEOMETRY};
+
+/// Ten pages with a running header, a page number footer and two lines of body.
+fn filing() -> Vec<TextElement> {
+    let mut elements = Vec::new();
+    for page in 1..=10 {
+        elements.push(element(
+            "Acme Corp \u{2014} Form 10-K",
+            page,
+            "Helvetica",
+            9.0,
+            (72.0, 770.0),
+        ));
+        if page == 2 {
+            elements.push(element(
+                "Item 1A. Risk Factors",
+                page,
+                "Helvetica",
+                14.0,
+                (72.0, 720.0),
+            ));
+        }
+        elements.push(element(
+            &format!("Body text for page {page}."),
+            page,
+            "Helvetica",
+            10.0,
+            (72.0, 660.0),
+        ));
+        elements.push(element(
+            "The year was 2015.",
+            page,
+            "Helvetica",
+            10.0,
+            (72.0, 400.0),
+        ));
+        elements.push(element(
+            &format!("{page}"),
+            page,
+            "Helvetica",
+            9.0,
+            (72.0, 30.0),
+        ));
+    }
+    elements
+}
+
+#[test]
+fn test_find_headers_footers() {
+    let elements = filing();
+    let flagged: Vec<&str> = elements
+        .iter()
+        .zip(find_headers_footers(&elements, &BTreeMap::new()))
+        .filter(|(_, flag)| *flag)
+        .map(|(mi, _)| mi.text.as_str())
+        .collect();
+
+    assert_eq!(flagged.len(), 20);
+    assert!(flagged
+        .iter()
+        .all(|text| text.starts_with("Acme") || text.parse::<u32>().is_ok()));
+}
+
+#[test]
+fn test_section_without_headers_footers() {
+    let elements = strip_headers_footers(&filing(), &BTreeMap::new());
+    let start = elements
+        .iter()
+        .find(|mi| mi.text == "Item 1A. Risk Factors")
+        .unwrap();
+    let text = extract_section_content(&elements, start);
+
+    assert!(text.starts_with("Item 1A. Risk Factors Body text for page 2."));
+    assert!(text.contains("Body text for page 10."));
+    assert!(!text.contains("Acme Corp"));
+    assert!(!text.contains(" 3 "));
+}
+
+#[test]
+fn test_short_documents_have_no_headers_footers() {
+    let elements: Vec<TextElement> = filing()
+        .into_iter()
+        .filter(|mi| mi.page_number <= 2)
+        .collect();
+    assert!(find_headers_footers(&elements, &BTreeMap::new())
+        .iter()
+        .all(|flag| !flag));
+}
+
+#[test]
+fn test_header_band_follows_page_size() {
+    // Text only fills the middle of each page, with a stamp repeated at its top
+    let elements: Vec<TextElement> = (1..=5)
+        .flat_map(|page| {
+            [
+                element("Draft", page, "Helvetica", 9.0, (72.0, 580.0)),
+                element(
+                    &format!("Body text for page {page}."),
+                    page,
+                    "Helvetica",
+                    10.0,
+                    (72.0, 300.0),
+                ),
+            ]
+        })
+        .collect();
+    assert!(find_headers_footers(&elements, &BTreeMap::new())
+        .iter()
+        .all(|flag| !flag));
+
+    // On a landscape page 580pt is near the top
+    let landscape = PageGeometry {
+        width: 792.0,
+        height: 612.0,
+        ..DEFAULT_PAGE_GEOMETRY
+    };
+    let pages: BTreeMap<u32, PageGeometry> = (1..=5).map(|page| (page, landscape)).collect();
+    let flags = find_headers_footers(&elements, &pages);
+    assert_eq!(flags, [true, false].repeat(5));
+}
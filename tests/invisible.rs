@@ -0,0 +1,68 @@
+mod common;
+
+use common::build_pdf;
+use delver::layout::{dedupe_text_layers, INVISIBLE_RENDER_MODE};
+use delver::parse::get_pdf_text;
+use lopdf::content::Operation;
+use lopdf::Object;
+
+/// A text object at (72, 700) drawn with render mode `mode`.
+fn text_with_mode(mode: i64, text: &str) -> Vec<Operation> {
+    vec![
+        Operation::new("BT", vec![]),
+        Operation::new("Tr", vec![mode.into()]),
+        Operation::new("Tf", vec!["F1".into(), 12.into()]),
+        Operation::new("Td", vec![72.into(), 700.into()]),
+        Operation::new("Tj", vec![Object::string_literal(text)]),
+        Operation::new("ET", vec![]),
+    ]
+}
+
+fn texts(doc: &lopdf::Document, include_invisible: bool) -> Vec<(String, u8)> {
+    let elements = get_pdf_text(doc).unwrap();
+    dedupe_text_layers(&elements, include_invisible)
+        .into_iter()
+        .map(|mi| (mi.text, mi.render_mode))
+        .collect()
+}
+
+#[test]
+fn test_invisible_duplicate_is_dropped() {
+    let mut page = text_with_mode(0, "Total revenue");
+    page.extend(text_with_mode(3, "Total revenue"));
+    page.extend(text_with_mode(3, "Net income"));
+    let doc = build_pdf(vec![page]);
+
+    assert_eq!(
+        texts(&doc, true),
+        vec![
+            ("Total revenue".to_string(), 0),
+            ("Net income".to_string(), INVISIBLE_RENDER_MODE)
+        ]
+    );
+    assert_eq!(texts(&doc, false), vec![("Total revenue".to_string(), 0)]);
+}
+
+#[test]
+fn test_ocr_only_layer_is_kept() {
+    // A scanned page whose only text is the invisible OCR layer
+    let doc = build_pdf(vec![text_with_mode(3, "Scanned annual report")]);
+    assert_eq!(
+        texts(&doc, true),
+        vec![("Scanned annual report".to_string(), INVISIBLE_RENDER_MODE)]
+    );
+}
+
+#[test]
+fn test_render_mode_carries_across_text_objects() {
+    let mut page = text_with_mode(3, "First");
+    page.extend(common::text_ops(12, 72, 680, "Second"));
+    let doc = build_pdf(vec![page]);
+
+    let modes: Vec<u8> = get_pdf_text(&doc)
+        .unwrap()
+        .iter()
+        .map(|mi| mi.render_mode)
+        .collect();
+    assert_eq!(modes, vec![3, 3]);
+}
@@ -1,29 +1,105 @@
-use lopdf::Document;
-use std::path::Path;
+mod common;
 
-use crate::{extract_text_fragments, identify_headings};
+use common::element;
+use delver::layout::{detect_headings, HeadingConfig};
+use delver::parse::{get_pdf_text, load_pdf, OutlineEntry};
 
 #[test]
 fn test_detect_headings() {
-    // Create the test PDF
-    assert!(create_test_pdf().is_ok());
+    let doc = load_pdf("tests/example.pdf").unwrap();
+    let text_elements = get_pdf_text(&doc).unwrap();
 
-    // Load the PDF document
-    let doc = Document::load("tests/example.pdf").unwrap();
-
-    // Extract text fragments
-    let fragments = extract_text_fragments(&doc);
-
-    // Identify headings
-    let nodes = identify_headings(&fragments);
+    let headings = detect_headings(&text_elements, &[], &HeadingConfig::default());
 
     // Check for expected headings
-    let expected_headings = vec!["Hello World!", "Subheading 1", "Subheading 2"];
-    let detected_headings: Vec<&str> = nodes
+    let expected_headings = vec![
+        ("Hello World!", 1),
+        ("Subheading 1", 2),
+        ("Subheading 2", 2),
+    ];
+    let detected_headings: Vec<(&str, usize)> = headings
         .iter()
-        .filter(|node| node.is_heading)
-        .map(|node| node.text.as_str())
+        .map(|heading| (heading.text.as_str(), heading.level))
         .collect();
 
     assert_eq!(expected_headings, detected_headings);
 }
+
+/// Everything is set at 10pt, so only weight, spacing, numbering and bookmarks
+/// tell the headings apart.
+#[test]
+fn test_detect_same_size_headings() {
+    let text_elements = vec![
+        element(
+            "Item 7. Management's Discussion",
+            1,
+            "Times-Bold",
+            10.0,
+            (72.0, 700.0),
+        ),
+        element(
+            "The year in review and the outlook.",
+            1,
+            "Times-Roman",
+            10.0,
+            (72.0, 686.0),
+        ),
+        element(
+            "1. Revenue grew in every segment.",
+            1,
+            "Times-Roman",
+            10.0,
+            (72.0, 674.0),
+        ),
+        element("Liquidity", 1, "Times-Bold", 10.0, (72.0, 640.0)),
+        element("Cash held steady.", 1, "Times-Roman", 10.0, (72.0, 626.0)),
+        element(
+            "Note: amounts in millions.",
+            1,
+            "Times-Bold",
+            10.0,
+            (72.0, 614.0),
+        ),
+        element(
+            "Critical Accounting Estimates",
+            1,
+            "Times-Roman",
+            10.0,
+            (72.0, 580.0),
+        ),
+        element(
+            "Estimates are reviewed each quarter.",
+            1,
+            "Times-Roman",
+            10.0,
+            (72.0, 566.0),
+        ),
+    ];
+    let outlines = vec![OutlineEntry {
+        title: "Critical Accounting Estimates".to_string(),
+        page: 1,
+        y: Some(590.0),
+        depth: 1,
+    }];
+
+    let headings = detect_headings(&text_elements, &outlines, &HeadingConfig::default());
+    let detected: Vec<&str> = headings.iter().map(|h| h.text.as_str()).collect();
+    assert_eq!(
+        detected,
+        vec![
+            "Item 7. Management's Discussion",
+            "Liquidity",
+            "Critical Accounting Estimates",
+        ]
+    );
+    assert!(headings.iter().all(|h| h.level == 1));
+
+    let loose = HeadingConfig {
+        min_score: 0.5,
+        ..Default::default()
+    };
+    let detected = detect_headings(&text_elements, &outlines, &loose);
+    assert!(detected
+        .iter()
+        .any(|h| h.text == "1. Revenue grew in every segment."));
+}
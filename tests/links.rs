@@ -0,0 +1,116 @@
+mod common;
+
+use common::{build_pdf, save_temp_pdf, text_ops};
+use delver::layout::{
+    perform_line_matching, reference_counts, select_best_match, select_best_match_with_references,
+    TocPolicy,
+};
+use delver::parse::{collect_link_targets, get_pdf_text, load_pdf, LinkTarget};
+use lopdf::{dictionary, Document, Object, ObjectId};
+
+fn link_to(doc: &mut Document, destination: Object) -> ObjectId {
+    doc.add_object(dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Link",
+        "Rect" => vec![72.into(), 690.into(), 200.into(), 710.into()],
+        "Dest" => destination,
+    })
+}
+
+/// "Legal Proceedings" appears as a large call-out on page 1 and as the real
+/// heading on page 3, which three links on pages 1 and 2 point at.
+fn linked_document() -> Document {
+    let mut page_one = text_ops(18, 72, 100, "Legal Proceedings");
+    page_one.extend(text_ops(10, 72, 700, "See Legal Proceedings."));
+    let mut page_three = text_ops(12, 72, 700, "Legal Proceedings");
+    page_three.extend(text_ops(10, 72, 680, "We are party to several suits."));
+    let mut doc = build_pdf(vec![
+        page_one,
+        text_ops(10, 72, 700, "As discussed under Legal Proceedings."),
+        page_three,
+    ]);
+
+    let pages = doc.get_pages();
+    let heading = || -> Object {
+        vec![
+            pages[&3].into(),
+            "XYZ".into(),
+            0.into(),
+            712.into(),
+            Object::Null,
+        ]
+        .into()
+    };
+    let first = link_to(&mut doc, heading());
+    let second = link_to(&mut doc, heading());
+    let third = doc.add_object(dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Link",
+        "Rect" => vec![72.into(), 690.into(), 200.into(), 710.into()],
+        "A" => dictionary! { "S" => "GoTo", "D" => heading() },
+    });
+    let external = doc.add_object(dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Link",
+        "Rect" => vec![72.into(), 690.into(), 200.into(), 710.into()],
+        "A" => dictionary! {
+            "S" => "URI",
+            "URI" => Object::string_literal("https://example.com"),
+        },
+    });
+    for (page, annots) in [(1, vec![first, external]), (2, vec![second, third])] {
+        let annots: Vec<Object> = annots.into_iter().map(Object::from).collect();
+        doc.get_dictionary_mut(pages[&page])
+            .unwrap()
+            .set("Annots", annots);
+    }
+
+    let path = save_temp_pdf(&mut doc, "links.pdf");
+    let doc = load_pdf(&path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    doc
+}
+
+#[test]
+fn test_collect_link_targets() {
+    let doc = linked_document();
+    let target = LinkTarget {
+        page: 3,
+        x: Some(0.0),
+        y: Some(712.0),
+    };
+    assert_eq!(collect_link_targets(&doc), vec![target; 3]);
+}
+
+#[test]
+fn test_reference_counts() {
+    let doc = linked_document();
+    let text_elements = get_pdf_text(&doc).unwrap();
+    let counts = reference_counts(&text_elements, &collect_link_targets(&doc));
+
+    let heading = text_elements
+        .iter()
+        .position(|te| te.page_number == 3 && te.text == "Legal Proceedings")
+        .unwrap();
+    assert_eq!(counts[heading], 3);
+    assert_eq!(counts.iter().sum::<usize>(), 3);
+}
+
+#[test]
+fn test_referenced_heading_wins() {
+    let doc = linked_document();
+    let text_elements = get_pdf_text(&doc).unwrap();
+    let matched = perform_line_matching(&text_elements, "Legal Proceedings");
+    assert_eq!(select_best_match(matched.clone()).unwrap().page_number, 1);
+
+    let best = select_best_match_with_references(
+        matched,
+        &text_elements,
+        &Default::default(),
+        TocPolicy::Skip,
+        &[],
+        &collect_link_targets(&doc),
+    )
+    .unwrap();
+    assert_eq!(best.page_number, 3);
+}
@@ -0,0 +1,194 @@
+mod common;
+
+use common::element;
+use delver::layout::{
+    group_text_into_lines, group_text_into_lines_with_threshold, match_span, normalize_text,
+    perform_line_matching, perform_line_matching_with_options, perform_line_regex_matching,
+    perform_matching, perform_matching_with_options, perform_regex_matching, JoinThreshold,
+    MatchOptions, DEFAULT_LINE_JOIN_THRESHOLD,
+};
+use delver::parse::TextElement;
+use regex::Regex;
+
+/// A heading emitted as three runs, followed by body text on the next line.
+fn split_heading() -> Vec<TextElement> {
+    vec![
+        element("Management's", 18, "F1", 12.0, (72.0, 700.0)),
+        element("Discussion and ", 18, "F1", 12.0, (160.0, 700.0)),
+        element("Analysis", 18, "F1", 12.0, (290.0, 701.5)),
+        element("Our revenue grew.", 18, "F1", 12.0, (72.0, 680.0)),
+    ]
+}
+
+fn item_headings() -> Vec<TextElement> {
+    vec![
+        element(
+            "Item 7A. Quantitative and Qualitative Disclosures",
+            2,
+            "F1",
+            12.0,
+            (72.0, 700.0),
+        ),
+        element(
+            "Item 7. Management's Discussion and Analysis",
+            18,
+            "F1",
+            12.0,
+            (72.0, 700.0),
+        ),
+        element(
+            "See Item 7. for a discussion of results",
+            22,
+            "F1",
+            12.0,
+            (72.0, 700.0),
+        ),
+    ]
+}
+
+#[test]
+fn test_regex_matching_anchors_item_7() {
+    let pattern = Regex::new(r"^Item\s+7\.").unwrap();
+    let matches = perform_regex_matching(item_headings(), &pattern);
+
+    let pages: Vec<u32> = matches.iter().map(|m| m.page_number).collect();
+    assert_eq!(pages, vec![18]);
+}
+
+#[test]
+fn test_line_regex_matching_spans_runs() {
+    let pattern = Regex::new(r"^Management's Discussion and Analysis$").unwrap();
+    let matches = perform_line_regex_matching(&split_heading(), &pattern, &MatchOptions::default());
+
+    let texts: Vec<&str> = matches.iter().map(|m| m.text.as_str()).collect();
+    assert_eq!(texts, vec!["Management's"]);
+    assert!(perform_regex_matching(split_heading(), &pattern).is_empty());
+}
+
+#[test]
+fn test_substring_matching_is_unanchored() {
+    let matches = perform_matching(item_headings(), "Item 7.");
+
+    let pages: Vec<u32> = matches.iter().map(|m| m.page_number).collect();
+    assert_eq!(pages, vec![18, 22]);
+}
+
+#[test]
+fn test_group_text_into_lines() {
+    let lines = group_text_into_lines(&split_heading(), DEFAULT_LINE_JOIN_THRESHOLD);
+
+    let text: Vec<&str> = lines.iter().map(|l| l.text.as_str()).collect();
+    assert_eq!(
+        text,
+        vec!["Management's Discussion and Analysis", "Our revenue grew."]
+    );
+    assert_eq!(lines[0].elements, vec![0, 1, 2]);
+    assert_eq!(lines[1].elements, vec![3]);
+}
+
+#[test]
+fn test_line_matching_finds_split_heading() {
+    let elements = split_heading();
+    let search = "Management's Discussion and Analysis";
+    assert!(perform_matching(elements.clone(), search).is_empty());
+
+    let matches = perform_line_matching(&elements, search);
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].text, "Management's");
+    assert_eq!(matches[0].position, (72.0, 700.0));
+}
+
+fn lenient() -> MatchOptions {
+    MatchOptions {
+        ignore_case: true,
+        normalize_whitespace: true,
+        strip_soft_hyphens: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_normalize_text() {
+    assert_eq!(
+        normalize_text(" ITEM\u{00A0}7.  MANAGE\u{00AD}MENT\u{2009}\n", &lenient()),
+        "item 7. management"
+    );
+    assert_eq!(
+        normalize_text(" ITEM\u{00A0}7. ", &MatchOptions::default()),
+        " ITEM\u{00A0}7. "
+    );
+}
+
+#[test]
+fn test_all_caps_heading_matches_mixed_case_pattern() {
+    let elements = vec![element(
+        "ITEM 7.  MANAGEMENT'S DISCUSSION\u{00A0}AND ANALYSIS",
+        18,
+        "F1",
+        12.0,
+        (72.0, 700.0),
+    )];
+    let search = "Item 7. Management's Discussion and Analysis";
+
+    assert!(perform_matching(elements.clone(), search).is_empty());
+    assert_eq!(
+        perform_matching_with_options(elements, search, &lenient()).len(),
+        1
+    );
+}
+
+#[test]
+fn test_line_matching_with_options() {
+    let elements = split_heading();
+    let search = "MANAGEMENT'S DISCUSSION AND ANALYSIS";
+
+    assert!(perform_line_matching(&elements, search).is_empty());
+    let matches = perform_line_matching_with_options(&elements, search, &lenient());
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].text, "Management's");
+}
+
+#[test]
+fn test_join_threshold_from_str() {
+    assert_eq!("5".parse(), Ok(JoinThreshold::Points(5.0)));
+    assert_eq!(" 2.5pt".parse(), Ok(JoinThreshold::Points(2.5)));
+    assert_eq!("1.2em".parse(), Ok(JoinThreshold::Em(1.2)));
+    assert!("wide".parse::<JoinThreshold>().is_err());
+    assert_eq!(JoinThreshold::Em(1.2).resolve(10.0), 12.0);
+}
+
+#[test]
+fn test_tight_and_loose_line_thresholds() {
+    // Superscript footnote marker 3pt above the baseline, then a line 14pt below
+    let elements = vec![
+        element("Revenue", 5, "F1", 12.0, (72.0, 700.0)),
+        element("1", 5, "F1", 12.0, (110.0, 703.0)),
+        element("Net income", 5, "F1", 12.0, (72.0, 686.0)),
+    ];
+    let count = |threshold: &str| {
+        group_text_into_lines_with_threshold(&elements, threshold.parse().unwrap()).len()
+    };
+
+    assert_eq!(count("1pt"), 3);
+    assert_eq!(count("5"), 2);
+    assert_eq!(count("20"), 1);
+    // element_at uses 12pt text, so 0.5em is 6pt
+    assert_eq!(count("0.5em"), 2);
+    assert_eq!(count("0.2em"), 3);
+}
+
+#[test]
+fn test_match_span_is_in_original_offsets() {
+    let text = "ITEM\u{00A0}7.  MANAGE\u{00AD}MENT'S Discussion";
+    let span = match_span(text, "item 7. management's", &lenient()).unwrap();
+    assert_eq!(&text[span], "ITEM\u{00A0}7.  MANAGE\u{00AD}MENT'S");
+
+    assert_eq!(
+        match_span("Item 7. Overview", "Overview", &MatchOptions::default()),
+        Some(8..16)
+    );
+    assert_eq!(
+        match_span("Item 7. Overview", "overview", &MatchOptions::default()),
+        None
+    );
+}
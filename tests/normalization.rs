@@ -0,0 +1,104 @@
+mod common;
+
+use common::{add_courier_font, finish_pdf};
+use delver::layout::perform_line_matching;
+use delver::parse::{
+    get_pdf_text, get_pdf_text_with_options, load_pdf_bytes, TextNormalization, TextOptions,
+};
+use lopdf::content::Operation;
+use lopdf::{dictionary, Document, Object, Stream};
+
+/// A two-byte code map taking printable ASCII to itself, and codes 1 to 4 to
+/// "ﬁ", "ﬄ", a right single quote and a zero-width space.
+const TO_UNICODE: &str = "/CIDInit /ProcSet findresource begin
+12 dict begin
+begincmap
+/CMapName /Ligatures def
+/CMapType 2 def
+1 begincodespacerange
+<0000> <FFFF>
+endcodespacerange
+1 beginbfrange
+<0020> <007E> <0020>
+endbfrange
+4 beginbfchar
+<0001> <FB01>
+<0002> <FB04>
+<0003> <2019>
+<0004> <200B>
+endbfchar
+endcmap
+CMapName currentdict /CMap defineresource pop
+end
+end";
+
+/// One page showing `text`, one code per byte, in a font whose ToUnicode map
+/// has ligatures.
+fn ligature_document(text: &[u8]) -> Document {
+    let codes: Vec<u8> = text.iter().flat_map(|byte| [0, *byte]).collect();
+    let mut doc = Document::with_version("1.5");
+    let font_id = add_courier_font(&mut doc);
+    let cmap_id = doc.add_object(Stream::new(dictionary! {}, TO_UNICODE.as_bytes().to_vec()));
+    doc.get_dictionary_mut(font_id)
+        .unwrap()
+        .set("ToUnicode", cmap_id);
+    let resources = dictionary! {
+        "Font" => dictionary! { "F1" => font_id },
+    };
+    let page = vec![
+        Operation::new("BT", vec![]),
+        Operation::new("Tf", vec!["F1".into(), 12.into()]),
+        Operation::new("Td", vec![72.into(), 700.into()]),
+        Operation::new("Tj", vec![Object::string_literal(codes)]),
+        Operation::new("ET", vec![]),
+    ];
+    let mut doc = finish_pdf(doc, resources, vec![page]);
+    let mut bytes = Vec::new();
+    doc.save_to(&mut bytes).unwrap();
+    load_pdf_bytes(&bytes).unwrap()
+}
+
+#[test]
+fn test_ligatures_are_expanded() {
+    let doc = ligature_document(b"\x01nancial ba\x02ed\x04 by the \x03report\x03");
+    let text = get_pdf_text(&doc).unwrap();
+    assert_eq!(
+        text[0].text,
+        "financial baffled by the \u{2019}report\u{2019}"
+    );
+    assert_eq!(
+        text[0].raw_text.as_deref(),
+        Some("\u{FB01}nancial ba\u{FB04}ed\u{200B} by the \u{2019}report\u{2019}")
+    );
+    assert_eq!(perform_line_matching(&text, "financial").len(), 1);
+
+    let options = TextOptions {
+        normalization: TextNormalization {
+            punctuation: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let text = get_pdf_text_with_options(&doc, &options).unwrap();
+    assert_eq!(text[0].text, "financial baffled by the 'report'");
+}
+
+#[test]
+fn test_unchanged_text_keeps_no_raw_copy() {
+    let doc = ligature_document(b"plain text");
+    let text = get_pdf_text(&doc).unwrap();
+    assert_eq!(text[0].text, "plain text");
+    assert_eq!(text[0].raw_text, None);
+
+    let options = TextOptions {
+        normalization: TextNormalization {
+            ligatures: false,
+            punctuation: false,
+            zero_width: false,
+        },
+        ..Default::default()
+    };
+    let doc = ligature_document(b"\x01nd");
+    let text = get_pdf_text_with_options(&doc, &options).unwrap();
+    assert_eq!(text[0].text, "\u{FB01}nd");
+}
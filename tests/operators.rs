@@ -0,0 +1,427 @@
+mod common;
+
+use common::{add_courier_font, build_pdf, finish_pdf, text_ops};
+use delver::parse::{get_pdf_text, load_pdf_bytes, TextElement};
+use lopdf::content::Operation;
+use lopdf::{dictionary, Document, Object, Stream};
+
+/// Elements extracted from one text object at (72, 700) made of `operations`.
+fn text_object_elements(operations: Vec<Operation>) -> Vec<TextElement> {
+    let mut page = vec![
+        Operation::new("BT", vec![]),
+        Operation::new("Tf", vec!["F1".into(), 12.into()]),
+        Operation::new("Td", vec![72.into(), 700.into()]),
+    ];
+    page.extend(operations);
+    page.push(Operation::new("ET", vec![]));
+    let doc = build_pdf(vec![page]);
+    get_pdf_text(&doc).unwrap()
+}
+
+fn text_object(operations: Vec<Operation>) -> Vec<String> {
+    text_object_elements(operations)
+        .into_iter()
+        .map(|mi| mi.text)
+        .collect()
+}
+
+fn show_array(parts: Vec<Object>) -> Operation {
+    Operation::new("TJ", vec![Object::Array(parts)])
+}
+
+#[test]
+fn test_tj_word_gaps_become_spaces() {
+    let text = text_object(vec![show_array(vec![
+        Object::string_literal("The"),
+        (-300).into(),
+        Object::string_literal("quick"),
+        (-280).into(),
+        Object::string_literal("br"),
+        (-12).into(),
+        Object::string_literal("o"),
+        45.into(),
+        Object::string_literal("wn"),
+        Object::Real(-250.5),
+        Object::string_literal("fox"),
+    ])]);
+    assert_eq!(text, vec!["The quick brown fox"]);
+}
+
+#[test]
+fn test_tj_gap_after_space_adds_nothing() {
+    let text = text_object(vec![show_array(vec![
+        Object::string_literal("Net "),
+        (-400).into(),
+        Object::string_literal("income"),
+    ])]);
+    assert_eq!(text, vec!["Net income"]);
+}
+
+#[test]
+fn test_tj_column_jumps_split_cells() {
+    let elements = text_object_elements(vec![show_array(vec![
+        Object::string_literal("Revenue"),
+        (-6000).into(),
+        Object::string_literal("1,234"),
+        (-3000).into(),
+        Object::string_literal("5,678"),
+    ])]);
+    let cells: Vec<&str> = elements.iter().map(|mi| mi.text.as_str()).collect();
+    assert_eq!(cells, vec!["Revenue", "1,234", "5,678"]);
+    // 7 characters at half an em, then a 6 em jump, at 12pt
+    assert_eq!(elements[0].position, (72.0, 700.0));
+    assert_eq!(elements[1].position, (72.0 + 42.0 + 72.0, 700.0));
+    assert_eq!(elements[2].position, (186.0 + 30.0 + 36.0, 700.0));
+}
+
+#[test]
+fn test_td_column_jump_splits_cells() {
+    let elements = text_object_elements(vec![
+        Operation::new("Tj", vec![Object::string_literal("Net income")]),
+        Operation::new("Td", vec![200.into(), 0.into()]),
+        Operation::new("Tj", vec![Object::string_literal("812")]),
+        Operation::new("Td", vec![30.into(), 0.into()]),
+        Operation::new("Tj", vec![Object::string_literal("(4)")]),
+    ]);
+    let cells: Vec<(&str, (f32, f32))> = elements
+        .iter()
+        .map(|mi| (mi.text.as_str(), mi.position))
+        .collect();
+    // The second move lands right after "812", so it continues the cell
+    assert_eq!(
+        cells,
+        vec![("Net income", (72.0, 700.0)), ("812(4)", (302.0, 700.0))]
+    );
+}
+
+fn cell_row() -> Operation {
+    show_array(vec![
+        Object::string_literal("Revenue"),
+        (-6000).into(),
+        Object::string_literal("1,234"),
+    ])
+}
+
+#[test]
+fn test_tj_jump_follows_horizontal_scaling() {
+    let elements = text_object_elements(vec![Operation::new("Tz", vec![50.into()]), cell_row()]);
+    // Both the text and the jump are drawn at half width
+    assert_eq!(elements[1].position, (72.0 + 21.0 + 36.0, 700.0));
+}
+
+#[test]
+fn test_tj_jump_follows_rotated_text_matrix() {
+    let elements = text_object_elements(vec![
+        Operation::new(
+            "Tm",
+            vec![
+                0.into(),
+                1.into(),
+                (-1).into(),
+                0.into(),
+                300.into(),
+                100.into(),
+            ],
+        ),
+        cell_row(),
+    ]);
+    let cells: Vec<(&str, (f32, f32))> = elements
+        .iter()
+        .map(|mi| (mi.text.as_str(), mi.position))
+        .collect();
+    // Reading upwards, the second cell is above the first rather than beside it
+    assert_eq!(
+        cells,
+        vec![("Revenue", (300.0, 100.0)), ("1,234", (300.0, 214.0))]
+    );
+}
+
+#[test]
+fn test_quote_operators_move_to_next_line() {
+    let elements = text_object_elements(vec![
+        Operation::new("TL", vec![14.into()]),
+        Operation::new("Tj", vec![Object::string_literal("Line one")]),
+        Operation::new("'", vec![Object::string_literal("Line two")]),
+        Operation::new(
+            "\"",
+            vec![
+                2.into(),
+                Object::Real(0.5),
+                Object::string_literal("Line three"),
+            ],
+        ),
+        Operation::new("T*", vec![]),
+        Operation::new("Tj", vec![Object::string_literal("Line four")]),
+    ]);
+    let lines: Vec<(&str, f32)> = elements
+        .iter()
+        .map(|mi| (mi.text.as_str(), mi.position.1))
+        .collect();
+    assert_eq!(
+        lines,
+        vec![
+            ("Line one", 700.0),
+            ("Line two", 686.0),
+            ("Line three", 672.0),
+            ("Line four", 658.0),
+        ]
+    );
+}
+
+/// A cell followed by a 6em jump, so the next cell's x shows how wide the
+/// first was estimated to be.
+fn spaced_row(spacing: Operation, first_cell: Object) -> Vec<Operation> {
+    vec![
+        spacing,
+        show_array(vec![
+            first_cell,
+            (-6000).into(),
+            Object::string_literal("9"),
+        ]),
+    ]
+}
+
+#[test]
+fn test_negative_char_spacing_tightens_text() {
+    let elements = text_object_elements(spaced_row(
+        Operation::new("Tc", vec![(-1).into()]),
+        Object::string_literal("Revenue"),
+    ));
+    // 7 glyphs each a point closer
+    assert_eq!(elements[1].position, (72.0 + 42.0 - 7.0 + 72.0, 700.0));
+
+    let elements = text_object_elements(spaced_row(
+        Operation::new("Tc", vec![(-10).into()]),
+        Object::string_literal("Revenue"),
+    ));
+    assert_eq!(elements[1].position, (72.0 + 72.0, 700.0));
+}
+
+#[test]
+fn test_word_spacing_applies_to_single_byte_spaces() {
+    let elements = text_object_elements(spaced_row(
+        Operation::new("Tw", vec![10.into()]),
+        Object::string_literal("Net income"),
+    ));
+    assert_eq!(elements[1].position, (72.0 + 60.0 + 10.0 + 72.0, 700.0));
+}
+
+/// Two-byte codes mapping printable ASCII to itself.
+const TWO_BYTE_CMAP: &str = "/CIDInit /ProcSet findresource begin
+12 dict begin
+begincmap
+/CMapName /TwoByte def
+/CMapType 2 def
+1 begincodespacerange
+<0000> <FFFF>
+endcodespacerange
+1 beginbfrange
+<0020> <007E> <0020>
+endbfrange
+endcmap
+CMapName currentdict /CMap defineresource pop
+end
+end";
+
+#[test]
+fn test_word_spacing_skips_two_byte_spaces() {
+    let two_byte = |text: &[u8]| -> Object {
+        Object::string_literal(text.iter().flat_map(|b| [0, *b]).collect::<Vec<u8>>())
+    };
+    let mut doc = Document::with_version("1.5");
+    let font_id = add_courier_font(&mut doc);
+    let cmap_id = doc.add_object(Stream::new(
+        dictionary! {},
+        TWO_BYTE_CMAP.as_bytes().to_vec(),
+    ));
+    doc.get_dictionary_mut(font_id)
+        .unwrap()
+        .set("ToUnicode", cmap_id);
+    let resources = dictionary! {
+        "Font" => dictionary! { "F1" => font_id },
+    };
+    let mut page = vec![
+        Operation::new("BT", vec![]),
+        Operation::new("Tf", vec!["F1".into(), 12.into()]),
+        Operation::new("Td", vec![72.into(), 700.into()]),
+    ];
+    page.push(Operation::new("Tw", vec![10.into()]));
+    page.push(show_array(vec![
+        two_byte(b"Net income"),
+        (-6000).into(),
+        two_byte(b"9"),
+    ]));
+    page.push(Operation::new("ET", vec![]));
+    let mut doc = finish_pdf(doc, resources, vec![page]);
+    let mut bytes = Vec::new();
+    doc.save_to(&mut bytes).unwrap();
+
+    let elements = get_pdf_text(&load_pdf_bytes(&bytes).unwrap()).unwrap();
+    assert_eq!(elements[0].text, "Net income");
+    assert_eq!(elements[1].position, (72.0 + 60.0 + 72.0, 700.0));
+}
+
+#[test]
+fn test_matrix_scaled_text_reports_drawn_size() {
+    let elements = text_object_elements(vec![
+        Operation::new("Tf", vec!["F1".into(), 1.into()]),
+        Operation::new(
+            "Tm",
+            vec![
+                24.into(),
+                0.into(),
+                0.into(),
+                24.into(),
+                72.into(),
+                700.into(),
+            ],
+        ),
+        Operation::new("Tj", vec![Object::string_literal("Big")]),
+    ]);
+    assert_eq!(elements[0].text, "Big");
+    assert_eq!(elements[0].font_size, 24.0);
+}
+
+#[test]
+fn test_td_is_scaled_by_text_matrix() {
+    let elements = text_object_elements(vec![
+        Operation::new("Tf", vec!["F1".into(), 1.into()]),
+        Operation::new(
+            "Tm",
+            vec![
+                9.into(),
+                0.into(),
+                0.into(),
+                9.into(),
+                72.into(),
+                700.into(),
+            ],
+        ),
+        Operation::new("Tj", vec![Object::string_literal("Line one")]),
+        Operation::new("Td", vec![0.into(), (-1.2).into()]),
+        Operation::new("Tj", vec![Object::string_literal("Line two")]),
+        Operation::new("Td", vec![2.into(), (-1.2).into()]),
+        Operation::new("Tj", vec![Object::string_literal("Indented")]),
+    ]);
+    let lines: Vec<(&str, (f32, f32))> = elements
+        .iter()
+        .map(|mi| (mi.text.as_str(), mi.position))
+        .collect();
+    // 1.2 text space units at 9pt are 10.8pt
+    assert_eq!(
+        lines,
+        vec![
+            ("Line one", (72.0, 700.0)),
+            ("Line two", (72.0, 689.2)),
+            ("Indented", (90.0, 678.4)),
+        ]
+    );
+}
+
+#[test]
+fn test_cm_scaled_text_reports_drawn_size() {
+    let mut page = vec![
+        Operation::new("q", vec![]),
+        Operation::new(
+            "cm",
+            vec![2.into(), 0.into(), 0.into(), 2.into(), 0.into(), 0.into()],
+        ),
+    ];
+    page.extend(text_ops(12, 36, 350, "Heading"));
+    page.push(Operation::new("Q", vec![]));
+    page.extend(text_ops(12, 72, 600, "Body"));
+    let elements = get_pdf_text(&build_pdf(vec![page])).unwrap();
+
+    let sizes: Vec<(&str, f32, (f32, f32))> = elements
+        .iter()
+        .map(|mi| (mi.text.as_str(), mi.font_size, mi.position))
+        .collect();
+    assert_eq!(
+        sizes,
+        vec![
+            ("Heading", 24.0, (72.0, 700.0)),
+            ("Body", 12.0, (72.0, 600.0))
+        ]
+    );
+}
+
+#[test]
+fn test_font_carries_across_text_objects() {
+    let mut page = text_ops(14, 72, 700, "Heading");
+    page.extend([
+        Operation::new("BT", vec![]),
+        Operation::new("Td", vec![72.into(), 680.into()]),
+        Operation::new("Tj", vec![Object::string_literal("Body")]),
+        Operation::new("ET", vec![]),
+    ]);
+    let elements = get_pdf_text(&build_pdf(vec![page])).unwrap();
+
+    let fonts: Vec<(&str, Option<&str>, f32)> = elements
+        .iter()
+        .map(|mi| (mi.text.as_str(), mi.font_name.as_deref(), mi.font_size))
+        .collect();
+    assert_eq!(
+        fonts,
+        vec![
+            ("Heading", Some("Courier"), 14.0),
+            ("Body", Some("Courier"), 14.0)
+        ]
+    );
+}
+
+#[test]
+fn test_restoring_graphics_state_restores_font() {
+    let mut page = text_ops(12, 72, 700, "Before");
+    page.push(Operation::new("q", vec![]));
+    page.extend(text_ops(20, 72, 680, "Inside"));
+    page.push(Operation::new("Q", vec![]));
+    page.extend([
+        Operation::new("BT", vec![]),
+        Operation::new("Td", vec![72.into(), 660.into()]),
+        Operation::new("Tj", vec![Object::string_literal("After")]),
+        Operation::new("ET", vec![]),
+    ]);
+    let elements = get_pdf_text(&build_pdf(vec![page])).unwrap();
+
+    let sizes: Vec<(&str, f32)> = elements
+        .iter()
+        .map(|mi| (mi.text.as_str(), mi.font_size))
+        .collect();
+    assert_eq!(
+        sizes,
+        vec![("Before", 12.0), ("Inside", 20.0), ("After", 12.0)]
+    );
+}
+
+#[test]
+fn test_style_changes_split_runs() {
+    let elements = text_object_elements(vec![
+        Operation::new("Tj", vec![Object::string_literal("Revenue")]),
+        Operation::new("Ts", vec![5.into()]),
+        Operation::new("Tj", vec![Object::string_literal("1")]),
+        Operation::new("Ts", vec![0.into()]),
+        Operation::new("Tf", vec!["F1".into(), 8.into()]),
+        Operation::new("Tj", vec![Object::string_literal(" grew")]),
+        Operation::new("rg", vec![1.into(), 0.into(), 0.into()]),
+        Operation::new("Tj", vec![Object::string_literal(" sharply")]),
+    ]);
+    let runs: Vec<(&str, f32)> = elements
+        .iter()
+        .map(|mi| (mi.text.as_str(), mi.font_size))
+        .collect();
+    assert_eq!(
+        runs,
+        vec![
+            ("Revenue", 12.0),
+            ("1", 12.0),
+            (" grew", 8.0),
+            (" sharply", 8.0)
+        ]
+    );
+    assert_eq!(elements[2].color, Some((0, 0, 0)));
+    assert_eq!(elements[3].color, Some((255, 0, 0)));
+    // Each run starts after the one before it
+    assert!(elements
+        .windows(2)
+        .all(|w| w[0].position.0 < w[1].position.0));
+}
@@ -0,0 +1,255 @@
+mod common;
+
+use common::{build_pdf, save_temp_pdf, text_ops};
+use delver::layout::{
+    matching_outline_entries, perform_line_matching, select_best_match,
+    select_best_match_with_outlines, MatchOptions, TocPolicy, DEFAULT_TOC_PENALTY,
+};
+use delver::parse::{
+    get_named_destinations, get_outline_entries, get_pdf_text, load_pdf, pdf2toc, OutlineEntry,
+};
+use lopdf::{dictionary, Document, Object};
+
+/// Three pages where a large "Risk Factors" call-out on page 1 outranks the real
+/// heading on page 3, which is what the bookmarks point at.
+fn bookmarked_document() -> Document {
+    let mut page_one = text_ops(18, 72, 100, "Risk Factors");
+    page_one.extend(text_ops(10, 72, 700, "Summary of the year."));
+    let mut page_three = text_ops(12, 72, 700, "Risk Factors");
+    page_three.extend(text_ops(10, 72, 680, "Our business faces many risks."));
+    let mut doc = build_pdf(vec![
+        page_one,
+        text_ops(10, 72, 700, "Business overview."),
+        page_three,
+    ]);
+
+    let pages = doc.get_pages();
+    let outlines_id = doc.new_object_id();
+    let business_id = doc.new_object_id();
+    let risks_id = doc.new_object_id();
+    let market_id = doc.new_object_id();
+    doc.objects.insert(
+        business_id,
+        Object::Dictionary(dictionary! {
+            "Title" => Object::string_literal("Item 1. Business"),
+            "Parent" => outlines_id,
+            "Next" => risks_id,
+            "A" => dictionary! {
+                "S" => "GoTo",
+                "D" => vec![pages[&2].into(), "Fit".into()],
+            },
+        }),
+    );
+    doc.objects.insert(
+        risks_id,
+        Object::Dictionary(dictionary! {
+            "Title" => Object::string_literal("Item 1A. Risk Factors"),
+            "Parent" => outlines_id,
+            "Prev" => business_id,
+            "First" => market_id,
+            "Last" => market_id,
+            "Dest" => vec![pages[&3].into(), "XYZ".into(), 0.into(), 712.into(), Object::Null],
+        }),
+    );
+    doc.objects.insert(
+        market_id,
+        Object::Dictionary(dictionary! {
+            "Title" => Object::string_literal("Market risk"),
+            "Parent" => risks_id,
+            "Dest" => Object::Name(b"market".to_vec()),
+        }),
+    );
+    doc.objects.insert(
+        outlines_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Outlines",
+            "First" => business_id,
+            "Last" => risks_id,
+        }),
+    );
+
+    let catalog = doc.catalog_mut().unwrap();
+    catalog.set("Outlines", outlines_id);
+    catalog.set(
+        "Dests",
+        dictionary! {
+            "market" => vec![pages[&3].into(), "FitH".into(), 680.into()],
+        },
+    );
+    doc
+}
+
+fn load_bookmarked_document(name: &str) -> Document {
+    let path = save_temp_pdf(&mut bookmarked_document(), name);
+    let doc = load_pdf(&path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    doc
+}
+
+#[test]
+fn test_get_outline_entries() {
+    let doc = load_bookmarked_document("outline-entries.pdf");
+    let entry = |title: &str, page, y, depth| OutlineEntry {
+        title: title.to_string(),
+        page,
+        y,
+        depth,
+    };
+
+    assert_eq!(
+        get_outline_entries(&doc),
+        vec![
+            entry("Item 1. Business", 2, None, 0),
+            entry("Item 1A. Risk Factors", 3, Some(712.0), 0),
+            entry("Market risk", 3, Some(680.0), 1),
+        ]
+    );
+}
+
+#[test]
+fn test_bookmarks_anchor_ambiguous_headings() {
+    let doc = load_bookmarked_document("outline-anchor.pdf");
+    let text_elements = get_pdf_text(&doc).unwrap();
+    let matched = perform_line_matching(&text_elements, "Risk Factors");
+    assert_eq!(matched.len(), 2);
+
+    // Without bookmarks the large call-out on page 1 wins.
+    let best = select_best_match(matched.clone()).unwrap();
+    assert_eq!(best.page_number, 1);
+
+    let outlines = get_outline_entries(&doc);
+    let bookmarks = matching_outline_entries(&outlines, "Risk Factors", &MatchOptions::default());
+    assert_eq!(bookmarks.len(), 1);
+
+    let best = select_best_match_with_outlines(
+        matched,
+        &text_elements,
+        &Default::default(),
+        TocPolicy::Penalize(DEFAULT_TOC_PENALTY),
+        &bookmarks,
+    )
+    .unwrap();
+    assert_eq!(best.page_number, 3);
+    assert_eq!(best.position.1, 700.0);
+}
+
+#[test]
+fn test_bookmark_adds_candidate_for_reworded_heading() {
+    let doc = load_bookmarked_document("outline-reworded.pdf");
+    let text_elements = get_pdf_text(&doc).unwrap();
+    let search = "Item 1. Business";
+    let matched = perform_line_matching(&text_elements, search);
+    assert!(matched.is_empty());
+
+    let outlines = get_outline_entries(&doc);
+    let bookmarks = matching_outline_entries(&outlines, search, &MatchOptions::default());
+    let best = select_best_match_with_outlines(
+        matched,
+        &text_elements,
+        &Default::default(),
+        TocPolicy::Skip,
+        &bookmarks,
+    )
+    .unwrap();
+    assert_eq!(best.page_number, 2);
+    assert_eq!(best.text, "Business overview.");
+}
+
+/// Named destinations split across a two-leaf `/Names` tree, plus one in the legacy
+/// catalog `/Dests` dictionary.
+fn document_with_name_tree() -> Document {
+    let mut doc = build_pdf(vec![
+        text_ops(12, 72, 700, "Introduction"),
+        text_ops(12, 72, 700, "Risk Factors"),
+        text_ops(12, 72, 700, "Legal Proceedings"),
+    ]);
+    let pages = doc.get_pages();
+
+    let risks_id = doc.add_object(dictionary! {
+        "D" => vec![pages[&2].into(), "XYZ".into(), 0.into(), 712.into(), Object::Null],
+    });
+    let legal_id = doc.add_object(vec![pages[&3].into(), "Fit".into()]);
+    let first_leaf = doc.add_object(dictionary! {
+        "Limits" => vec![Object::string_literal("intro"), Object::string_literal("risks")],
+        "Names" => vec![
+            Object::string_literal("intro"),
+            vec![pages[&1].into(), "Fit".into()].into(),
+            Object::string_literal("risks"),
+            risks_id.into(),
+        ],
+    });
+    let second_leaf = doc.add_object(dictionary! {
+        "Limits" => vec![Object::string_literal("legal"), Object::string_literal("legal")],
+        "Names" => vec![Object::string_literal("legal"), legal_id.into()],
+    });
+    let tree_id = doc.add_object(dictionary! {
+        "Kids" => vec![first_leaf.into(), second_leaf.into()],
+    });
+
+    let catalog = doc.catalog_mut().unwrap();
+    catalog.set("Names", dictionary! { "Dests" => tree_id });
+    catalog.set(
+        "Dests",
+        dictionary! { "legacy" => vec![pages[&1].into(), "FitH".into(), 700.into()] },
+    );
+    doc
+}
+
+#[test]
+fn test_named_destinations_from_name_tree() {
+    let doc = document_with_name_tree();
+    let pages = doc.get_pages();
+    let named = get_named_destinations(&doc);
+
+    let expected: Vec<(&str, Object)> = vec![
+        (
+            "legacy",
+            vec![pages[&1].into(), "FitH".into(), 700.into()].into(),
+        ),
+        ("intro", vec![pages[&1].into(), "Fit".into()].into()),
+        (
+            "risks",
+            vec![
+                pages[&2].into(),
+                "XYZ".into(),
+                0.into(),
+                712.into(),
+                Object::Null,
+            ]
+            .into(),
+        ),
+        ("legal", vec![pages[&3].into(), "Fit".into()].into()),
+    ];
+    assert_eq!(named.len(), expected.len());
+    for (name, destination) in expected {
+        assert_eq!(named.get(name), Some(&destination), "destination {name}");
+    }
+}
+
+#[test]
+fn test_pdf2toc_writes_json() {
+    let path = save_temp_pdf(&mut bookmarked_document(), "toc.pdf");
+    let output = path.with_extension("json");
+
+    pdf2toc(&path, &output, false).unwrap();
+    let compact = std::fs::read_to_string(&output).unwrap();
+    pdf2toc(&path, &output, true).unwrap();
+    let pretty = std::fs::read_to_string(&output).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&output).unwrap();
+
+    assert!(!compact.contains('\n'));
+    assert!(pretty.contains('\n'));
+    let toc: serde_json::Value = serde_json::from_str(&pretty).unwrap();
+    assert_eq!(
+        toc,
+        serde_json::from_str::<serde_json::Value>(&compact).unwrap()
+    );
+    let titles: Vec<&str> = toc["toc"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["title"].as_str().unwrap())
+        .collect();
+    assert!(titles.contains(&"Item 1A. Risk Factors"));
+}
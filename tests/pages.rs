@@ -0,0 +1,161 @@
+mod common;
+
+use common::{build_pdf, element, text_ops};
+use delver::builder::{ElementBuilder, TemplateBuilder};
+use delver::dom::parse_template;
+use delver::layout::{
+    extract_section_content_with_options, extract_template_sections, perform_line_matching,
+    restrict_to_pages, select_best_match, PageRange, SectionOptions,
+};
+use delver::parse::{
+    get_pdf_text, get_pdf_text_with_options, PageSelection, TextElement, TextOptions,
+};
+use lopdf::dictionary;
+
+/// "Overview" heads a short summary on page 2 and the real section on page 40;
+/// the summary heading is set larger, so it wins on style alone.
+fn document() -> Vec<TextElement> {
+    vec![
+        element("Overview", 2, "Helvetica", 16.0, (72.0, 700.0)),
+        element("In brief.", 2, "Helvetica", 10.0, (72.0, 680.0)),
+        element("Overview", 40, "Helvetica", 14.0, (72.0, 700.0)),
+        element("The full discussion.", 40, "Helvetica", 10.0, (72.0, 680.0)),
+        element("More discussion.", 41, "Helvetica", 10.0, (72.0, 700.0)),
+    ]
+}
+
+#[test]
+fn test_page_start_selects_later_heading() {
+    let root =
+        parse_template(r#"Section(match="Overview", pageStart=30) { TextChunk() }"#).unwrap();
+    let range = PageRange::from_element(&root.elements[0]);
+    assert_eq!(
+        range,
+        PageRange {
+            start: Some(30),
+            end: None
+        }
+    );
+
+    let elements = document();
+    let matched = perform_line_matching(&elements, "Overview");
+    assert_eq!(select_best_match(matched.clone()).unwrap().page_number, 2);
+
+    let best = select_best_match(restrict_to_pages(matched, range)).unwrap();
+    assert_eq!(best.page_number, 40);
+}
+
+#[test]
+fn test_template_page_range_reaches_extraction() {
+    let mut summary = text_ops(16, 72, 700, "Overview");
+    summary.extend(text_ops(10, 72, 680, "In brief."));
+    let mut section = text_ops(14, 72, 700, "Overview");
+    section.extend(text_ops(10, 72, 680, "The full discussion."));
+    let doc = build_pdf(vec![
+        summary,
+        text_ops(10, 72, 700, "Filler."),
+        section,
+        text_ops(10, 72, 700, "More discussion."),
+    ]);
+    let elements = get_pdf_text(&doc).unwrap();
+
+    let extract = |template: &str| {
+        let root = parse_template(template).unwrap();
+        let sections = extract_template_sections(&doc, &elements, &root).unwrap();
+        (sections[0].page, sections[0].text.clone())
+    };
+    assert_eq!(extract(r#"Section(match="Overview")"#).0, 1);
+    assert_eq!(
+        extract(r#"Section(match="Overview", pageStart=3)"#),
+        (
+            3,
+            "Overview The full discussion. More discussion. ".to_string()
+        )
+    );
+    assert_eq!(
+        extract(r#"Section(match="Overview", pageStart=3) { TextChunk(pageEnd=3) }"#),
+        (3, "Overview The full discussion. ".to_string())
+    );
+}
+
+#[test]
+fn test_page_end_limits_section_body() {
+    let elements = document();
+    let options = SectionOptions {
+        pages: PageRange {
+            start: None,
+            end: Some(40),
+        },
+        ..Default::default()
+    };
+    let text = extract_section_content_with_options(&elements, &elements[2], &options);
+    assert_eq!(text, "Overview The full discussion. ");
+}
+
+#[test]
+fn test_page_range_clamps_past_end() {
+    let root = TemplateBuilder::new()
+        .element(
+            ElementBuilder::section("overview")
+                .match_pattern("Overview")
+                .page_start(50)
+                .page_end(90),
+        )
+        .build()
+        .unwrap();
+    let range = PageRange::from_element(&root.elements[0]).clamp(41);
+    assert_eq!(
+        range,
+        PageRange {
+            start: Some(41),
+            end: Some(41)
+        }
+    );
+    assert!(range.contains(41));
+    assert!(!range.contains(40));
+
+    let error = parse_template(r#"Section(match="Overview", pageStart=0)"#).unwrap_err();
+    assert!(error.message.contains("`pageStart` must be a page number"));
+}
+
+#[test]
+fn test_page_selection_parsing() {
+    let selection: PageSelection = "1-80, 120-130,200".parse().unwrap();
+    assert!(selection.contains(1) && selection.contains(80) && selection.contains(125));
+    assert!(selection.contains(200));
+    assert!(!selection.contains(81) && !selection.contains(199));
+    assert_eq!(selection.last(), 200);
+
+    for invalid in ["", "0-5", "9-3", "1-", "a"] {
+        assert!(invalid.parse::<PageSelection>().is_err(), "{invalid:?}");
+    }
+}
+
+#[test]
+fn test_excluded_pages_are_not_decoded() {
+    let mut doc = build_pdf(
+        (1..=4)
+            .map(|page| text_ops(12, 72, 700, &format!("Page {page}")))
+            .collect(),
+    );
+    // Page 2 can't be decoded, so extraction only succeeds if it is skipped
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type0",
+        "BaseFont" => "Broken",
+        "Encoding" => "Identity-H",
+    });
+    let page_id = doc.get_pages()[&2];
+    doc.get_dictionary_mut(page_id).unwrap().set(
+        "Resources",
+        dictionary! { "Font" => dictionary! { "F1" => font_id } },
+    );
+
+    let options = TextOptions {
+        pages: Some("1,3-4".parse().unwrap()),
+        ..Default::default()
+    };
+    let text = get_pdf_text_with_options(&doc, &options).unwrap();
+    let texts: Vec<&str> = text.iter().map(|te| te.text.as_str()).collect();
+    assert_eq!(texts, vec!["Page 1", "Page 3", "Page 4"]);
+}
@@ -0,0 +1,143 @@
+mod common;
+
+use common::{add_courier_font, build_pdf, finish_pdf, text_ops};
+use delver::layout::{reconstruct_table, reconstruct_table_with_rules};
+use delver::parse::{get_pdf_paths, get_pdf_text, PathElement, PathKind};
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Document, Object, Stream};
+
+fn op(operator: &str, operands: &[f32]) -> Operation {
+    Operation::new(
+        operator,
+        operands.iter().map(|value| Object::Real(*value)).collect(),
+    )
+}
+
+/// A two row table with columns so close that only the rules drawn between
+/// them tell them apart, boxed in, plus a curve and a clipping path.
+fn ruled_table() -> Document {
+    build_pdf(vec![ruled_table_ops()])
+}
+
+fn ruled_table_ops() -> Vec<Operation> {
+    let mut page = vec![
+        op("q", &[]),
+        op("w", &[0.5]),
+        // A border around the table and a rule under its header
+        op("re", &[70.0, 660.0, 220.0, 55.0]),
+        op("S", &[]),
+        op("m", &[70.0, 695.0]),
+        op("l", &[290.0, 695.0]),
+        op("S", &[]),
+        // Column rules filled as thin rectangles
+        op("re", &[230.0, 660.0, 0.5, 55.0]),
+        op("re", &[250.0, 660.0, 0.5, 55.0]),
+        op("f", &[]),
+        // Clipping to the page paints nothing
+        op("re", &[0.0, 0.0, 612.0, 792.0]),
+        Operation::new("W", vec![]),
+        op("n", &[]),
+        op("m", &[400.0, 500.0]),
+        op("c", &[410.0, 520.0, 430.0, 520.0, 440.0, 500.0]),
+        op("S", &[]),
+        op("Q", &[]),
+        // A line drawn at twice the size
+        op("q", &[]),
+        op("cm", &[2.0, 0.0, 0.0, 2.0, 0.0, 0.0]),
+        op("m", &[10.0, 10.0]),
+        op("l", &[20.0, 10.0]),
+        op("S", &[]),
+        op("Q", &[]),
+    ];
+    for (text, x, y) in [
+        ("Item", 72, 700),
+        ("2015", 236, 700),
+        ("2014", 254, 700),
+        ("Revenue", 72, 680),
+        ("812", 236, 680),
+        ("790", 254, 680),
+    ] {
+        page.extend(text_ops(10, x, y, text));
+    }
+    page
+}
+
+#[test]
+fn test_paths_are_extracted_and_classified() {
+    let paths = get_pdf_paths(&ruled_table()).unwrap();
+    let shapes: Vec<_> = paths
+        .iter()
+        .map(|path: &PathElement| (path.kind, path.bbox, path.stroke_width))
+        .collect();
+    let expected = vec![
+        (PathKind::Rect, (70.0, 660.0, 290.0, 715.0), 0.5),
+        (PathKind::Rule, (70.0, 695.0, 290.0, 695.0), 0.5),
+        (PathKind::Rule, (230.0, 660.0, 230.5, 715.0), 0.0),
+        (PathKind::Rule, (250.0, 660.0, 250.5, 715.0), 0.0),
+        (PathKind::Other, (400.0, 500.0, 440.0, 520.0), 0.5),
+        (PathKind::Rule, (20.0, 20.0, 40.0, 20.0), 2.0),
+    ];
+    assert_eq!(shapes, expected);
+    assert!(paths.iter().all(|path| path.page_number == 1));
+    assert_eq!(paths.iter().filter(|p| p.is_vertical_rule()).count(), 2);
+}
+
+#[test]
+fn test_rules_split_table_columns() {
+    let doc = ruled_table();
+    let text = get_pdf_text(&doc).unwrap();
+    let paths = get_pdf_paths(&doc).unwrap();
+
+    // The value columns are too close to split on the gap between them
+    assert_eq!(
+        reconstruct_table(&text, None),
+        vec![vec!["Item", "2015 2014"], vec!["Revenue", "812 790"]]
+    );
+    assert_eq!(
+        reconstruct_table_with_rules(&text, None, &paths),
+        vec![vec!["Item", "2015", "2014"], vec!["Revenue", "812", "790"]]
+    );
+}
+
+#[test]
+fn test_rules_and_text_share_transforms() {
+    // The table is drawn by a form, placed at twice the size and moved
+    let mut doc = Document::with_version("1.5");
+    let font_id = add_courier_font(&mut doc);
+    let content = Content {
+        operations: ruled_table_ops(),
+    };
+    let form_id = doc.add_object(Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "BBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        },
+        content.encode().unwrap(),
+    ));
+    let resources = dictionary! {
+        "Font" => dictionary! { "F1" => font_id },
+        "XObject" => dictionary! { "Fm1" => form_id },
+    };
+    let page = vec![
+        op("q", &[]),
+        op("cm", &[2.0, 0.0, 0.0, 2.0, -100.0, -700.0]),
+        Operation::new("Do", vec!["Fm1".into()]),
+        op("Q", &[]),
+    ];
+    let doc = finish_pdf(doc, resources, vec![page]);
+    let text = get_pdf_text(&doc).unwrap();
+    let paths = get_pdf_paths(&doc).unwrap();
+
+    let rules: Vec<f32> = paths
+        .iter()
+        .filter(|path| path.is_vertical_rule())
+        .map(|path| path.bbox.0)
+        .collect();
+    assert_eq!(rules, vec![360.0, 400.0]);
+    assert_eq!(text[1].position, (372.0, 700.0));
+    assert_eq!(
+        reconstruct_table_with_rules(&text, None, &paths),
+        vec![vec!["Item", "2015", "2014"], vec!["Revenue", "812", "790"]]
+    );
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use common::{build_pdf, text_ops};
+use delver::error::DelverError;
+use delver::parse::{get_pdf_text, get_pdf_text_report};
+use lopdf::{dictionary, Document};
+
+/// Three pages where the middle one uses an Identity-H font with no ToUnicode map,
+/// so its text can't be decoded.
+fn document_with_broken_page() -> Document {
+    let mut doc = build_pdf(vec![
+        text_ops(12, 72, 700, "First page"),
+        text_ops(12, 72, 700, "Second page"),
+        text_ops(12, 72, 700, "Third page"),
+    ]);
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type0",
+        "BaseFont" => "Broken",
+        "Encoding" => "Identity-H",
+    });
+    let page_id = doc.get_pages()[&2];
+    doc.get_dictionary_mut(page_id).unwrap().set(
+        "Resources",
+        dictionary! { "Font" => dictionary! { "F1" => font_id } },
+    );
+    doc
+}
+
+#[test]
+fn test_broken_page_is_skipped_with_warning() {
+    let report = get_pdf_text_report(&document_with_broken_page());
+
+    let text: Vec<&str> = report
+        .text_elements
+        .iter()
+        .map(|e| e.text.as_str())
+        .collect();
+    assert_eq!(text, vec!["First page", "Third page"]);
+    assert_eq!(report.warnings.len(), 1);
+    assert_eq!(report.warnings[0].page, 2);
+}
+
+#[test]
+fn test_strict_extraction_fails_on_broken_page() {
+    let err = get_pdf_text(&document_with_broken_page()).unwrap_err();
+    assert!(
+        matches!(err, DelverError::PageExtraction { page: 2, .. }),
+        "{err}"
+    );
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use common::{build_pdf, text_ops};
+use delver::dom::parse_template;
+use delver::layout::{extract_template_sections, restrict_to_region, Region};
+use delver::parse::{get_page_geometry, get_pdf_text, load_pdf_bytes, PageGeometry};
+use lopdf::{Document, Object};
+
+fn load(doc: &mut Document) -> Document {
+    let mut bytes = Vec::new();
+    doc.save_to(&mut bytes).unwrap();
+    load_pdf_bytes(&bytes).unwrap()
+}
+
+#[test]
+fn test_region_anchors_field_without_pattern() {
+    let mut page = text_ops(18, 72, 740, "ACME Supplies");
+    page.extend(text_ops(10, 420, 740, "Invoice no. 10482"));
+    page.extend(text_ops(10, 420, 726, "Date: 2024-03-01"));
+    page.extend(text_ops(
+        10,
+        72,
+        400,
+        "Invoice no. appears on every remittance.",
+    ));
+    let doc = load(&mut build_pdf(vec![page]));
+    let pages = get_page_geometry(&doc);
+
+    let root = parse_template("Section(region=[0.5, 0, 1, 0.25]) { TextChunk() }").unwrap();
+    let region = Region::from_element(&root.elements[0]).unwrap();
+    assert_eq!(
+        region,
+        Region {
+            x0: 0.5,
+            y0: 0.0,
+            x1: 1.0,
+            y1: 0.25
+        }
+    );
+
+    let inside: Vec<String> = restrict_to_region(get_pdf_text(&doc).unwrap(), &region, &pages)
+        .into_iter()
+        .map(|mi| mi.text)
+        .collect();
+    assert_eq!(inside, vec!["Invoice no. 10482", "Date: 2024-03-01"]);
+}
+
+#[test]
+fn test_template_region_reaches_extraction() {
+    let mut page = text_ops(10, 420, 760, "Invoice no. 10482");
+    page.extend(text_ops(18, 72, 740, "ACME Supplies"));
+    page.extend(text_ops(10, 420, 726, "Date: 2024-03-01"));
+    page.extend(text_ops(
+        10,
+        72,
+        400,
+        "Invoice no. appears on every remittance.",
+    ));
+    let doc = load(&mut build_pdf(vec![page]));
+    let elements = get_pdf_text(&doc).unwrap();
+    let extract = |template: &str| {
+        let root = parse_template(template).unwrap();
+        let sections = extract_template_sections(&doc, &elements, &root).unwrap();
+        (sections[0].heading.clone(), sections[0].text.clone())
+    };
+
+    assert_eq!(
+        extract(r#"Section(match="Invoice no.")"#).0,
+        "Invoice no. appears on every remittance."
+    );
+    assert_eq!(
+        extract(r#"Section(match="Invoice no.", region=[0.5, 0, 1, 0.25])"#).0,
+        "Invoice no. 10482"
+    );
+    assert_eq!(
+        extract(r#"Section(match="ACME Supplies") { TextChunk(region=[0.5, 0, 1, 0.25]) }"#).1,
+        "ACME Supplies Date: 2024-03-01 "
+    );
+}
+
+#[test]
+fn test_page_geometry_is_inherited() {
+    let mut doc = build_pdf(vec![
+        text_ops(10, 72, 300, "Letter."),
+        text_ops(10, 72, 350, "Landscape A4."),
+    ]);
+    let second_page = doc.get_pages()[&2];
+    let landscape: Vec<Object> = vec![0.into(), 0.into(), 842.into(), 595.into()];
+    doc.get_dictionary_mut(second_page)
+        .unwrap()
+        .set("MediaBox", landscape);
+    let doc = load(&mut doc);
+
+    let pages = get_page_geometry(&doc);
+    assert_eq!(pages[&1].width, 612.0);
+    assert_eq!(pages[&1].height, 792.0);
+    assert_eq!(
+        pages[&2],
+        PageGeometry {
+            left: 0.0,
+            bottom: 0.0,
+            width: 842.0,
+            height: 595.0,
+            rotation: 0,
+        }
+    );
+
+    // 350pt up is in the bottom half of a letter page but the top half of a
+    // landscape A4 one
+    let bottom_half = Region {
+        x0: 0.0,
+        y0: 0.5,
+        x1: 1.0,
+        y1: 1.0,
+    };
+    let inside = restrict_to_region(get_pdf_text(&doc).unwrap(), &bottom_half, &pages);
+    assert_eq!(inside.len(), 1);
+    assert_eq!(inside[0].text, "Letter.");
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use common::element;
+use delver::layout::{merge_script_runs, ScriptStyle};
+use delver::parse::TextElement;
+
+fn texts(elements: &[TextElement]) -> Vec<&str> {
+    elements.iter().map(|mi| mi.text.as_str()).collect()
+}
+
+#[test]
+fn test_footnote_marker_merges_into_base() {
+    let elements = vec![
+        element("Net revenue ", 1, "Helvetica", 10.0, (72.0, 700.0)),
+        element("1", 1, "Helvetica", 6.0, (130.0, 704.0)),
+        element(" grew 4%.", 1, "Helvetica", 10.0, (134.0, 700.0)),
+    ];
+
+    let merged = merge_script_runs(&elements, ScriptStyle::Unicode);
+    assert_eq!(texts(&merged), vec!["Net revenue¹", " grew 4%."]);
+
+    let merged = merge_script_runs(&elements, ScriptStyle::Bracketed);
+    assert_eq!(texts(&merged), vec!["Net revenue[1]", " grew 4%."]);
+}
+
+#[test]
+fn test_subscript_merges_into_base() {
+    let elements = vec![
+        element("Emissions of CO", 1, "Helvetica", 10.0, (72.0, 700.0)),
+        element("2", 1, "Helvetica", 6.0, (150.0, 697.5)),
+        element("H", 1, "Helvetica", 10.0, (72.0, 680.0)),
+        element("a", 1, "Helvetica", 6.0, (80.0, 678.0)),
+    ];
+
+    let merged = merge_script_runs(&elements, ScriptStyle::Unicode);
+    // Letters have no subscript form, so they fall back to brackets
+    assert_eq!(texts(&merged), vec!["Emissions of CO₂", "H[a]"]);
+}
+
+#[test]
+fn test_small_caps_and_new_lines_are_not_scripts() {
+    let elements = vec![
+        element("Annual ", 1, "Helvetica", 10.0, (72.0, 700.0)),
+        element("REPORT", 1, "Helvetica", 6.5, (110.0, 700.0)),
+        element("Footnotes", 1, "Helvetica", 10.0, (72.0, 600.0)),
+        element("1 Restated.", 1, "Helvetica", 6.0, (72.0, 590.0)),
+    ];
+
+    let merged = merge_script_runs(&elements, ScriptStyle::Unicode);
+    assert_eq!(merged.len(), 4);
+}
@@ -0,0 +1,447 @@
+mod common;
+
+use common::{build_pdf, element, text_ops};
+use delver::dom::parse_template;
+use delver::layout::{
+    extract_section_content, extract_section_content_with_options, extract_template_sections,
+    extract_template_sections_with_options, match_span, ChunkFilter, JoinThreshold, MatchOptions,
+    ReadingOrder, SectionOptions, TemplateOptions,
+};
+use delver::parse::{get_pdf_text, TextElement};
+
+/// Headings share a 16pt bold font and no end marker is given.
+fn document() -> Vec<TextElement> {
+    vec![
+        element("1. Overview", 1, "Helvetica-Bold", 16.0, (72.0, 700.0)),
+        element("Body one.", 1, "Helvetica", 12.0, (72.0, 680.0)),
+        element("Body two.", 1, "Helvetica", 12.0, (72.0, 660.0)),
+        element("2. Risks", 1, "Helvetica-Bold", 16.0, (72.0, 640.0)),
+        element("Risk body.", 1, "Helvetica", 12.0, (72.0, 620.0)),
+        element("Part II", 2, "Helvetica-Bold", 20.0, (72.0, 700.0)),
+        element("Other.", 2, "Helvetica", 12.0, (72.0, 680.0)),
+    ]
+}
+
+#[test]
+fn test_section_ends_at_next_sibling_heading() {
+    let elements = document();
+    let text = extract_section_content(&elements, &elements[0]);
+    assert_eq!(text, "1. Overview Body one. Body two. ");
+}
+
+#[test]
+fn test_section_ends_at_larger_heading() {
+    let elements = document();
+    let text = extract_section_content(&elements, &elements[3]);
+    assert_eq!(text, "2. Risks Risk body. ");
+}
+
+#[test]
+fn test_section_runs_to_end_of_document() {
+    let elements = document();
+    let text = extract_section_content(&elements, &elements[5]);
+    assert_eq!(text, "Part II Other. ");
+}
+
+#[test]
+fn test_section_reads_top_of_page_first() {
+    let mut elements = document();
+    elements.reverse();
+    let start = elements
+        .iter()
+        .find(|e| e.text == "1. Overview")
+        .unwrap()
+        .clone();
+    let text = extract_section_content(&elements, &start);
+    assert_eq!(text, "1. Overview Body one. Body two. ");
+}
+
+fn columns() -> SectionOptions {
+    SectionOptions {
+        order: ReadingOrder::Columns,
+        ..Default::default()
+    }
+}
+
+/// A heading over two columns of body text sharing baselines.
+fn two_column_page() -> Vec<TextElement> {
+    let mut elements = vec![element(
+        "Risk Factors",
+        3,
+        "Helvetica-Bold",
+        16.0,
+        (72.0, 720.0),
+    )];
+    for (row, y) in [700.0, 686.0, 672.0].into_iter().enumerate() {
+        elements.push(element(
+            &format!("Left {row}."),
+            3,
+            "Helvetica",
+            10.0,
+            (72.0, y),
+        ));
+        let mut right = element(&format!("Right {row}."), 3, "Helvetica", 10.0, (72.0, y));
+        right.position.0 = 320.0;
+        elements.push(right);
+    }
+    elements
+}
+
+#[test]
+fn test_columns_read_left_column_first() {
+    let elements = two_column_page();
+
+    let raster = extract_section_content(&elements, &elements[0]);
+    assert_eq!(
+        raster,
+        "Risk Factors Left 0. Right 0. Left 1. Right 1. Left 2. Right 2. "
+    );
+
+    let columns = extract_section_content_with_options(&elements, &elements[0], &columns());
+    assert_eq!(
+        columns,
+        "Risk Factors Left 0. Left 1. Left 2. Right 0. Right 1. Right 2. "
+    );
+}
+
+#[test]
+fn test_single_column_page_is_unchanged_by_column_order() {
+    let elements = document();
+    assert_eq!(
+        extract_section_content_with_options(&elements, &elements[0], &columns()),
+        extract_section_content(&elements, &elements[0])
+    );
+}
+
+#[test]
+fn test_words_hyphenated_across_lines_are_joined() {
+    let elements = vec![
+        element("Liquidity", 4, "Helvetica-Bold", 16.0, (72.0, 700.0)),
+        element("Our cash manage-", 4, "Helvetica", 12.0, (72.0, 680.0)),
+        element("ment policy uses non-", 4, "Helvetica", 12.0, (72.0, 666.0)),
+        element(
+            "GAAP measures and a well-",
+            4,
+            "Helvetica",
+            12.0,
+            (72.0, 652.0),
+        ),
+        element("Known auditor.", 4, "Helvetica", 12.0, (72.0, 638.0)),
+    ];
+
+    let text = extract_section_content(&elements, &elements[0]);
+    assert_eq!(
+        text,
+        "Liquidity Our cash management policy uses non- GAAP measures and a well- Known auditor. "
+    );
+
+    let options = SectionOptions {
+        dehyphenate: false,
+        ..Default::default()
+    };
+    let text = extract_section_content_with_options(&elements, &elements[0], &options);
+    assert!(text.contains("manage- ment"), "{text}");
+}
+
+#[test]
+fn test_hyphen_within_a_line_is_kept() {
+    let mut elements = vec![
+        element("Liquidity", 4, "Helvetica-Bold", 16.0, (72.0, 700.0)),
+        element("Year-", 4, "Helvetica", 12.0, (72.0, 680.0)),
+        element("over-year", 4, "Helvetica", 12.0, (72.0, 680.0)),
+    ];
+    elements[2].position.0 = 110.0;
+
+    let text = extract_section_content(&elements, &elements[0]);
+    assert_eq!(text, "Liquidity Year- over-year ");
+}
+
+#[test]
+fn test_excluded_boilerplate_is_dropped() {
+    let elements = vec![
+        element("Item 7. Overview", 1, "Helvetica-Bold", 16.0, (72.0, 700.0)),
+        element(
+            "Forward-Looking Statements",
+            1,
+            "Helvetica",
+            12.0,
+            (72.0, 680.0),
+        ),
+        element(
+            "This report contains forward-looking statements.",
+            1,
+            "Helvetica",
+            12.0,
+            (72.0, 660.0),
+        ),
+        element("Revenue grew 10%.", 1, "Helvetica", 12.0, (72.0, 640.0)),
+    ];
+    let root = parse_template(
+        r#"Section(match="Overview") { TextChunk(excludePattern="(?i)forward-looking") }"#,
+    )
+    .unwrap();
+    let filter = ChunkFilter::from_element(&root.elements[0].children[0]).unwrap();
+
+    let (kept, filtered) = filter.filter_lines(&elements[1..]);
+    assert_eq!(kept.len(), 1);
+    assert_eq!(filtered, 2);
+
+    let options = SectionOptions {
+        filter,
+        ..Default::default()
+    };
+    let text = extract_section_content_with_options(&elements, &elements[0], &options);
+    assert_eq!(text, "Item 7. Overview Revenue grew 10%. ");
+}
+
+#[test]
+fn test_template_chunk_filter_drops_lines() {
+    let mut page = text_ops(16, 72, 700, "Item 7. Overview");
+    page.extend(text_ops(12, 72, 680, "Revenue grew 10%."));
+    page.extend(text_ops(
+        12,
+        72,
+        660,
+        "Forward-looking statements may differ.",
+    ));
+    page.extend(text_ops(12, 72, 640, "Costs were flat."));
+    let doc = build_pdf(vec![page]);
+    let elements = get_pdf_text(&doc).unwrap();
+
+    let extract = |template: &str| {
+        let root = parse_template(template).unwrap();
+        extract_template_sections(&doc, &elements, &root).unwrap()[0]
+            .text
+            .clone()
+    };
+    assert_eq!(
+        extract(r#"Section(match="Overview") { TextChunk(excludePattern="(?i)forward-looking") }"#),
+        "Item 7. Overview Revenue grew 10%. Costs were flat. "
+    );
+    assert_eq!(
+        extract(r#"Section(match="Overview") { TextChunk(includePattern="^Costs") }"#),
+        "Item 7. Overview Costs were flat. "
+    );
+}
+
+#[test]
+fn test_template_match_spans_runs_of_heading_line() {
+    // "Item 7." and the rest of the heading are separate runs in the same font
+    let mut page = text_ops(14, 72, 700, "Item 7.");
+    page.extend(text_ops(14, 140, 700, "Overview Revenue rose."));
+    page.extend(text_ops(10, 72, 680, "Costs fell."));
+    let doc = build_pdf(vec![page]);
+    let elements = get_pdf_text(&doc).unwrap();
+    assert_eq!(elements.len(), 3);
+
+    let extract = |template: &str| {
+        let root = parse_template(template).unwrap();
+        let section = extract_template_sections(&doc, &elements, &root).unwrap()[0].clone();
+        (section.heading, section.text)
+    };
+    assert_eq!(
+        extract(r#"Section(match="Item 7. Overview")"#),
+        (
+            "Item 7. Overview Revenue rose.".to_string(),
+            "Item 7. Overview Revenue rose. Costs fell. ".to_string()
+        )
+    );
+    // Only the text after the match is body, so the filter sees it
+    assert_eq!(
+        extract(r#"Section(match="Item 7. Overview") { TextChunk(excludePattern="^Revenue") }"#).1,
+        "Item 7. Overview Costs fell. "
+    );
+}
+
+#[test]
+fn test_template_end_match_ends_section() {
+    // "Summary" is set like the body, so it is no natural boundary
+    let mut page = text_ops(16, 72, 700, "Risks");
+    page.extend(text_ops(10, 72, 680, "Rates may rise."));
+    page.extend(text_ops(10, 72, 660, "Summary"));
+    page.extend(text_ops(10, 72, 640, "Closing text."));
+    let doc = build_pdf(vec![page]);
+    let elements = get_pdf_text(&doc).unwrap();
+
+    let extract = |template: &str| {
+        let root = parse_template(template).unwrap();
+        extract_template_sections(&doc, &elements, &root).unwrap()[0]
+            .text
+            .clone()
+    };
+    assert_eq!(
+        extract(r#"Section(match="Risks")"#),
+        "Risks Rates may rise. Summary Closing text. "
+    );
+    assert_eq!(
+        extract(r#"Section(match="Risks", end_match="Summary")"#),
+        "Risks Rates may rise. "
+    );
+    // The end can fall part way through a line
+    assert_eq!(
+        extract(r#"Section(match="Risks", end_match="may rise")"#),
+        "Risks Rates "
+    );
+    // Without a hit the natural boundary is used
+    assert_eq!(
+        extract(r#"Section(match="Risks", end_match="Appendix")"#),
+        "Risks Rates may rise. Summary Closing text. "
+    );
+}
+
+#[test]
+fn test_template_regex_match() {
+    let mut first = text_ops(14, 72, 700, "Item 7A. Market Risk");
+    first.extend(text_ops(10, 72, 680, "Rates may rise."));
+    let mut second = text_ops(10, 72, 700, "See Item 7. for results.");
+    second.extend(text_ops(14, 72, 680, "Item 7. Overview"));
+    second.extend(text_ops(10, 72, 660, "Revenue rose."));
+    let doc = build_pdf(vec![first, second]);
+    let elements = get_pdf_text(&doc).unwrap();
+
+    let root = parse_template(r#"Section(match=["^Item\s+7\.", 600, "regex"])"#).unwrap();
+    let sections = extract_template_sections(&doc, &elements, &root).unwrap();
+    assert_eq!(sections[0].heading, "Item 7. Overview");
+    assert_eq!(sections[0].page, 2);
+    assert_eq!(sections[0].text, "Item 7. Overview Revenue rose. ");
+}
+
+#[test]
+fn test_template_nested_section_stays_in_parent() {
+    // A larger "Summary" comes before the parent and would win on its own
+    let mut page = text_ops(14, 72, 700, "Summary");
+    page.extend(text_ops(10, 72, 680, "Intro text."));
+    page.extend(text_ops(16, 72, 660, "Risks"));
+    page.extend(text_ops(10, 72, 640, "Rates may rise."));
+    page.extend(text_ops(12, 72, 620, "Summary"));
+    page.extend(text_ops(10, 72, 600, "Risk summary."));
+    page.extend(text_ops(16, 72, 580, "Outlook"));
+    page.extend(text_ops(10, 72, 560, "Later."));
+    let doc = build_pdf(vec![page]);
+    let elements = get_pdf_text(&doc).unwrap();
+
+    let root = parse_template(
+        r#"Section(match="Risks", as="risks") { Section(match="Summary", as="summary") }"#,
+    )
+    .unwrap();
+    let sections = extract_template_sections(&doc, &elements, &root).unwrap();
+    let texts: Vec<(Option<&str>, &str)> = sections
+        .iter()
+        .map(|section| (section.name.as_deref(), section.text.as_str()))
+        .collect();
+    assert_eq!(
+        texts,
+        vec![
+            (
+                Some("risks"),
+                "Risks Rates may rise. Summary Risk summary. "
+            ),
+            // Nothing inside the parent ends it, so it runs to the parent's end
+            (Some("summary"), "Summary Risk summary. "),
+        ]
+    );
+}
+
+#[test]
+fn test_template_heading_line_uses_join_threshold() {
+    // The rest of the heading is drawn first and sits 7pt below "Item 7."
+    let mut page = text_ops(14, 140, 693, "Overview Revenue rose.");
+    page.extend(text_ops(14, 72, 700, "Item 7."));
+    page.extend(text_ops(10, 72, 670, "Costs fell."));
+    let doc = build_pdf(vec![page]);
+    let elements = get_pdf_text(&doc).unwrap();
+
+    let root = parse_template(
+        r#"Section(match="Item 7. Overview") { TextChunk(excludePattern="^Revenue") }"#,
+    )
+    .unwrap();
+    let options = TemplateOptions {
+        matching: MatchOptions {
+            line_join_threshold: JoinThreshold::Points(8.0),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let sections =
+        extract_template_sections_with_options(&doc, &elements, &root, &options).unwrap();
+    assert_eq!(sections[0].heading, "Item 7. Overview Revenue rose.");
+    assert_eq!(sections[0].text, "Item 7. Overview Costs fell. ");
+}
+
+/// The heading and the first sentence of its body were drawn as one text run.
+fn merged_heading() -> Vec<TextElement> {
+    vec![
+        element("Closing remarks.", 1, "Helvetica", 12.0, (72.0, 720.0)),
+        element(
+            "Item 7. Overview Forward-looking statements follow.",
+            1,
+            "Helvetica-Bold",
+            16.0,
+            (72.0, 700.0),
+        ),
+        element("Revenue grew 10%.", 1, "Helvetica", 12.0, (72.0, 680.0)),
+        element(
+            "Costs were flat. Item 8. Financial Statements",
+            1,
+            "Helvetica-Bold",
+            16.0,
+            (72.0, 660.0),
+        ),
+        element("Balance sheet.", 1, "Helvetica", 12.0, (72.0, 640.0)),
+    ]
+}
+
+#[test]
+fn test_body_starts_after_heading_in_shared_run() {
+    let elements = merged_heading();
+    let heading = &elements[1];
+    let span = match_span(&heading.text, "Item 7. Overview", &MatchOptions::default()).unwrap();
+    assert_eq!(&heading.text[span.clone()], "Item 7. Overview");
+
+    let options = SectionOptions {
+        filter: ChunkFilter {
+            exclude: Some(regex::Regex::new("(?i)^forward-looking").unwrap()),
+            ..Default::default()
+        },
+        start_offset: Some(span.end),
+        ..Default::default()
+    };
+    let text = extract_section_content_with_options(&elements, heading, &options);
+    assert_eq!(text, "Item 7. Overview Revenue grew 10%. ");
+
+    // Without the offset the whole run is heading and escapes the filter
+    let options = SectionOptions {
+        start_offset: None,
+        ..options
+    };
+    let text = extract_section_content_with_options(&elements, heading, &options);
+    assert_eq!(
+        text,
+        "Item 7. Overview Forward-looking statements follow. Revenue grew 10%. "
+    );
+}
+
+#[test]
+fn test_body_ends_before_next_heading_in_shared_run() {
+    let elements = merged_heading();
+    let end = &elements[3];
+    let span = match_span(
+        &end.text,
+        "item 8.",
+        &MatchOptions {
+            ignore_case: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let options = SectionOptions {
+        end_offset: Some(span.start),
+        ..Default::default()
+    };
+    let text = extract_section_content_with_options(&elements, &elements[1], &options);
+    assert_eq!(
+        text,
+        "Item 7. Overview Forward-looking statements follow. Revenue grew 10%. Costs were flat. "
+    );
+}
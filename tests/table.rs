@@ -0,0 +1,79 @@
+use delver::layout::reconstruct_table;
+use delver::parse::TextElement;
+
+fn cell(text: &str, x: f32, y: f32) -> TextElement {
+    TextElement {
+        text: text.to_string(),
+        page_number: 12,
+        font_size: 10.0,
+        font_name: Some("Helvetica".to_string()),
+        position: (x, y),
+        ..Default::default()
+    }
+}
+
+/// A five row by four column income statement, drawn column by column with
+/// right-aligned numbers and one label wrapped over two lines.
+fn income_statement() -> Vec<TextElement> {
+    vec![
+        cell("Net sales", 72.0, 700.0),
+        cell("Cost of sales", 72.0, 685.0),
+        cell("Selling, general and", 72.0, 670.0),
+        cell("administrative expenses", 72.0, 658.0),
+        cell("Operating income", 72.0, 643.0),
+        cell("2015", 300.0, 715.0),
+        cell("30,274", 294.0, 700.0),
+        cell("15,383", 294.0, 685.0),
+        cell("6,182", 300.0, 658.0),
+        cell("6,946", 300.0, 643.0),
+        cell("2014", 380.0, 715.0),
+        cell("31,821", 374.0, 700.0),
+        cell("16,447", 374.0, 685.0),
+        cell("6,469", 380.0, 658.0),
+        cell("7,135", 380.0, 643.0),
+        cell("2013", 460.0, 715.0),
+        cell("30,871", 454.0, 700.0),
+        cell("16,106", 454.0, 685.0),
+        cell("6,384", 460.0, 658.0),
+        cell("6,666", 460.0, 643.0),
+    ]
+}
+
+fn expected() -> Vec<Vec<String>> {
+    [
+        ["", "2015", "2014", "2013"],
+        ["Net sales", "30,274", "31,821", "30,871"],
+        ["Cost of sales", "15,383", "16,447", "16,106"],
+        [
+            "Selling, general and administrative expenses",
+            "6,182",
+            "6,469",
+            "6,384",
+        ],
+        ["Operating income", "6,946", "7,135", "6,666"],
+    ]
+    .iter()
+    .map(|row| row.iter().map(|c| c.to_string()).collect())
+    .collect()
+}
+
+#[test]
+fn test_reconstruct_table_detects_columns() {
+    assert_eq!(reconstruct_table(&income_statement(), None), expected());
+}
+
+#[test]
+fn test_reconstruct_table_with_column_count() {
+    assert_eq!(reconstruct_table(&income_statement(), Some(4)), expected());
+}
+
+#[test]
+fn test_reconstruct_table_collapses_to_fewer_columns() {
+    let rows = reconstruct_table(&income_statement(), Some(2));
+    assert_eq!(rows[1], vec!["Net sales", "30,274 31,821 30,871"]);
+}
+
+#[test]
+fn test_reconstruct_empty_table() {
+    assert!(reconstruct_table(&[], None).is_empty());
+}
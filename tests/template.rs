@@ -0,0 +1,350 @@
+use delver::dom::{parse_template, parse_template_auto, parse_template_json, Value};
+
+#[test]
+fn test_fractional_attribute_parses_as_float() {
+    let root = parse_template(
+        r#"Section(match="Risk Factors", threshold=0.85) { TextChunk(chunkSize=500) }"#,
+    )
+    .unwrap();
+    let section = &root.elements[0];
+    let threshold = &section.attributes["threshold"];
+    let chunk_size = &section.children[0].attributes["chunkSize"];
+
+    assert!(matches!(threshold, Value::Float(f) if *f == 0.85));
+    assert!(matches!(chunk_size, Value::Number(500)));
+    assert_eq!(threshold.as_number(), Some(0.85));
+    assert_eq!(chunk_size.as_number(), Some(500.0));
+    assert_eq!(section.attributes["match"].as_number(), None);
+}
+
+#[test]
+fn test_float_values_serialize_as_json_numbers() {
+    let weights = Value::Array(vec![
+        Value::Float(0.5),
+        Value::Float(-1.25),
+        Value::Number(3),
+    ]);
+    assert_eq!(serde_json::to_string(&weights).unwrap(), "[0.5,-1.25,3]");
+
+    let value: f64 =
+        serde_json::from_str(&serde_json::to_string(&Value::Float(0.85)).unwrap()).unwrap();
+    assert_eq!(value, 0.85);
+}
+
+#[test]
+fn test_out_of_range_integer_is_rejected() {
+    let error = parse_template(
+        "Section(match=\"Risks\") {\n  TextChunk(chunkSize=99999999999999999999)\n}",
+    )
+    .unwrap_err();
+    assert_eq!(error.element.as_deref(), Some("TextChunk"));
+    assert_eq!((error.line, error.column), (2, 23));
+    assert_eq!(
+        error.message,
+        "invalid number `99999999999999999999` in `chunkSize`: number too large to fit in target type"
+    );
+}
+
+#[test]
+fn test_unknown_element_is_rejected() {
+    let error =
+        parse_template("Section(match=\"Risks\") {\n  TextChunck(chunkSize=500)\n}").unwrap_err();
+    assert_eq!((error.line, error.column), (2, 3));
+    assert_eq!(error.element.as_deref(), Some("TextChunck"));
+    assert_eq!(error.message, "unknown element `TextChunck`");
+}
+
+#[test]
+fn test_unknown_attribute_is_rejected() {
+    let error = parse_template("Section(matc=\"Risks\", as=\"risks\")").unwrap_err();
+    assert_eq!((error.line, error.column), (1, 9));
+    assert_eq!(error.element.as_deref(), Some("Section"));
+    assert!(error
+        .message
+        .starts_with("unknown attribute `matc` on Section"));
+    assert_eq!(
+        error.to_string(),
+        format!("line 1, column 9: {}", error.message)
+    );
+}
+
+#[test]
+fn test_malformed_array_reports_position() {
+    let error = parse_template("TextChunk(\n  addMeta=[a, b,, c]\n)").unwrap_err();
+    assert_eq!(error.line, 2);
+    assert!(error.element.is_none());
+}
+
+#[test]
+fn test_comments_are_ignored() {
+    let template = r#"
+        // Filings use "Item 7." or "ITEM 7" depending on the year
+        Section(match="Item 7", /* end before the financial statements */ end_match="Item 8") {
+            TextChunk(chunkSize=500) // one chunk per page or so
+        }
+        /* Section(match="Item 9") */
+    "#;
+    let root = parse_template(template).unwrap();
+    assert_eq!(root.elements.len(), 1);
+    assert_eq!(root.elements[0].children.len(), 1);
+    assert!(matches!(&root.elements[0].attributes["end_match"], Value::String(s) if s == "Item 8"));
+}
+
+#[test]
+fn test_triple_quoted_string_spans_lines() {
+    let template = r#"Section(match="""Management's "Discussion"
+and Analysis""")"#;
+    let root = parse_template(template).unwrap();
+    assert!(matches!(
+        &root.elements[0].attributes["match"],
+        Value::String(s) if s == "Management's \"Discussion\"\nand Analysis"
+    ));
+}
+
+#[test]
+fn test_fragment_used_with_different_arguments() {
+    let template = r#"
+        use MDA(end="Item 8")
+        use MDA(end="Item 7A")
+
+        define MDA(end) {
+            Section(match="Management's Discussion and Analysis", end_match=end, as="mda") {
+                TextChunk(chunkSize=500, addMeta=[mda])
+            }
+        }
+    "#;
+    let root = parse_template(template).unwrap();
+    assert_eq!(root.elements.len(), 2);
+
+    let end_matches: Vec<&Value> = root
+        .elements
+        .iter()
+        .map(|e| &e.attributes["end_match"])
+        .collect();
+    assert!(matches!(end_matches[0], Value::String(s) if s == "Item 8"));
+    assert!(matches!(end_matches[1], Value::String(s) if s == "Item 7A"));
+    for section in &root.elements {
+        assert_eq!(section.name, "Section");
+        assert_eq!(section.children[0].name, "TextChunk");
+        // Identifiers that don't name a parameter are left alone
+        assert!(matches!(
+            &section.children[0].attributes["addMeta"],
+            Value::Array(meta) if matches!(&meta[0], Value::Identifier(m) if m == "mda")
+        ));
+    }
+}
+
+#[test]
+fn test_recursive_fragment_is_rejected() {
+    let template = r#"
+        define Outer() { Section(match="Part I") { use Inner() } }
+        define Inner() { use Outer() }
+        use Outer()
+    "#;
+    let error = parse_template(template).unwrap_err();
+    assert_eq!(error.line, 3);
+    assert_eq!(
+        error.message,
+        "fragment `Outer` uses itself: Outer -> Inner -> Outer"
+    );
+}
+
+#[test]
+fn test_fragment_arguments_are_checked() {
+    let define = "define Risks(end) { Section(match=\"Risk Factors\", end_match=end) }\n";
+
+    let error = parse_template(&format!("{define}use Risks()")).unwrap_err();
+    assert_eq!(error.message, "missing argument `end` for fragment `Risks`");
+
+    let error = parse_template(&format!("{define}use Risks(end=\"Item 2\", start=1)")).unwrap_err();
+    assert_eq!(error.message, "fragment `Risks` has no parameter `start`");
+
+    let error = parse_template("use Missing()").unwrap_err();
+    assert_eq!(error.message, "unknown fragment `Missing`");
+}
+
+#[test]
+fn test_json_template_matches_dsl() {
+    let dsl = std::fs::read_to_string("10k.tmpl").unwrap();
+    let json = r#"
+    {
+        "elements": [{
+            "type": "Section",
+            "attributes": {"match": "Section 1: Management Discussion & Analysis", "as": "section1"},
+            "children": [{
+                "type": "Section",
+                "attributes": {"match": "Section 1.1: Risks", "as": "section1_1"},
+                "children": [{
+                    "type": "Section",
+                    "attributes": {"match": "Section 1.1b: Fiscal Risks", "as": "section1_1b"},
+                    "children": [{
+                        "type": "TextChunk",
+                        "attributes": {
+                            "chunkSize": 500,
+                            "chunkOverlap": 150,
+                            "addMeta": [
+                                {"identifier": "section1"},
+                                {"identifier": "section1_1"},
+                                {"identifier": "section1_1b"}
+                            ]
+                        }
+                    }]
+                }]
+            }]
+        }]
+    }"#;
+
+    let expected = parse_template(&dsl).unwrap();
+    assert_eq!(parse_template_json(json).unwrap(), expected);
+    assert_eq!(parse_template_auto(json).unwrap(), expected);
+    assert_eq!(parse_template_auto(&dsl).unwrap(), expected);
+}
+
+#[test]
+fn test_json_template_values_and_validation() {
+    let root = parse_template_auto(
+        r#"[{"type": "Section", "attributes": {"match": "Risks", "threshold": 0.85}}]"#,
+    )
+    .unwrap();
+    assert_eq!(root.elements[0].attributes["threshold"], Value::Float(0.85));
+
+    let error = parse_template_json(r#"[{"type": "Section", "attributes": {"matc": "Risks"}}]"#)
+        .unwrap_err();
+    assert_eq!(error.element.as_deref(), Some("Section"));
+    assert!(error
+        .message
+        .starts_with("unknown attribute `matc` on Section"));
+
+    let error =
+        parse_template_json("[{\"type\": \"Section\",\n  \"attributes\": {]}]").unwrap_err();
+    assert_eq!(error.line, 2);
+}
+
+#[test]
+fn test_text_chunk_patterns_are_validated() {
+    let root = parse_template(
+        r#"Section(match="Risks") { TextChunk(excludePattern="(?i)forward-looking") }"#,
+    )
+    .unwrap();
+    let chunk = &root.elements[0].children[0];
+    assert_eq!(
+        chunk.attributes["excludePattern"],
+        Value::String("(?i)forward-looking".to_string())
+    );
+
+    let error = parse_template("Section(match=\"Risks\") {\n  TextChunk(includePattern=\"(\")\n}")
+        .unwrap_err();
+    assert_eq!(error.element.as_deref(), Some("TextChunk"));
+    assert!(error
+        .message
+        .starts_with("invalid regex in `includePattern`"));
+
+    let error =
+        parse_template_json(r#"[{"type": "TextChunk", "attributes": {"excludePattern": "[a-"}}]"#)
+            .unwrap_err();
+    assert!(error
+        .message
+        .starts_with("invalid regex in `excludePattern`"));
+}
+
+#[test]
+fn test_regex_match_is_validated() {
+    let root = parse_template(r#"Section(match=["^Item\s+7\.", 600, "regex"]) {}"#).unwrap();
+    assert_eq!(
+        root.elements[0].attributes["match"],
+        Value::Array(vec![
+            Value::String(r"^Item\s+7\.".to_string()),
+            Value::Number(600),
+            Value::String("regex".to_string()),
+        ])
+    );
+
+    let error = parse_template(r#"Section(match=["(", 600, "regex"]) {}"#).unwrap_err();
+    assert_eq!(error.element.as_deref(), Some("Section"));
+    assert!(error.message.starts_with("invalid regex in `match`"));
+
+    let error =
+        parse_template(r#"Section(match="Risks", end_match=["Item", 1, "glob"]) {}"#).unwrap_err();
+    assert_eq!(
+        error.message,
+        r#"unknown match mode "glob" in `end_match`, expected "text" or "regex""#
+    );
+}
+
+#[test]
+fn test_font_constraints_are_validated() {
+    let root =
+        parse_template(r#"Section(match="Overview", minFontSize=14.5, bold=true) {}"#).unwrap();
+    assert_eq!(root.elements[0].attributes["bold"], Value::Boolean(true));
+
+    let error = parse_template(r#"Section(match="Overview", minFontSize=0) {}"#).unwrap_err();
+    assert_eq!(error.message, "`minFontSize` must be a positive size");
+
+    let error = parse_template(r#"Section(match="Overview", bold="yes") {}"#).unwrap_err();
+    assert_eq!(error.message, "`bold` must be true or false");
+
+    let error = parse_template(r#"TextChunk(fontName="Arial")"#).unwrap_err();
+    assert!(error
+        .message
+        .starts_with("unknown attribute `fontName` on TextChunk"));
+}
+
+#[test]
+fn test_region_is_validated() {
+    assert!(parse_template("TextChunk(region=[0, 0.5, 1, 1])").is_ok());
+    for region in ["[0, 0, 1]", "[0.5, 0, 0.2, 1]", "[0, 0, 1, 1.5]", "\"top\""] {
+        let error = parse_template(&format!("TextChunk(region={region})")).unwrap_err();
+        assert!(error.message.starts_with("`region` must be"), "{region}");
+    }
+}
+
+#[test]
+fn test_colors_are_validated() {
+    assert!(
+        parse_template(r##"Section(color="#FF0000") { TextChunk(excludeColor="#808080") }"##)
+            .is_ok()
+    );
+    for color in [r#""red""#, r##""#ff00""##, "12"] {
+        let error = parse_template(&format!("TextChunk(excludeColor={color})")).unwrap_err();
+        assert!(
+            error.message.starts_with("`excludeColor` must be a colour"),
+            "{color}"
+        );
+    }
+}
+
+#[test]
+fn test_fragment_arguments_are_validated() {
+    let define = r#"define Sec(p, area, ink) {
+        Section(match="Risk", pageStart=p, color=ink) { TextChunk(region=area) }
+    }
+    "#;
+    let root = parse_template(&format!(
+        r##"{define}use Sec(p=3, area=[0, 0, 0.5, 0.5], ink="#ff0000")"##
+    ))
+    .unwrap();
+    let section = &root.elements[0];
+    assert_eq!(section.attributes["pageStart"], Value::Number(3));
+    assert_eq!(
+        section.attributes["color"],
+        Value::String("#ff0000".to_string())
+    );
+
+    for (arguments, message) in [
+        (
+            r##"p=0, area=[0, 0, 1, 1], ink="#ff0000""##,
+            "`pageStart` must be",
+        ),
+        (
+            r##"p=3, area=[0.5, 0, 0.2, 1], ink="#ff0000""##,
+            "`region` must be",
+        ),
+        (
+            r#"p=3, area=[0, 0, 1, 1], ink="red""#,
+            "`color` must be a colour",
+        ),
+    ] {
+        let error = parse_template(&format!("{define}use Sec({arguments})")).unwrap_err();
+        assert!(error.message.starts_with(message), "{arguments}: {error}");
+    }
+}
@@ -0,0 +1,164 @@
+mod common;
+
+use common::{add_courier_font, build_pdf, finish_pdf, text_ops};
+use delver::layout::{layout_dump, LayoutDump, LineKind, ReadingOrder};
+use delver::parse::{extract_text, get_pdf_text, load_pdf_bytes};
+use lopdf::content::Operation;
+use lopdf::{dictionary, Document, Object};
+
+#[test]
+fn test_extract_text_from_fixture() {
+    let bytes = std::fs::read("tests/example.pdf").unwrap();
+    let text = extract_text(&bytes).unwrap();
+    assert!(text.errors.is_empty());
+    assert_eq!(
+        text.text[&1],
+        vec![
+            "Hello World!",
+            "Subheading 1",
+            "This is the first section text.",
+            "Subheading 2",
+            "This is the second section text.",
+        ]
+    );
+}
+
+#[test]
+fn test_extract_text_orders_lines() {
+    // Drawn bottom-up, with the second line split into two runs drawn right to left
+    let mut first_page = text_ops(10, 72, 600, "Last line.");
+    first_page.extend(text_ops(10, 200, 650, "the middle."));
+    first_page.extend(text_ops(10, 72, 650, "Then"));
+    first_page.extend(text_ops(14, 72, 700, "Title"));
+    let mut doc = build_pdf(vec![first_page, text_ops(10, 72, 700, "Second page.")]);
+    let mut bytes = Vec::new();
+    doc.save_to(&mut bytes).unwrap();
+
+    let text = extract_text(&bytes).unwrap();
+    assert_eq!(
+        text.text[&1],
+        vec!["Title", "Then the middle.", "Last line."]
+    );
+    assert_eq!(text.text[&2], vec!["Second page."]);
+}
+
+#[test]
+fn test_layout_dump_round_trips() {
+    let mut page = text_ops(10, 200, 650, "the middle.");
+    page.extend(text_ops(10, 72, 650, "Then"));
+    page.extend(text_ops(18, 72, 700, "Title"));
+    let mut doc = build_pdf(vec![page]);
+    let mut bytes = Vec::new();
+    doc.save_to(&mut bytes).unwrap();
+    let doc = load_pdf_bytes(&bytes).unwrap();
+    let elements = get_pdf_text(&doc).unwrap();
+
+    let json = serde_json::to_string(&layout_dump(&elements, ReadingOrder::Raster)).unwrap();
+    let dump: LayoutDump = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(dump.elements.len(), 3);
+    let lines: Vec<_> = dump
+        .lines
+        .iter()
+        .map(|l| (l.line.text.as_str(), l.kind))
+        .collect();
+    assert_eq!(
+        lines,
+        vec![
+            ("Title", LineKind::Heading),
+            ("Then the middle.", LineKind::Body)
+        ]
+    );
+    let words: Vec<_> = dump.lines[1]
+        .line
+        .elements
+        .iter()
+        .map(|&i| dump.elements[i].text.as_str())
+        .collect();
+    assert_eq!(words, vec!["Then", "the middle."]);
+    assert_eq!(
+        dump.elements[dump.lines[0].line.elements[0]].font_size,
+        18.0
+    );
+}
+
+#[test]
+fn test_extract_text_reports_page_geometry() {
+    let mut doc = build_pdf(vec![
+        text_ops(10, 72, 700, "Letter."),
+        text_ops(10, 72, 700, "Rotated A4."),
+        text_ops(10, 72, 700, "Cropped."),
+    ]);
+    let pages = doc.get_pages();
+    let a4: Vec<Object> = vec![0.into(), 0.into(), 595.into(), 842.into()];
+    let page = doc.get_dictionary_mut(pages[&2]).unwrap();
+    page.set("MediaBox", a4);
+    page.set("Rotate", -90);
+    let crop: Vec<Object> = vec![36.into(), 36.into(), 576.into(), 756.into()];
+    doc.get_dictionary_mut(pages[&3])
+        .unwrap()
+        .set("CropBox", crop);
+    let mut bytes = Vec::new();
+    doc.save_to(&mut bytes).unwrap();
+
+    let text = extract_text(&bytes).unwrap();
+    let sizes: Vec<_> = text
+        .pages
+        .values()
+        .map(|page| {
+            (
+                page.left,
+                page.bottom,
+                page.width,
+                page.height,
+                page.rotation,
+            )
+        })
+        .collect();
+    assert_eq!(
+        sizes,
+        vec![
+            (0.0, 0.0, 612.0, 792.0, 0),
+            (0.0, 0.0, 595.0, 842.0, 270),
+            (36.0, 36.0, 540.0, 720.0, 0),
+        ]
+    );
+
+    let json: serde_json::Value = serde_json::to_value(&text).unwrap();
+    assert_eq!(json["pages"]["2"]["width"], 595.0);
+    assert_eq!(json["pages"]["2"]["rotation"], 270);
+}
+
+#[test]
+fn test_unsupported_font_encodings_fall_back() {
+    let mut doc = Document::with_version("1.5");
+    let courier_id = add_courier_font(&mut doc);
+    // lopdf reads the name but can't decode it
+    let custom_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "InHouseSerif",
+        "Encoding" => "InHouse-Custom",
+    });
+    let resources = dictionary! {
+        "Font" => dictionary! { "F1" => courier_id, "F2" => custom_id },
+    };
+    let show = |font: &str, y: i64, text: &str| {
+        vec![
+            Operation::new("BT", vec![]),
+            Operation::new("Tf", vec![font.into(), 12.into()]),
+            Operation::new("Td", vec![72.into(), y.into()]),
+            Operation::new("Tj", vec![Object::string_literal(text)]),
+            Operation::new("ET", vec![]),
+        ]
+    };
+    let page = [show("F1", 700, "Readable"), show("F2", 680, "Custom")].concat();
+    let doc = finish_pdf(doc, resources, vec![page]);
+
+    let text: Vec<String> = get_pdf_text(&doc)
+        .unwrap()
+        .into_iter()
+        .map(|mi| mi.text)
+        .collect();
+    assert_eq!(text, vec!["Readable", "Custom"]);
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use common::element;
+use delver::layout::{
+    find_toc_pages, perform_line_matching, rank_candidates, select_best_match,
+    select_best_match_with_toc, TocPolicy, DEFAULT_TOC_PENALTY,
+};
+use delver::parse::TextElement;
+
+/// A two-column contents page listing Item 7, then the real heading on page 30.
+fn filing() -> Vec<TextElement> {
+    let entries = [
+        ("Item 1. Business", "3"),
+        ("Item 1A. Risk Factors", "10"),
+        ("Item 1B. Unresolved Staff Comments", "17"),
+        ("Item 2. Properties", "18"),
+        ("Item 3. Legal Proceedings", "19"),
+        ("Item 5. Market for Common Equity", "24"),
+        ("Item 6. Selected Financial Data", "28"),
+        ("Item 7. Management's Discussion and Analysis", "30"),
+        ("Item 8. Financial Statements", "45"),
+        ("Item 9. Changes in Accountants", "90"),
+    ];
+    let mut elements = vec![element(
+        "TABLE OF CONTENTS",
+        2,
+        "Helvetica",
+        12.0,
+        (250.0, 160.0),
+    )];
+    for (index, (label, page)) in entries.iter().enumerate() {
+        let x = if index < 5 { 72.0 } else { 320.0 };
+        let y = 140.0 - (index % 5) as f32 * 20.0;
+        elements.push(element(label, 2, "Helvetica", 10.0, (x, y)));
+        elements.push(element(page, 2, "Helvetica", 10.0, (x + 200.0, y)));
+    }
+    elements.extend([
+        element(
+            "Item 7. Management's Discussion and Analysis",
+            30,
+            "Helvetica",
+            10.0,
+            (72.0, 700.0),
+        ),
+        element(
+            "Overview of the year.",
+            30,
+            "Helvetica",
+            10.0,
+            (72.0, 680.0),
+        ),
+        element(
+            "Revenue rose in every segment.",
+            30,
+            "Helvetica",
+            10.0,
+            (72.0, 660.0),
+        ),
+    ]);
+    elements
+}
+
+#[test]
+fn test_find_toc_pages() {
+    let pages = find_toc_pages(&filing());
+    assert_eq!(pages.into_iter().collect::<Vec<u32>>(), vec![2]);
+}
+
+#[test]
+fn test_short_pages_are_not_toc() {
+    let elements = vec![
+        element("Total revenue 2015", 5, "Helvetica", 10.0, (72.0, 700.0)),
+        element("Total assets 2014", 5, "Helvetica", 10.0, (72.0, 680.0)),
+    ];
+    assert!(find_toc_pages(&elements).is_empty());
+}
+
+#[test]
+fn test_best_match_skips_toc_entry() {
+    let elements = filing();
+    let matches = perform_line_matching(&elements, "Management's Discussion and Analysis");
+    assert_eq!(matches.len(), 2);
+
+    // The contents entry sits low on the page and wins on position alone
+    assert_eq!(select_best_match(matches.clone()).unwrap().page_number, 2);
+
+    let toc_pages = find_toc_pages(&elements);
+    for policy in [TocPolicy::Penalize(DEFAULT_TOC_PENALTY), TocPolicy::Skip] {
+        let best = select_best_match_with_toc(matches.clone(), &toc_pages, policy).unwrap();
+        assert_eq!(best.page_number, 30);
+    }
+}
+
+#[test]
+fn test_rank_candidates() {
+    let elements = filing();
+    let matches = perform_line_matching(&elements, "Management's Discussion and Analysis");
+    let toc_pages = find_toc_pages(&elements);
+
+    let ranked = rank_candidates(
+        matches.clone(),
+        &elements,
+        &toc_pages,
+        TocPolicy::Penalize(DEFAULT_TOC_PENALTY),
+        &[],
+        &[],
+    );
+    let pages: Vec<u32> = ranked.iter().map(|c| c.element.page_number).collect();
+    assert_eq!(pages, vec![30, 2]);
+    assert_eq!(ranked[0].score, 10.0);
+    assert_eq!(ranked[1].score, 10.0 + 10.0 - DEFAULT_TOC_PENALTY);
+
+    let ranked = rank_candidates(matches, &elements, &toc_pages, TocPolicy::Skip, &[], &[]);
+    assert_eq!(ranked.len(), 1);
+}
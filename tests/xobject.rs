@@ -0,0 +1,194 @@
+mod common;
+
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Document, Object, ObjectId, Stream};
+
+use common::{add_courier_font, build_pdf, finish_pdf, save_temp_pdf, text_ops};
+use delver::parse::{get_pdf_text, load_pdf, TextElement};
+
+fn body_ops() -> Vec<Operation> {
+    let mut ops = text_ops(24, 72, 700, "Item 7. Management Discussion");
+    ops.extend(text_ops(12, 72, 680, "Revenue increased in 2015."));
+    ops
+}
+
+fn add_form(
+    doc: &mut Document,
+    operations: Vec<Operation>,
+    matrix: Option<Vec<Object>>,
+) -> ObjectId {
+    let mut dict = dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Form",
+        "BBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+    };
+    if let Some(matrix) = matrix {
+        dict.set("Matrix", matrix);
+    }
+    let content = Content { operations };
+    doc.add_object(Stream::new(dict, content.encode().unwrap()))
+}
+
+/// Page content is a single `Do` of `/Fm1`, whose XObject resource is `form_id`.
+fn form_pdf(doc: Document, font_id: ObjectId, form_id: ObjectId) -> Document {
+    let resources = dictionary! {
+        "Font" => dictionary! { "F1" => font_id },
+        "XObject" => dictionary! { "Fm1" => form_id },
+    };
+    finish_pdf(
+        doc,
+        resources,
+        vec![vec![Operation::new("Do", vec!["Fm1".into()])]],
+    )
+}
+
+fn summary(elements: &[TextElement]) -> Vec<(String, u32, f32, (f32, f32))> {
+    elements
+        .iter()
+        .map(|e| (e.text.clone(), e.page_number, e.font_size, e.position))
+        .collect()
+}
+
+#[test]
+fn test_form_text_matches_flat_page() {
+    let flat = build_pdf(vec![body_ops()]);
+
+    let mut doc = Document::with_version("1.5");
+    let font_id = add_courier_font(&mut doc);
+    let form_id = add_form(&mut doc, body_ops(), None);
+    let nested = form_pdf(doc, font_id, form_id);
+
+    assert_eq!(
+        summary(&get_pdf_text(&nested).unwrap()),
+        summary(&get_pdf_text(&flat).unwrap())
+    );
+}
+
+#[test]
+fn test_nested_form_matrices_compose() {
+    let mut doc = Document::with_version("1.5");
+    let font_id = add_courier_font(&mut doc);
+    let inner_id = add_form(
+        &mut doc,
+        text_ops(12, 10, 20, "Footer"),
+        Some(vec![
+            2.into(),
+            0.into(),
+            0.into(),
+            2.into(),
+            5.into(),
+            5.into(),
+        ]),
+    );
+    let outer_id = add_form(
+        &mut doc,
+        vec![Operation::new("Do", vec!["Fm2".into()])],
+        Some(vec![
+            1.into(),
+            0.into(),
+            0.into(),
+            1.into(),
+            100.into(),
+            50.into(),
+        ]),
+    );
+    // The outer form declares its own resources, including the inner form.
+    let outer = doc
+        .get_object_mut(outer_id)
+        .unwrap()
+        .as_stream_mut()
+        .unwrap();
+    outer.dict.set(
+        "Resources",
+        dictionary! {
+            "Font" => dictionary! { "F1" => font_id },
+            "XObject" => dictionary! { "Fm2" => inner_id },
+        },
+    );
+    let doc = form_pdf(doc, font_id, outer_id);
+
+    let elements = get_pdf_text(&doc).unwrap();
+    assert_eq!(elements.len(), 1);
+    assert_eq!(elements[0].text, "Footer");
+    // (10, 20) scaled by 2 and shifted by (5, 5), then shifted by (100, 50).
+    assert_eq!(elements[0].position, (125.0, 95.0));
+}
+
+fn translate(x: i64, y: i64) -> Operation {
+    Operation::new(
+        "cm",
+        vec![1.into(), 0.into(), 0.into(), 1.into(), x.into(), y.into()],
+    )
+}
+
+#[test]
+fn test_cm_places_page_text_and_forms() {
+    let mut doc = Document::with_version("1.5");
+    let font_id = add_courier_font(&mut doc);
+    let form_id = add_form(&mut doc, text_ops(12, 10, 20, "Footer"), None);
+    let resources = dictionary! {
+        "Font" => dictionary! { "F1" => font_id },
+        "XObject" => dictionary! { "Fm1" => form_id },
+    };
+    let mut ops = vec![Operation::new("q", vec![]), translate(100, 200)];
+    ops.extend(text_ops(12, 10, 20, "Hello"));
+    ops.push(translate(0, -150));
+    ops.push(Operation::new("Do", vec!["Fm1".into()]));
+    ops.push(Operation::new("Q", vec![]));
+    // Q restores the identity CTM
+    ops.extend(text_ops(12, 10, 20, "After"));
+    let doc = finish_pdf(doc, resources, vec![ops]);
+
+    let positions: Vec<(String, (f32, f32))> = get_pdf_text(&doc)
+        .unwrap()
+        .into_iter()
+        .map(|e| (e.text, e.position))
+        .collect();
+    assert_eq!(
+        positions,
+        vec![
+            ("Hello".to_string(), (110.0, 220.0)),
+            ("Footer".to_string(), (110.0, 70.0)),
+            ("After".to_string(), (10.0, 20.0)),
+        ]
+    );
+}
+
+#[test]
+fn test_self_referencing_form_terminates() {
+    let mut doc = Document::with_version("1.5");
+    let font_id = add_courier_font(&mut doc);
+    let mut ops = text_ops(12, 72, 700, "Loop");
+    ops.push(Operation::new("Do", vec!["Fm1".into()]));
+    let form_id = add_form(&mut doc, ops, None);
+    let doc = form_pdf(doc, font_id, form_id);
+
+    let elements = get_pdf_text(&doc).unwrap();
+    assert_eq!(elements.len(), 16);
+    assert!(elements.iter().all(|e| e.text == "Loop"));
+}
+
+#[test]
+fn test_form_xobjects_survive_filtered_load() {
+    let mut doc = Document::with_version("1.5");
+    let font_id = add_courier_font(&mut doc);
+    let form_id = add_form(&mut doc, body_ops(), None);
+    let mut doc = form_pdf(doc, font_id, form_id);
+    let path = save_temp_pdf(&mut doc, "form-xobject.pdf");
+
+    let loaded = load_pdf(&path).unwrap();
+    let text: Vec<String> = get_pdf_text(&loaded)
+        .unwrap()
+        .into_iter()
+        .map(|e| e.text)
+        .collect();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        text,
+        vec![
+            "Item 7. Management Discussion",
+            "Revenue increased in 2015."
+        ]
+    );
+}